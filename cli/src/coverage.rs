@@ -0,0 +1,65 @@
+use std::fmt;
+
+use cods::Coverage;
+
+use crate::display::display_coverage;
+use crate::style::{Color, LBlue, LRed};
+use crate::{bprintln, Args, ANSI_ESC};
+
+/// Print the annotated sources to stderr and write the lcov file, depending on the arguments.
+/// `files` contains the path, source and coverage of each file. Returns false if the lcov
+/// file couldn't be written.
+pub fn report(args: &Args, files: &[(&str, &str, &Coverage)]) -> bool {
+    if args.coverage {
+        for (path, input, coverage) in files {
+            if files.len() > 1 {
+                eprintln!("{}{path}{ANSI_ESC}", LBlue::bold());
+            }
+            eprint!("{}", display_coverage(coverage, input));
+        }
+    }
+    if let Some(lcov_path) = &args.lcov {
+        let mut buf = String::new();
+        for (path, _, coverage) in files {
+            let _ = write_lcov(&mut buf, path, coverage);
+        }
+        if std::fs::write(lcov_path, buf).is_err() {
+            bprintln!(LRed, "Error writing file: {lcov_path}");
+            return false;
+        }
+    }
+    true
+}
+
+/// Write the coverage of a single source file in the lcov tracefile format.
+pub fn write_lcov(f: &mut impl fmt::Write, path: &str, coverage: &Coverage) -> fmt::Result {
+    writeln!(f, "TN:")?;
+    writeln!(f, "SF:{path}")?;
+
+    let lines = coverage.lines();
+    for (line, hits) in lines.iter() {
+        writeln!(f, "DA:{},{hits}", line + 1)?;
+    }
+    writeln!(f, "LF:{}", lines.len())?;
+    writeln!(f, "LH:{}", lines.iter().filter(|(_, h)| *h > 0).count())?;
+
+    let mut found = 0;
+    let mut hit = 0;
+    for (block, b) in coverage.branches.iter().enumerate() {
+        let line = b.span.start.line + 1;
+        for (branch, hits) in b.hits.iter().enumerate() {
+            found += 1;
+            if *hits > 0 {
+                hit += 1;
+            }
+            match b.reached() {
+                true => writeln!(f, "BRDA:{line},{block},{branch},{hits}")?,
+                false => writeln!(f, "BRDA:{line},{block},{branch},-")?,
+            }
+        }
+    }
+    writeln!(f, "BRF:{found}")?;
+    writeln!(f, "BRH:{hit}")?;
+
+    writeln!(f, "end_of_record")
+}
@@ -0,0 +1,553 @@
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::fmt::Write as _;
+use std::io::{self, BufRead, Write};
+use std::ops::ControlFlow;
+use std::path::Path;
+use std::process::ExitCode;
+use std::rc::Rc;
+
+use cods::{
+    fmt_val, Ast, Context, DataType, DebugInfo, Env, EvalState, Io, Observer, Program, UserFacing,
+    Val, VarInfo,
+};
+
+use crate::json::Json;
+use crate::lsp::{read_message, write_message};
+
+/// There is only ever a single thread being debugged.
+const THREAD_ID: f64 = 1.0;
+
+/// Run a debug adapter that communicates over stdin and stdout.
+pub fn run() -> ExitCode {
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    match serve(stdin.lock(), stdout.lock()) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("Debug adapter error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn serve<'a>(input: impl BufRead + 'a, output: impl Write + 'a) -> io::Result<ExitCode> {
+    let conn = Rc::new(RefCell::new(Connection::new(input, output)));
+    let mut launch = None;
+    let mut breakpoints = HashSet::new();
+
+    loop {
+        let Some(req) = conn.borrow_mut().read_request()? else {
+            return Ok(ExitCode::FAILURE);
+        };
+        let body = match req.command.as_str() {
+            "initialize" => {
+                let capabilities = Json::obj([
+                    ("supportsConfigurationDoneRequest", Json::Bool(true)),
+                    ("supportsTerminateRequest", Json::Bool(true)),
+                ]);
+                let mut conn = conn.borrow_mut();
+                conn.respond(&req, Ok(capabilities))?;
+                conn.event("initialized", Json::obj([]))?;
+                continue;
+            }
+            "launch" => Launch::new(&req.arguments).map(|l| {
+                launch = Some(l);
+                Json::Null
+            }),
+            "setBreakpoints" => Ok(set_breakpoints(&mut breakpoints, &req.arguments)),
+            "configurationDone" => {
+                conn.borrow_mut().respond(&req, Ok(Json::Null))?;
+                break;
+            }
+            "threads" => Ok(threads()),
+            "disconnect" | "terminate" => {
+                conn.borrow_mut().respond(&req, Ok(Json::Null))?;
+                return Ok(ExitCode::SUCCESS);
+            }
+            c => Err(format!("Unsupported request `{c}`")),
+        };
+        conn.borrow_mut().respond(&req, body)?;
+    }
+
+    if let Some(launch) = launch {
+        let exit_code = launch.run(&conn, breakpoints);
+        let mut conn = conn.borrow_mut();
+        if conn.disconnected {
+            return Ok(ExitCode::SUCCESS);
+        }
+        let body = Json::obj([("exitCode", Json::Num(exit_code as f64))]);
+        conn.event("exited", body)?;
+    }
+    conn.borrow_mut().event("terminated", Json::obj([]))?;
+
+    loop {
+        let Some(req) = conn.borrow_mut().read_request()? else {
+            return Ok(ExitCode::SUCCESS);
+        };
+        let body = match req.command.as_str() {
+            "threads" => Ok(Json::obj([("threads", Json::Arr(Vec::new()))])),
+            "disconnect" | "terminate" => {
+                conn.borrow_mut().respond(&req, Ok(Json::Null))?;
+                return Ok(ExitCode::SUCCESS);
+            }
+            _ => Err("The program has terminated".to_owned()),
+        };
+        conn.borrow_mut().respond(&req, body)?;
+    }
+}
+
+struct Request {
+    seq: Json,
+    command: String,
+    arguments: Json,
+}
+
+struct Connection<'a> {
+    input: Box<dyn BufRead + 'a>,
+    output: Box<dyn Write + 'a>,
+    seq: u32,
+    /// Set once the client requested to end the session.
+    disconnected: bool,
+}
+
+impl<'a> Connection<'a> {
+    fn new(input: impl BufRead + 'a, output: impl Write + 'a) -> Self {
+        Self {
+            input: Box::new(input),
+            output: Box::new(output),
+            seq: 0,
+            disconnected: false,
+        }
+    }
+
+    fn read_request(&mut self) -> io::Result<Option<Request>> {
+        let Some(msg) = read_message(&mut self.input)? else {
+            return Ok(None);
+        };
+        let msg = Json::parse(&msg).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let command = msg
+            .get("command")
+            .and_then(Json::as_str)
+            .unwrap_or_default();
+        Ok(Some(Request {
+            seq: msg.get("seq").cloned().unwrap_or(Json::Null),
+            command: command.to_owned(),
+            arguments: msg.get("arguments").cloned().unwrap_or(Json::Null),
+        }))
+    }
+
+    fn respond(&mut self, req: &Request, body: Result<Json, String>) -> io::Result<()> {
+        if matches!(req.command.as_str(), "disconnect" | "terminate") {
+            self.disconnected = true;
+        }
+        let (success, key, val) = match body {
+            Ok(b) => (true, "body", b),
+            Err(e) => (false, "message", Json::Str(e)),
+        };
+        let msg = Json::obj([
+            ("seq", self.next_seq()),
+            ("type", Json::str("response")),
+            ("request_seq", req.seq.clone()),
+            ("success", Json::Bool(success)),
+            ("command", Json::str(&req.command)),
+            (key, val),
+        ]);
+        self.send(&msg)
+    }
+
+    fn event(&mut self, event: &str, body: Json) -> io::Result<()> {
+        let msg = Json::obj([
+            ("seq", self.next_seq()),
+            ("type", Json::str("event")),
+            ("event", Json::str(event)),
+            ("body", body),
+        ]);
+        self.send(&msg)
+    }
+
+    fn next_seq(&mut self) -> Json {
+        self.seq += 1;
+        Json::Num(self.seq as f64)
+    }
+
+    fn send(&mut self, msg: &Json) -> io::Result<()> {
+        write_message(&mut self.output, msg)?;
+        self.output.flush()
+    }
+}
+
+/// Forwards the output of the script to the client.
+struct OutputWriter<'a> {
+    conn: Rc<RefCell<Connection<'a>>>,
+    category: &'static str,
+}
+
+impl Write for OutputWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let body = Json::obj([
+            ("category", Json::str(self.category)),
+            ("output", Json::str(String::from_utf8_lossy(buf))),
+        ]);
+        self.conn.borrow_mut().event("output", body)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+struct Launch {
+    path: String,
+    program: Program,
+    args: Vec<String>,
+    stop_on_entry: bool,
+}
+
+impl Launch {
+    fn new(arguments: &Json) -> Result<Self, String> {
+        let Some(path) = arguments.get("program").and_then(Json::as_str) else {
+            return Err("Missing `program` path".to_owned());
+        };
+        let input = std::fs::read_to_string(path)
+            .map_err(|e| format!("Error reading file `{path}`: {e}"))?;
+
+        let mut ctx = Context::default();
+        let program = ctx.compile(&input).map_err(|e| error_message(path, &e))?;
+
+        let args = arguments
+            .get("args")
+            .and_then(Json::as_arr)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|a| a.as_str().map(str::to_owned))
+            .collect();
+        let stop_on_entry = arguments.get("stopOnEntry") == Some(&Json::Bool(true));
+
+        Ok(Self {
+            path: path.to_owned(),
+            program,
+            args,
+            stop_on_entry,
+        })
+    }
+
+    /// Evaluate the program under the debugger and return its exit code.
+    fn run(&self, conn: &Rc<RefCell<Connection<'_>>>, breakpoints: HashSet<u32>) -> u8 {
+        let step = match self.stop_on_entry {
+            true => Step::Entry,
+            false => Step::Continue,
+        };
+        let debugger = Debugger {
+            conn: Rc::clone(conn),
+            debug: &self.program.debug,
+            path: &self.path,
+            stepper: Stepper::new(breakpoints, step),
+        };
+        let stdout = OutputWriter {
+            conn: Rc::clone(conn),
+            category: "stdout",
+        };
+        let mut stderr = OutputWriter {
+            conn: Rc::clone(conn),
+            category: "stderr",
+        };
+        let mut env = Env::new(Io::new(io::empty(), stdout));
+        env.observer = Some(Box::new(debugger));
+        env.args = self.args.clone();
+
+        let mut stack = self.program.stack();
+        match self.program.eval_with_env(&mut stack, &mut env) {
+            Ok(_) => env.exit_code.unwrap_or(0),
+            Err(e) => {
+                let mut msg = format!("{}\n", error_message(&self.path, &e));
+                for t in env.trace.iter() {
+                    let pos = t.span.start;
+                    let _ = writeln!(
+                        msg,
+                        "    in `{}`, called from {}:{}:{}",
+                        t.name,
+                        self.path,
+                        pos.line + 1,
+                        pos.col + 1
+                    );
+                }
+                let _ = stderr.write_all(msg.as_bytes());
+                1
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Step {
+    /// Stop before the first statement.
+    Entry,
+    /// Only stop at breakpoints.
+    Continue,
+    /// Stop at the next line.
+    In,
+    /// Stop at the next line in a frame at or below the depth.
+    Over(usize),
+    /// Stop at the next line in a frame below the depth.
+    Out(usize),
+}
+
+/// Why execution stopped before a statement.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum StopReason {
+    Entry,
+    Step,
+    Breakpoint,
+}
+
+impl StopReason {
+    /// The reason of a DAP `stopped` event.
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Entry => "entry",
+            Self::Step => "step",
+            Self::Breakpoint => "breakpoint",
+        }
+    }
+}
+
+/// Decides before which statements execution stops, shared with the terminal debugger.
+pub(crate) struct Stepper {
+    /// 0-indexed lines.
+    pub breakpoints: HashSet<u32>,
+    pub step: Step,
+    /// The line and call depth of the previous statement, breakpoints and steps only stop
+    /// when a new line is entered.
+    prev: Option<(u32, usize)>,
+}
+
+impl Stepper {
+    pub fn new(breakpoints: HashSet<u32>, step: Step) -> Self {
+        Self {
+            breakpoints,
+            step,
+            prev: None,
+        }
+    }
+
+    /// The reason to stop before a statement starting at `line`, evaluated with `depth` calls
+    /// on the stack.
+    pub fn stop_reason(&mut self, line: u32, depth: usize) -> Option<StopReason> {
+        let entered = self.prev != Some((line, depth));
+        self.prev = Some((line, depth));
+
+        match self.step {
+            Step::Entry => Some(StopReason::Entry),
+            Step::In if entered => Some(StopReason::Step),
+            Step::Over(d) if entered && depth <= d => Some(StopReason::Step),
+            Step::Out(d) if depth < d => Some(StopReason::Step),
+            _ if entered && self.breakpoints.contains(&line) => Some(StopReason::Breakpoint),
+            _ => None,
+        }
+    }
+}
+
+struct Debugger<'a, 'c> {
+    conn: Rc<RefCell<Connection<'c>>>,
+    debug: &'a DebugInfo,
+    path: &'a str,
+    stepper: Stepper,
+}
+
+impl Observer for Debugger<'_, '_> {
+    fn statement(&mut self, state: &EvalState<'_>, ast: &Ast) -> ControlFlow<u8> {
+        let depth = state.calls.len();
+        let Some(reason) = self.stepper.stop_reason(ast.span.start.line, depth) else {
+            return ControlFlow::Continue(());
+        };
+        match self.pause(state, reason) {
+            Ok(flow) => flow,
+            Err(_) => ControlFlow::Break(1),
+        }
+    }
+}
+
+impl Debugger<'_, '_> {
+    /// Handle requests until the client resumes execution.
+    fn pause(&mut self, state: &EvalState<'_>, reason: StopReason) -> io::Result<ControlFlow<u8>> {
+        let depth = state.calls.len();
+        let body = Json::obj([
+            ("reason", Json::str(reason.as_str())),
+            ("threadId", Json::Num(THREAD_ID)),
+            ("allThreadsStopped", Json::Bool(true)),
+        ]);
+        self.conn.borrow_mut().event("stopped", body)?;
+
+        loop {
+            let Some(req) = self.conn.borrow_mut().read_request()? else {
+                return Ok(ControlFlow::Break(1));
+            };
+            let step = match req.command.as_str() {
+                "continue" => Some(Step::Continue),
+                "next" => Some(Step::Over(depth)),
+                "stepIn" => Some(Step::In),
+                "stepOut" => Some(Step::Out(depth)),
+                _ => None,
+            };
+            if let Some(step) = step {
+                self.stepper.step = step;
+                let body = match step {
+                    Step::Continue => Json::obj([("allThreadsContinued", Json::Bool(true))]),
+                    _ => Json::Null,
+                };
+                self.conn.borrow_mut().respond(&req, Ok(body))?;
+                return Ok(ControlFlow::Continue(()));
+            }
+
+            let body = match req.command.as_str() {
+                "threads" => Ok(threads()),
+                "stackTrace" => Ok(self.stack_trace(state)),
+                "scopes" => scopes(state, &req.arguments),
+                "variables" => self.variables(state, &req.arguments),
+                "setBreakpoints" => Ok(set_breakpoints(
+                    &mut self.stepper.breakpoints,
+                    &req.arguments,
+                )),
+                "disconnect" | "terminate" => {
+                    self.conn.borrow_mut().respond(&req, Ok(Json::Null))?;
+                    return Ok(ControlFlow::Break(0));
+                }
+                c => Err(format!("Unsupported request `{c}`")),
+            };
+            self.conn.borrow_mut().respond(&req, body)?;
+        }
+    }
+
+    fn stack_trace(&self, state: &EvalState<'_>) -> Json {
+        let name = Path::new(self.path)
+            .file_name()
+            .map_or(self.path.into(), |n| n.to_string_lossy());
+        let source = Json::obj([("name", Json::str(name)), ("path", Json::str(self.path))]);
+
+        let frames = (0..state.calls.len())
+            .rev()
+            .map(|i| {
+                let pos = state.calls[i].span.start;
+                Json::obj([
+                    ("id", Json::Num(i as f64)),
+                    ("name", Json::str(state.fun_name(i))),
+                    ("source", source.clone()),
+                    ("line", Json::Num(pos.line as f64 + 1.0)),
+                    ("column", Json::Num(pos.col as f64 + 1.0)),
+                ])
+            })
+            .collect();
+        Json::obj([
+            ("stackFrames", Json::Arr(frames)),
+            ("totalFrames", Json::Num(state.calls.len() as f64)),
+        ])
+    }
+
+    /// Variable references are the index of the call frame plus one, since `0` means the
+    /// variable has no children.
+    fn variables(&self, state: &EvalState<'_>, arguments: &Json) -> Result<Json, String> {
+        let index = arguments
+            .get("variablesReference")
+            .and_then(Json::as_u32)
+            .and_then(|r| (r as usize).checked_sub(1))
+            .filter(|i| *i < state.calls.len())
+            .ok_or("Invalid `variablesReference`")?;
+
+        let vars = live_vars(self.debug, state, index)
+            .into_iter()
+            .map(|(v, val)| {
+                Json::obj([
+                    ("name", Json::str(&v.name)),
+                    ("value", Json::str(fmt_var(val))),
+                    ("type", Json::str(v.data_type.to_string())),
+                    ("variablesReference", Json::Num(0.0)),
+                ])
+            })
+            .collect();
+        Ok(Json::obj([("variables", Json::Arr(vars))]))
+    }
+}
+
+fn scopes(state: &EvalState<'_>, arguments: &Json) -> Result<Json, String> {
+    let index = arguments
+        .get("frameId")
+        .and_then(Json::as_u32)
+        .map(|i| i as usize)
+        .filter(|i| *i < state.calls.len())
+        .ok_or("Invalid `frameId`")?;
+
+    let scope = |name: &str, index: usize| {
+        Json::obj([
+            ("name", Json::str(name)),
+            ("variablesReference", Json::Num(index as f64 + 1.0)),
+            ("expensive", Json::Bool(false)),
+        ])
+    };
+    let mut scopes = Vec::new();
+    if state.calls[index].fun.is_some() {
+        scopes.push(scope("Locals", index));
+    }
+    if state.calls[0].fun.is_none() {
+        scopes.push(scope("Globals", 0));
+    }
+    Ok(Json::obj([("scopes", Json::Arr(scopes))]))
+}
+
+fn threads() -> Json {
+    let thread = Json::obj([("id", Json::Num(THREAD_ID)), ("name", Json::str("main"))]);
+    Json::obj([("threads", Json::Arr(vec![thread]))])
+}
+
+fn set_breakpoints(breakpoints: &mut HashSet<u32>, arguments: &Json) -> Json {
+    breakpoints.clear();
+    let lines = arguments
+        .get("breakpoints")
+        .and_then(Json::as_arr)
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|b| b.get("line").and_then(Json::as_u32))
+        .filter(|l| *l > 0);
+
+    let mut verified = Vec::new();
+    for l in lines {
+        breakpoints.insert(l - 1);
+        verified.push(Json::obj([
+            ("verified", Json::Bool(true)),
+            ("line", Json::Num(l as f64)),
+        ]));
+    }
+    Json::obj([("breakpoints", Json::Arr(verified))])
+}
+
+fn error_message(path: &str, error: &cods::Error) -> String {
+    match error.spans().first() {
+        Some(s) => format!("{path}:{}:{}: {error}", s.start.line + 1, s.start.col + 1),
+        None => format!("{path}: {error}"),
+    }
+}
+
+/// The variables that are live in the call at `index` together with their values, which are
+/// `None` if the variable hasn't been initialized yet.
+pub(crate) fn live_vars<'a>(
+    debug: &'a DebugInfo,
+    state: &EvalState<'a>,
+    index: usize,
+) -> Vec<(&'a VarInfo, Option<&'a Val>)> {
+    let call = state.calls[index];
+    let frame = state.frame(index);
+    debug
+        .live_vars(call.fun, call.span.start)
+        .into_iter()
+        .filter_map(|v| match frame.get(v.slot())? {
+            Val::Unit if v.data_type != DataType::Unit => Some((v, None)),
+            val => Some((v, Some(val))),
+        })
+        .collect()
+}
+
+/// Format the value of a variable, which might not be initialized yet.
+pub(crate) fn fmt_var(val: Option<&Val>) -> String {
+    val.map_or_else(|| "<uninitialized>".to_owned(), fmt_val)
+}
@@ -0,0 +1,230 @@
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::ops::ControlFlow;
+use std::process::ExitCode;
+use std::rc::Rc;
+
+use cods::{fmt_val, Ast, Checker, Context, DebugInfo, Env, EvalState, Io, Observer, Stack};
+
+use crate::dap::{fmt_var, live_vars, Step, Stepper, StopReason};
+use crate::display::{display_span, DisplayUserFacing};
+use crate::style::{Color, LBlue, LGreen, LRed};
+use crate::{bprint, bprintln, print_eval_with, Args, State, ANSI_ESC};
+
+const HELP: &str = "\
+Commands:
+    b, break <line>     Set a breakpoint
+    d, delete <line>    Remove a breakpoint
+    s, step             Step to the next line, entering function calls
+    n, next             Step to the next line in the current function
+    f, finish           Continue until the current function returns
+    c, continue         Continue until the next breakpoint
+    p, print <expr>     Evaluate an expression in the current frame
+    l, locals           Show the variables of the current frame
+    bt, backtrace       Show the call stack
+    h, help             Show this help message
+    q, quit             Stop the program and exit";
+
+type SharedInput = Rc<RefCell<Box<dyn BufRead>>>;
+
+/// Run a script under a debugger that reads commands from stdin.
+pub fn run(args: &Args, path: &str) -> ExitCode {
+    let Ok(input) = std::fs::read_to_string(path) else {
+        bprintln!(LRed, "Error reading file: {path}");
+        return ExitCode::FAILURE;
+    };
+
+    bprintln!(
+        LBlue,
+        "Debugging {path}, type `help` for a list of commands"
+    );
+    let stdin: SharedInput = Rc::new(RefCell::new(Box::new(BufReader::new(io::stdin()))));
+    let mut state = State::new(args);
+    let res = print_eval_with(&mut state, &input, args, |state, _, env| {
+        let debugger = Debugger {
+            input: Rc::clone(&stdin),
+            source: input.clone(),
+            debug: state.checker.debug.clone(),
+            stepper: Stepper::new(HashSet::new(), Step::Entry),
+        };
+        env.observer = Some(Box::new(debugger));
+        // Only buffer a single byte, so the script never consumes input meant for the debugger.
+        let script_stdin = BufReader::with_capacity(1, ScriptInput(Rc::clone(&stdin)));
+        env.io.stdin = Box::new(script_stdin);
+    });
+    match res {
+        Some(_) => state.exit_code(),
+        None => ExitCode::FAILURE,
+    }
+}
+
+/// The stdin of the script, which is shared with the debugger.
+struct ScriptInput(SharedInput);
+
+impl Read for ScriptInput {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.borrow_mut().read(buf)
+    }
+}
+
+struct Debugger {
+    input: SharedInput,
+    source: String,
+    debug: DebugInfo,
+    stepper: Stepper,
+}
+
+impl Observer for Debugger {
+    fn statement(&mut self, state: &EvalState<'_>, ast: &Ast) -> ControlFlow<u8> {
+        let depth = state.calls.len();
+        let Some(reason) = self.stepper.stop_reason(ast.span.start.line, depth) else {
+            return ControlFlow::Continue(());
+        };
+
+        let name = state.fun_name(depth - 1);
+        match reason {
+            StopReason::Breakpoint => bprintln!(LBlue, "Breakpoint hit in `{name}`"),
+            StopReason::Entry | StopReason::Step => bprintln!(LBlue, "Stopped in `{name}`"),
+        }
+        print!("{}", display_span::<LGreen>(ast.span, &self.source));
+        self.prompt(state)
+    }
+}
+
+impl Debugger {
+    /// Handle commands until execution is resumed.
+    fn prompt(&mut self, state: &EvalState<'_>) -> ControlFlow<u8> {
+        let depth = state.calls.len();
+        let mut line = String::new();
+        loop {
+            bprint!(LBlue, "(cods) ");
+            let _ = io::stdout().flush();
+
+            line.clear();
+            match self.input.borrow_mut().read_line(&mut line) {
+                Ok(0) | Err(_) => {
+                    println!();
+                    return ControlFlow::Break(0);
+                }
+                Ok(_) => (),
+            }
+            let (cmd, arg) = match line.trim().split_once(' ') {
+                Some((c, a)) => (c, a.trim()),
+                None => (line.trim(), ""),
+            };
+
+            let step = match cmd {
+                "" => continue,
+                "s" | "step" => Step::In,
+                "n" | "next" => Step::Over(depth),
+                "f" | "finish" => Step::Out(depth),
+                "c" | "continue" => Step::Continue,
+                "q" | "quit" => return ControlFlow::Break(0),
+                "b" | "break" => {
+                    if let Some(l) = parse_line(arg) {
+                        self.stepper.breakpoints.insert(l - 1);
+                        bprintln!(LBlue, "Breakpoint set at line {l}");
+                    }
+                    continue;
+                }
+                "d" | "delete" => {
+                    if let Some(l) = parse_line(arg) {
+                        match self.stepper.breakpoints.remove(&(l - 1)) {
+                            true => bprintln!(LBlue, "Breakpoint removed from line {l}"),
+                            false => bprintln!(LRed, "No breakpoint at line {l}"),
+                        }
+                    }
+                    continue;
+                }
+                "p" | "print" => {
+                    self.print(state, arg);
+                    continue;
+                }
+                "l" | "locals" => {
+                    self.locals(state);
+                    continue;
+                }
+                "bt" | "backtrace" => {
+                    self.backtrace(state);
+                    continue;
+                }
+                "h" | "help" => {
+                    println!("{HELP}");
+                    continue;
+                }
+                c => {
+                    bprintln!(
+                        LRed,
+                        "Unknown command `{c}`, type `help` for a list of commands"
+                    );
+                    continue;
+                }
+            };
+            self.stepper.step = step;
+            return ControlFlow::Continue(());
+        }
+    }
+
+    /// Evaluate the expression with the live variables of the current frame defined as
+    /// globals. Assignments to them don't affect the debugged program.
+    fn print(&self, state: &EvalState<'_>, expr: &str) {
+        let mut ctx = Context::default();
+        let mut checker = Checker::default();
+        let mut stack = Stack::default();
+
+        let current = state.calls.len() - 1;
+        let mut vars = Vec::new();
+        if current > 0 && state.calls[0].fun.is_none() {
+            vars.extend(live_vars(&self.debug, state, 0));
+        }
+        vars.extend(live_vars(&self.debug, state, current));
+        for (v, val) in vars {
+            if let Some(val) = val {
+                ctx.def_global(&mut checker, &mut stack, &v.name, val.clone(), false);
+            }
+        }
+
+        let res = ctx.compile_with(checker, expr).and_then(|program| {
+            let mut env = Env::new(Io::new(io::empty(), io::stdout()));
+            program.eval_with_env(&mut stack, &mut env)
+        });
+        match res {
+            Ok(v) => println!("{}", fmt_val(&v)),
+            Err(e) => println!("{}", e.display(expr)),
+        }
+    }
+
+    fn locals(&self, state: &EvalState<'_>) {
+        let vars = live_vars(&self.debug, state, state.calls.len() - 1);
+        if vars.is_empty() {
+            bprintln!(LBlue, "No variables");
+        }
+        for (v, val) in vars {
+            println!("{}: {} = {}", v.name, v.data_type, fmt_var(val));
+        }
+    }
+
+    fn backtrace(&self, state: &EvalState<'_>) {
+        for i in (0..state.calls.len()).rev() {
+            let pos = state.calls[i].span.start;
+            println!(
+                "#{} `{}` at {}:{}",
+                state.calls.len() - 1 - i,
+                state.fun_name(i),
+                pos.line + 1,
+                pos.col + 1
+            );
+        }
+    }
+}
+
+fn parse_line(arg: &str) -> Option<u32> {
+    match arg.parse::<u32>() {
+        Ok(l) if l > 0 => Some(l),
+        _ => {
+            bprintln!(LRed, "Expected a line number, found `{arg}`");
+            None
+        }
+    }
+}
@@ -2,10 +2,10 @@ use std::fmt::Write;
 use std::fmt::{self, Display};
 use std::marker::PhantomData;
 
-use cods::UserFacing;
+use cods::{Coverage, Span, TraceFrame, UserFacing};
 use unicode_width::UnicodeWidthChar;
 
-use crate::style::{LRed, LYellow};
+use crate::style::{LGreen, LRed, LYellow};
 use crate::{Color, LBlue, ANSI_ESC};
 
 impl DisplayUserFacing<LRed> for cods::Error {}
@@ -124,6 +124,71 @@ impl<U: DisplayUserFacing<C>, C: Color> Display for FmtUserFacing<'_, U, C> {
     }
 }
 
+/// Display the call sites of a runtime error's stack trace.
+pub fn display_trace<'a>(trace: &'a [TraceFrame], input: &'a str) -> FmtTrace<'a> {
+    FmtTrace { input, trace }
+}
+
+pub struct FmtTrace<'a> {
+    input: &'a str,
+    trace: &'a [TraceFrame],
+}
+
+impl Display for FmtTrace<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for frame in self.trace {
+            writeln!(
+                f,
+                "{spc:nr_w$} {blue}= in `{name}`, called from:{esc}",
+                spc = ' ',
+                nr_w = line_nr_width(frame.span.start.line + 1),
+                name = frame.name,
+                blue = LBlue::bold(),
+                esc = ANSI_ESC,
+            )?;
+            write!(f, "{}", display_span::<LBlue>(frame.span, self.input))?;
+        }
+        Ok(())
+    }
+}
+
+/// Display the first line of a span with the span marked, used to show a location that isn't
+/// an error.
+pub fn display_span<C: Color>(span: Span, input: &str) -> FmtSpan<'_, C> {
+    FmtSpan {
+        input,
+        span,
+        c: PhantomData,
+    }
+}
+
+pub struct FmtSpan<'a, C: Color> {
+    input: &'a str,
+    span: Span,
+    c: PhantomData<C>,
+}
+
+impl<C: Color> Display for FmtSpan<'_, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let nr = self.span.start.line;
+        let Some(line) = self.input.lines().nth(nr as usize) else {
+            return Ok(());
+        };
+        let end = match self.span.end.line == nr {
+            true => self.span.end.col,
+            false => line.chars().count() as u32,
+        };
+
+        let nr_width = line_nr_width(nr + 1);
+        print_line(f, nr + 1, nr_width, line)?;
+        mark_spans::<C>(f, nr_width, line, &[(self.span.start.col, end)])
+    }
+}
+
+fn line_nr_width(nr: u32) -> usize {
+    (nr as f64).log10() as usize + 1
+}
+
 fn print_line(
     f: &mut fmt::Formatter<'_>,
     line_nr: u32,
@@ -201,3 +266,69 @@ fn mark_error<C: Color>(f: &mut fmt::Formatter<'_>, n: usize) -> fmt::Result {
     }
     f.write_str(ANSI_ESC)
 }
+
+/// Display the source annotated with the hit count of each line and the branches taken,
+/// followed by a summary.
+pub fn display_coverage<'a>(coverage: &'a Coverage, input: &'a str) -> FmtCoverage<'a> {
+    FmtCoverage { input, coverage }
+}
+
+pub struct FmtCoverage<'a> {
+    input: &'a str,
+    coverage: &'a Coverage,
+}
+
+impl Display for FmtCoverage<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let lines = self.coverage.lines();
+        let mut hits = lines.iter().peekable();
+        let mut branches = self.coverage.branches.iter().peekable();
+        let hits_w = lines
+            .iter()
+            .map(|(_, h)| h.to_string().len())
+            .max()
+            .unwrap_or(1);
+
+        for (nr, line) in self.input.lines().enumerate() {
+            let nr = nr as u32;
+            match hits.next_if(|(l, _)| *l == nr) {
+                Some((_, 0)) => write!(f, "{}{:>hits_w$}{ANSI_ESC}", LRed::bold(), 0)?,
+                Some((_, h)) => write!(f, "{}{h:>hits_w$}{ANSI_ESC}", LGreen::normal())?,
+                None => write!(f, "{:hits_w$}", "")?,
+            }
+            write!(f, " {}│{ANSI_ESC} {line}", LBlue::bold())?;
+
+            while let Some(b) = branches.next_if(|b| b.span.start.line == nr) {
+                let taken = b.hits.iter().map(u32::to_string).collect::<Vec<_>>();
+                let color = match b.hits.contains(&0) {
+                    true => LYellow::normal(),
+                    false => LGreen::normal(),
+                };
+                write!(f, "  {color}[branches: {}]{ANSI_ESC}", taken.join(", "))?;
+            }
+            writeln!(f)?;
+        }
+
+        let lines_hit = lines.iter().filter(|(_, h)| *h > 0).count();
+        let branch_count = self.coverage.branches.iter().map(|b| b.hits.len()).sum();
+        let branches_hit = (self.coverage.branches.iter())
+            .flat_map(|b| b.hits.iter())
+            .filter(|h| **h > 0)
+            .count();
+        writeln!(f)?;
+        write_ratio(f, "Lines:   ", lines_hit, lines.len())?;
+        write_ratio(f, "Branches:", branches_hit, branch_count)
+    }
+}
+
+fn write_ratio(f: &mut fmt::Formatter<'_>, name: &str, hit: usize, total: usize) -> fmt::Result {
+    let percent = match total {
+        0 => 100.0,
+        _ => hit as f64 / total as f64 * 100.0,
+    };
+    writeln!(
+        f,
+        "{blue}{name}{ANSI_ESC} {hit}/{total} ({percent:.1}%)",
+        blue = LBlue::bold()
+    )
+}
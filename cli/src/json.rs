@@ -1,6 +1,8 @@
-use std::fmt;
+use std::fmt::{self, Write as _};
 
-use cods::{Error, Pos, Span, UserFacing, Warning};
+use cods::{Error, Pos, Span, TraceFrame, UserFacing, Warning};
+
+use crate::test_runner::{FileReport, Outcome};
 
 pub fn write_diagnostics(
     f: &mut impl fmt::Write,
@@ -26,8 +28,104 @@ pub fn write_diagnostics(
     write!(f, "]}}")
 }
 
+/// Write a runtime error together with its stack trace, in the same shape as
+/// [`write_diagnostics`].
+pub fn write_runtime_error(
+    f: &mut impl fmt::Write,
+    error: &Error,
+    trace: &[TraceFrame],
+) -> fmt::Result {
+    write!(f, "{{\"errors\":[{{")?;
+    write_userfacing_members(f, error)?;
+    write!(f, ",\"trace\":[")?;
+    if let Some((first, others)) = trace.split_first() {
+        write_trace_frame(f, first)?;
+        for t in others {
+            f.write_char(',')?;
+            write_trace_frame(f, t)?;
+        }
+    }
+    write!(f, "]}}],\"warnings\":[]}}")
+}
+
+/// Write the results of `cods test`.
+pub fn write_test_reports(f: &mut impl fmt::Write, reports: &[FileReport]) -> fmt::Result {
+    let mut passed = 0;
+    let mut failed = 0;
+    write!(f, "{{\"tests\":[")?;
+    let tests = reports
+        .iter()
+        .flat_map(|r| r.tests.iter().map(move |t| (r, t)));
+    for (i, (report, test)) in tests.enumerate() {
+        if i > 0 {
+            f.write_char(',')?;
+        }
+        match test.outcome.passed() {
+            true => passed += 1,
+            false => failed += 1,
+        }
+        write!(
+            f,
+            "{{\"path\":{},\"name\":{},\"passed\":{},\"output\":{}",
+            Json::str(&report.path),
+            Json::str(&test.name),
+            test.outcome.passed(),
+            Json::str(&test.output),
+        )?;
+        match &test.outcome {
+            Outcome::Passed => (),
+            Outcome::Error(e, trace) => {
+                write!(f, ",\"error\":{{")?;
+                write_userfacing_members(f, e)?;
+                write!(f, ",\"trace\":[")?;
+                if let Some((first, others)) = trace.split_first() {
+                    write_trace_frame(f, first)?;
+                    for t in others {
+                        f.write_char(',')?;
+                        write_trace_frame(f, t)?;
+                    }
+                }
+                write!(f, "]}}")?;
+            }
+            Outcome::Exit(code) => write!(f, ",\"exit_code\":{code}")?,
+        }
+        f.write_char('}')?;
+    }
+
+    write!(f, "],\"errors\":[")?;
+    let errors = reports
+        .iter()
+        .flat_map(|r| r.errors.iter().map(move |e| (r, e)));
+    for (i, (report, error)) in errors.enumerate() {
+        if i > 0 {
+            f.write_char(',')?;
+        }
+        write!(f, "{{\"path\":{},", Json::str(&report.path))?;
+        write_userfacing_members(f, error)?;
+        f.write_char('}')?;
+    }
+
+    let filtered = reports.iter().map(|r| r.filtered).sum::<usize>();
+    write!(
+        f,
+        "],\"passed\":{passed},\"failed\":{failed},\"filtered\":{filtered}}}"
+    )
+}
+
+fn write_trace_frame(f: &mut impl fmt::Write, t: &TraceFrame) -> fmt::Result {
+    write!(f, "{{\"name\":{},\"span\":", Json::str(&t.name))?;
+    write_span(f, &t.span)?;
+    f.write_char('}')
+}
+
 fn write_userfacing(f: &mut impl fmt::Write, w: &impl UserFacing) -> fmt::Result {
-    write!(f, "{{\"spans\":[")?;
+    f.write_char('{')?;
+    write_userfacing_members(f, w)?;
+    f.write_char('}')
+}
+
+fn write_userfacing_members(f: &mut impl fmt::Write, w: &impl UserFacing) -> fmt::Result {
+    write!(f, "\"spans\":[")?;
     let spans = w.spans();
     if let Some((first, others)) = spans.split_first() {
         write_span(f, first)?;
@@ -36,9 +134,10 @@ fn write_userfacing(f: &mut impl fmt::Write, w: &impl UserFacing) -> fmt::Result
             write_span(f, s)?;
         }
     }
-    write!(f, "],\"desc\":\"")?;
-    w.description(f, "", "")?;
-    write!(f, "\"}}")
+    let mut desc = String::new();
+    w.description(&mut desc, "", "")?;
+    write!(f, "],\"desc\":")?;
+    write_str(f, &desc)
 }
 
 fn write_span(f: &mut impl fmt::Write, s: &Span) -> fmt::Result {
@@ -52,3 +151,267 @@ fn write_span(f: &mut impl fmt::Write, s: &Span) -> fmt::Result {
 fn write_pos(f: &mut impl fmt::Write, p: Pos) -> fmt::Result {
     write!(f, "{{\"col\":{},\"line\":{}}}", p.col, p.line)
 }
+
+/// A JSON value, object members keep their insertion order.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Num(f64),
+    Str(String),
+    Arr(Vec<Json>),
+    Obj(Vec<(String, Json)>),
+}
+
+impl Json {
+    pub fn obj<const N: usize>(members: [(&str, Json); N]) -> Self {
+        Self::Obj(
+            members
+                .into_iter()
+                .map(|(k, v)| (k.to_owned(), v))
+                .collect(),
+        )
+    }
+
+    pub fn str(s: impl Into<String>) -> Self {
+        Self::Str(s.into())
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Self::Obj(members) => members.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    /// Follow a path of object keys.
+    pub fn path(&self, keys: &[&str]) -> Option<&Json> {
+        keys.iter().try_fold(self, |v, k| v.get(k))
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::Str(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_u32(&self) -> Option<u32> {
+        match self {
+            Self::Num(n) if *n >= 0.0 && *n <= u32::MAX as f64 => Some(*n as u32),
+            _ => None,
+        }
+    }
+
+    pub fn as_arr(&self) -> Option<&[Json]> {
+        match self {
+            Self::Arr(a) => Some(a),
+            _ => None,
+        }
+    }
+
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let mut parser = JsonParser {
+            chars: input.chars().peekable(),
+        };
+        let val = parser.value()?;
+        parser.whitespace();
+        match parser.chars.next() {
+            Some(c) => Err(format!("Unexpected trailing character `{c}`")),
+            None => Ok(val),
+        }
+    }
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Null => write!(f, "null"),
+            Self::Bool(b) => write!(f, "{b}"),
+            Self::Num(n) => write!(f, "{n}"),
+            Self::Str(s) => write_str(f, s),
+            Self::Arr(a) => {
+                f.write_char('[')?;
+                for (i, v) in a.iter().enumerate() {
+                    if i > 0 {
+                        f.write_char(',')?;
+                    }
+                    write!(f, "{v}")?;
+                }
+                f.write_char(']')
+            }
+            Self::Obj(members) => {
+                f.write_char('{')?;
+                for (i, (k, v)) in members.iter().enumerate() {
+                    if i > 0 {
+                        f.write_char(',')?;
+                    }
+                    write_str(f, k)?;
+                    write!(f, ":{v}")?;
+                }
+                f.write_char('}')
+            }
+        }
+    }
+}
+
+fn write_str(f: &mut impl fmt::Write, s: &str) -> fmt::Result {
+    f.write_char('"')?;
+    for c in s.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c if c.is_control() => write!(f, "\\u{:04x}", c as u32)?,
+            c => f.write_char(c)?,
+        }
+    }
+    f.write_char('"')
+}
+
+struct JsonParser<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+}
+
+impl JsonParser<'_> {
+    fn whitespace(&mut self) {
+        while self.chars.next_if(|c| c.is_ascii_whitespace()).is_some() {}
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        self.whitespace();
+        match self.chars.next() {
+            Some(c) if c == expected => Ok(()),
+            Some(c) => Err(format!("Expected `{expected}`, found `{c}`")),
+            None => Err(format!("Expected `{expected}`, found end of input")),
+        }
+    }
+
+    fn keyword(&mut self, keyword: &str, val: Json) -> Result<Json, String> {
+        for k in keyword.chars() {
+            if self.chars.next() != Some(k) {
+                return Err(format!("Invalid literal, expected `{keyword}`"));
+            }
+        }
+        Ok(val)
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        self.whitespace();
+        match self.chars.peek() {
+            Some('n') => self.keyword("null", Json::Null),
+            Some('t') => self.keyword("true", Json::Bool(true)),
+            Some('f') => self.keyword("false", Json::Bool(false)),
+            Some('"') => self.string().map(Json::Str),
+            Some('[') => self.array(),
+            Some('{') => self.object(),
+            Some(c) if *c == '-' || c.is_ascii_digit() => self.number(),
+            Some(c) => Err(format!("Unexpected character `{c}`")),
+            None => Err("Unexpected end of input".to_owned()),
+        }
+    }
+
+    fn number(&mut self) -> Result<Json, String> {
+        let mut num = String::new();
+        while let Some(c) = self
+            .chars
+            .next_if(|c| c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E'))
+        {
+            num.push(c);
+        }
+        num.parse()
+            .map(Json::Num)
+            .map_err(|_| format!("Invalid number `{num}`"))
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut str = String::new();
+        loop {
+            match self.chars.next() {
+                Some('"') => return Ok(str),
+                Some('\\') => match self.chars.next() {
+                    Some('"') => str.push('"'),
+                    Some('\\') => str.push('\\'),
+                    Some('/') => str.push('/'),
+                    Some('b') => str.push('\u{8}'),
+                    Some('f') => str.push('\u{c}'),
+                    Some('n') => str.push('\n'),
+                    Some('r') => str.push('\r'),
+                    Some('t') => str.push('\t'),
+                    Some('u') => {
+                        let high = self.hex4()?;
+                        let c = if (0xd800..0xdc00).contains(&high) {
+                            // surrogate pair
+                            if self.chars.next() != Some('\\') || self.chars.next() != Some('u') {
+                                return Err("Unpaired surrogate in string".to_owned());
+                            }
+                            let low = self.hex4()?;
+                            0x10000 + ((high - 0xd800) << 10) + (low.wrapping_sub(0xdc00) & 0x3ff)
+                        } else {
+                            high
+                        };
+                        str.push(char::from_u32(c).unwrap_or(char::REPLACEMENT_CHARACTER));
+                    }
+                    Some(c) => return Err(format!("Invalid escape character `{c}`")),
+                    None => return Err("Unterminated string".to_owned()),
+                },
+                Some(c) => str.push(c),
+                None => return Err("Unterminated string".to_owned()),
+            }
+        }
+    }
+
+    fn hex4(&mut self) -> Result<u32, String> {
+        let mut val = 0;
+        for _ in 0..4 {
+            let digit = self.chars.next().and_then(|c| c.to_digit(16));
+            match digit {
+                Some(d) => val = val * 16 + d,
+                None => return Err("Invalid unicode escape".to_owned()),
+            }
+        }
+        Ok(val)
+    }
+
+    fn array(&mut self) -> Result<Json, String> {
+        self.expect('[')?;
+        let mut items = Vec::new();
+        self.whitespace();
+        if self.chars.next_if_eq(&']').is_some() {
+            return Ok(Json::Arr(items));
+        }
+        loop {
+            items.push(self.value()?);
+            self.whitespace();
+            match self.chars.next() {
+                Some(',') => (),
+                Some(']') => return Ok(Json::Arr(items)),
+                _ => return Err("Expected `,` or `]` in array".to_owned()),
+            }
+        }
+    }
+
+    fn object(&mut self) -> Result<Json, String> {
+        self.expect('{')?;
+        let mut members = Vec::new();
+        self.whitespace();
+        if self.chars.next_if_eq(&'}').is_some() {
+            return Ok(Json::Obj(members));
+        }
+        loop {
+            self.whitespace();
+            let key = self.string()?;
+            self.expect(':')?;
+            members.push((key, self.value()?));
+            self.whitespace();
+            match self.chars.next() {
+                Some(',') => (),
+                Some('}') => return Ok(Json::Obj(members)),
+                _ => return Err("Expected `,` or `}` in object".to_owned()),
+            }
+        }
+    }
+}
@@ -0,0 +1,495 @@
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use std::process::ExitCode;
+
+use cods::{
+    BuiltinConst, BuiltinFun, Checker, Context, DataType, FunSignature, Pos, Repetition,
+    SignatureKind, Span, Symbol, SymbolKind, UserFacing,
+};
+
+use crate::json::Json;
+
+const PARSE_ERROR: f64 = -32700.0;
+const METHOD_NOT_FOUND: f64 = -32601.0;
+const INVALID_PARAMS: f64 = -32602.0;
+
+const SEVERITY_ERROR: f64 = 1.0;
+const SEVERITY_WARNING: f64 = 2.0;
+
+const COMPLETION_FUNCTION: f64 = 3.0;
+const COMPLETION_VARIABLE: f64 = 6.0;
+const COMPLETION_CONSTANT: f64 = 21.0;
+
+/// Run a language server that communicates over stdin and stdout.
+pub fn run() -> ExitCode {
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    match serve(stdin.lock(), stdout.lock()) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("Language server error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn serve(mut input: impl BufRead, mut output: impl Write) -> io::Result<ExitCode> {
+    let mut server = Server::default();
+    loop {
+        let Some(msg) = read_message(&mut input)? else {
+            // the client disappeared without sending `exit`
+            return Ok(ExitCode::FAILURE);
+        };
+
+        let msg = match Json::parse(&msg) {
+            Ok(m) => m,
+            Err(e) => {
+                write_message(&mut output, &error_response(Json::Null, PARSE_ERROR, &e))?;
+                continue;
+            }
+        };
+
+        let method = msg.get("method").and_then(Json::as_str).unwrap_or_default();
+        let params = msg.get("params").unwrap_or(&Json::Null);
+        match msg.get("id") {
+            Some(id) => {
+                let response = match server.request(method, params) {
+                    Ok(result) => Json::obj([
+                        ("jsonrpc", Json::str("2.0")),
+                        ("id", id.clone()),
+                        ("result", result),
+                    ]),
+                    Err((code, message)) => error_response(id.clone(), code, &message),
+                };
+                write_message(&mut output, &response)?;
+            }
+            None => {
+                if method == "exit" {
+                    let code = match server.shutdown {
+                        true => ExitCode::SUCCESS,
+                        false => ExitCode::FAILURE,
+                    };
+                    return Ok(code);
+                }
+
+                for n in server.notification(method, params) {
+                    write_message(&mut output, &n)?;
+                }
+            }
+        }
+        output.flush()?;
+    }
+}
+
+pub(crate) fn read_message(input: &mut impl BufRead) -> io::Result<Option<String>> {
+    let mut content_len = None;
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, val)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_len = val.trim().parse::<usize>().ok();
+            }
+        }
+    }
+
+    let Some(len) = content_len else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Missing Content-Length header",
+        ));
+    };
+    let mut buf = vec![0; len];
+    input.read_exact(&mut buf)?;
+    String::from_utf8(buf)
+        .map(Some)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+pub(crate) fn write_message(output: &mut impl Write, msg: &Json) -> io::Result<()> {
+    let msg = msg.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{msg}", msg.len())
+}
+
+fn error_response(id: Json, code: f64, message: &str) -> Json {
+    Json::obj([
+        ("jsonrpc", Json::str("2.0")),
+        ("id", id),
+        (
+            "error",
+            Json::obj([("code", Json::Num(code)), ("message", Json::str(message))]),
+        ),
+    ])
+}
+
+fn notification(method: &str, params: Json) -> Json {
+    Json::obj([
+        ("jsonrpc", Json::str("2.0")),
+        ("method", Json::str(method)),
+        ("params", params),
+    ])
+}
+
+#[derive(Default)]
+struct Server {
+    documents: HashMap<String, String>,
+    shutdown: bool,
+}
+
+type RequestResult = Result<Json, (f64, String)>;
+
+impl Server {
+    fn request(&mut self, method: &str, params: &Json) -> RequestResult {
+        match method {
+            "initialize" => Ok(Json::obj([
+                (
+                    "capabilities",
+                    Json::obj([
+                        (
+                            "textDocumentSync",
+                            Json::obj([
+                                ("openClose", Json::Bool(true)),
+                                // incremental
+                                ("change", Json::Num(2.0)),
+                            ]),
+                        ),
+                        ("hoverProvider", Json::Bool(true)),
+                        ("definitionProvider", Json::Bool(true)),
+                        ("completionProvider", Json::obj([])),
+                    ]),
+                ),
+                (
+                    "serverInfo",
+                    Json::obj([
+                        ("name", Json::str("cods")),
+                        ("version", Json::str(env!("CARGO_PKG_VERSION"))),
+                    ]),
+                ),
+            ])),
+            "shutdown" => {
+                self.shutdown = true;
+                Ok(Json::Null)
+            }
+            "textDocument/hover" => {
+                let (text, pos) = self.document_pos(params)?;
+                let ctx = analyze(text);
+                let Some(symbol) = symbol_at(&ctx.symbols, pos) else {
+                    return Ok(Json::Null);
+                };
+                let hover = format!("```cods\n{}\n```", describe_symbol(symbol));
+                Ok(Json::obj([
+                    (
+                        "contents",
+                        Json::obj([("kind", Json::str("markdown")), ("value", Json::Str(hover))]),
+                    ),
+                    ("range", lsp_range(text, symbol.span)),
+                ]))
+            }
+            "textDocument/definition" => {
+                let (text, pos) = self.document_pos(params)?;
+                let ctx = analyze(text);
+                let def = symbol_at(&ctx.symbols, pos).and_then(Symbol::def_span);
+                let Some(def) = def else {
+                    return Ok(Json::Null);
+                };
+                let uri = params.path(&["textDocument", "uri"]).cloned();
+                Ok(Json::obj([
+                    ("uri", uri.unwrap_or(Json::Null)),
+                    ("range", lsp_range(text, def)),
+                ]))
+            }
+            "textDocument/completion" => {
+                let (text, _) = self.document_pos(params)?;
+                Ok(Json::Arr(completions(&analyze(text))))
+            }
+            _ => Err((METHOD_NOT_FOUND, format!("Unknown method `{method}`"))),
+        }
+    }
+
+    fn notification(&mut self, method: &str, params: &Json) -> Vec<Json> {
+        let uri = params.path(&["textDocument", "uri"]).and_then(Json::as_str);
+        let Some(uri) = uri else {
+            return Vec::new();
+        };
+
+        match method {
+            "textDocument/didOpen" => {
+                let text = params
+                    .path(&["textDocument", "text"])
+                    .and_then(Json::as_str);
+                let text = text.unwrap_or_default().to_owned();
+                self.documents.insert(uri.to_owned(), text);
+            }
+            "textDocument/didChange" => {
+                let Some(text) = self.documents.get_mut(uri) else {
+                    return Vec::new();
+                };
+                let changes = params.get("contentChanges").and_then(Json::as_arr);
+                for c in changes.unwrap_or_default() {
+                    let new_text = c.get("text").and_then(Json::as_str).unwrap_or_default();
+                    match c.get("range") {
+                        Some(range) => {
+                            let start = range.get("start").and_then(|p| byte_offset(text, p));
+                            let end = range.get("end").and_then(|p| byte_offset(text, p));
+                            if let (Some(start), Some(end)) = (start, end) {
+                                text.replace_range(start..end.max(start), new_text);
+                            }
+                        }
+                        None => *text = new_text.to_owned(),
+                    }
+                }
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(uri);
+                return vec![publish_diagnostics(uri, Vec::new())];
+            }
+            _ => return Vec::new(),
+        }
+
+        let text = &self.documents[uri];
+        vec![publish_diagnostics(uri, diagnostics(text))]
+    }
+
+    fn document_pos(&self, params: &Json) -> Result<(&str, Pos), (f64, String)> {
+        let uri = params.path(&["textDocument", "uri"]).and_then(Json::as_str);
+        let text = uri.and_then(|u| self.documents.get(u));
+        let Some(text) = text else {
+            return Err((INVALID_PARAMS, "Unknown document".to_owned()));
+        };
+
+        let pos = params.get("position").and_then(|p| cods_pos(text, p));
+        let pos = pos.unwrap_or(Pos::new(0, 0));
+        Ok((text, pos))
+    }
+}
+
+fn analyze(text: &str) -> Context {
+    let mut ctx = Context::default();
+    let mut checker = Checker::default();
+    let res = ctx
+        .lex(text)
+        .and_then(|tokens| ctx.group(tokens))
+        .and_then(|items| ctx.parse(items))
+        .and_then(|csts| ctx.check_with(&mut checker, csts));
+    if let Err(e) = res {
+        ctx.errors.push(e);
+    }
+    ctx
+}
+
+fn diagnostics(text: &str) -> Vec<Json> {
+    let ctx = analyze(text);
+    let errors = ctx
+        .errors
+        .iter()
+        .map(|e| diagnostic(text, e, SEVERITY_ERROR));
+    let warnings = ctx
+        .warnings
+        .iter()
+        .map(|w| diagnostic(text, w, SEVERITY_WARNING));
+    errors.chain(warnings).collect()
+}
+
+fn diagnostic(text: &str, d: &impl UserFacing, severity: f64) -> Json {
+    let mut message = String::new();
+    let _ = d.description(&mut message, "", "");
+    let span = d.spans().first().copied().unwrap_or(Span::pos(0, 0));
+    Json::obj([
+        ("range", lsp_range(text, span)),
+        ("severity", Json::Num(severity)),
+        ("source", Json::str("cods")),
+        ("message", Json::Str(message)),
+    ])
+}
+
+fn publish_diagnostics(uri: &str, diagnostics: Vec<Json>) -> Json {
+    notification(
+        "textDocument/publishDiagnostics",
+        Json::obj([
+            ("uri", Json::str(uri)),
+            ("diagnostics", Json::Arr(diagnostics)),
+        ]),
+    )
+}
+
+/// The innermost symbol at the position.
+fn symbol_at(symbols: &[Symbol], pos: Pos) -> Option<&Symbol> {
+    symbols.iter().rev().find(|s| s.span.contains(&pos))
+}
+
+fn describe_symbol(symbol: &Symbol) -> String {
+    let name = &symbol.name;
+    match &symbol.kind {
+        SymbolKind::Var {
+            data_type, mutable, ..
+        } => {
+            let mutable = if *mutable { "mut " } else { "" };
+            format!("let {mutable}{name}: {data_type}")
+        }
+        SymbolKind::Const(c) => {
+            let val = c.val();
+            format!("const {name}: {} = {val}", val.data_type())
+        }
+        SymbolKind::Fun {
+            params,
+            return_type,
+            ..
+        } => {
+            let params = (params.iter())
+                .map(|(n, t)| format!("{n}: {t}"))
+                .collect::<Vec<_>>();
+            format!(
+                "fn {name}({}){}",
+                params.join(", "),
+                return_suffix(*return_type)
+            )
+        }
+        SymbolKind::BuiltinFun(b) => builtin_signatures(*b)
+            .iter()
+            .map(|s| describe_signature(name, s))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        SymbolKind::HostFun(signatures) => signatures
+            .iter()
+            .map(|s| describe_signature(name, s))
+            .collect::<Vec<_>>()
+            .join("\n"),
+    }
+}
+
+fn builtin_signatures(b: BuiltinFun) -> Vec<FunSignature> {
+    match b.signatures() {
+        SignatureKind::Normal(s) => s.iter().map(|(_, s)| s.clone()).collect(),
+        SignatureKind::Spill(_) => vec![FunSignature::empty()],
+    }
+}
+
+fn describe_signature(name: &str, s: &FunSignature) -> String {
+    let mut params = s.params.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+    if let Some(last) = params.last_mut() {
+        match s.repetition {
+            Repetition::One => (),
+            Repetition::ZeroOrMore => last.insert_str(0, ".."),
+            Repetition::OneOrMore => last.insert_str(0, "..."),
+        }
+    }
+    format!(
+        "fn {name}({}){}",
+        params.join(", "),
+        return_suffix(s.return_type)
+    )
+}
+
+fn return_suffix(data_type: DataType) -> String {
+    match data_type {
+        DataType::Unit => String::new(),
+        t => format!(" -> {t}"),
+    }
+}
+
+fn completions(ctx: &Context) -> Vec<Json> {
+    let item = |label: String, kind: f64, detail: String| {
+        Json::obj([
+            ("label", Json::Str(label)),
+            ("kind", Json::Num(kind)),
+            ("detail", Json::Str(detail)),
+        ])
+    };
+
+    let mut items = Vec::new();
+    let mut names = Vec::new();
+    // definitions inside the document
+    for s in ctx.symbols.iter().rev() {
+        if s.def_span() != Some(s.span) || names.contains(&s.name) {
+            continue;
+        }
+        let kind = match s.kind {
+            SymbolKind::Fun { .. } => COMPLETION_FUNCTION,
+            _ => COMPLETION_VARIABLE,
+        };
+        names.push(s.name.clone());
+        items.push(item(s.name.clone(), kind, describe_symbol(s)));
+    }
+
+    for b in BuiltinFun::members() {
+        let name = b.to_string();
+        let detail = (builtin_signatures(*b).iter())
+            .map(|s| describe_signature(&name, s))
+            .collect::<Vec<_>>()
+            .join("\n");
+        items.push(item(name, COMPLETION_FUNCTION, detail));
+    }
+    for c in BuiltinConst::members() {
+        let name = c.to_string();
+        let symbol = Symbol::new(name.clone(), Span::pos(0, 0), SymbolKind::Const(*c));
+        items.push(item(name, COMPLETION_CONSTANT, describe_symbol(&symbol)));
+    }
+
+    items
+}
+
+/// Convert a span measured in chars into an LSP range measured in UTF-16 code units.
+fn lsp_range(text: &str, span: Span) -> Json {
+    Json::obj([
+        ("start", lsp_pos(text, span.start)),
+        ("end", lsp_pos(text, span.end)),
+    ])
+}
+
+fn lsp_pos(text: &str, pos: Pos) -> Json {
+    let line = text.split('\n').nth(pos.line as usize).unwrap_or_default();
+    let character = (line.chars().take(pos.col as usize))
+        .map(char::len_utf16)
+        .sum::<usize>();
+    Json::obj([
+        ("line", Json::Num(pos.line as f64)),
+        ("character", Json::Num(character as f64)),
+    ])
+}
+
+/// Convert an LSP position measured in UTF-16 code units into a position measured in chars.
+fn cods_pos(text: &str, pos: &Json) -> Option<Pos> {
+    let line_idx = pos.get("line")?.as_u32()?;
+    let character = pos.get("character")?.as_u32()? as usize;
+    let line = text.split('\n').nth(line_idx as usize).unwrap_or_default();
+
+    let mut utf16 = 0;
+    let mut col = 0;
+    for c in line.chars() {
+        if utf16 >= character {
+            break;
+        }
+        utf16 += c.len_utf16();
+        col += 1;
+    }
+    Some(Pos::new(line_idx, col))
+}
+
+/// Convert an LSP position into a byte offset into the text.
+fn byte_offset(text: &str, pos: &Json) -> Option<usize> {
+    let line = pos.get("line")?.as_u32()? as usize;
+    let character = pos.get("character")?.as_u32()? as usize;
+
+    let mut offset = 0;
+    for (i, l) in text.split('\n').enumerate() {
+        if i == line {
+            let mut utf16 = 0;
+            for (byte, c) in l.char_indices() {
+                if utf16 >= character {
+                    return Some(offset + byte);
+                }
+                utf16 += c.len_utf16();
+            }
+            return Some(offset + l.len());
+        }
+        offset += l.len() + 1;
+    }
+    Some(text.len())
+}
@@ -1,29 +1,54 @@
+use std::cell::RefCell;
 use std::env::args;
 use std::io::{self, Write as _};
 use std::process::ExitCode;
+use std::rc::Rc;
 
-use cods::{Asts, Checker, Context, Stack, Val, Warning};
+use cods::{Asts, Checker, Context, Coverage, Env, Permissions, Rng, Stack, Tracer, Val, Warning};
 use cods_derive::EnumFromStr;
 
 use display::*;
 use style::*;
 
+mod coverage;
+mod dap;
+mod debug;
 mod display;
 mod json;
+mod lsp;
+mod profile;
 mod style;
+mod test_runner;
 
 #[derive(Default)]
 struct State {
     ctx: Context,
     checker: Checker,
     stack: Stack,
+    rng: Rng,
+    exit_code: Option<u8>,
 }
 
 impl State {
+    fn new(args: &Args) -> Self {
+        Self {
+            rng: args.seed.map(Rng::new).unwrap_or_default(),
+            ..Default::default()
+        }
+    }
+
     fn clear(&mut self) {
         self.ctx.clear();
         self.checker.clear();
         self.stack.clear();
+        self.exit_code = None;
+    }
+
+    fn exit_code(&self) -> ExitCode {
+        match self.exit_code {
+            Some(c) => ExitCode::from(c),
+            None => ExitCode::SUCCESS,
+        }
     }
 }
 
@@ -39,12 +64,25 @@ enum OutputFormat {
 struct Args {
     format: OutputFormat,
     skip_unused_warnings: bool,
+    permissions: Permissions,
+    seed: Option<u64>,
+    trace: bool,
+    folded: bool,
+    coverage: bool,
+    lcov: Option<String>,
+    filter: Option<String>,
+    script_args: Vec<String>,
 }
 
 enum Action {
     Run(String),
     Check(String),
+    Debug(String),
+    Profile(String),
+    Test(Vec<String>),
     Interactive,
+    Lsp,
+    Dap,
     Help,
     Version,
 }
@@ -66,12 +104,27 @@ fn main() -> ExitCode {
     while let Some(arg) = args.next() {
         match arg.as_str() {
             "i" | "interactive" => action = Some(Action::Interactive),
+            "lsp" => action = Some(Action::Lsp),
+            "dap" => action = Some(Action::Dap),
             "r" | "run" => {
                 let Some(path) = args.next() else {
                     error!("Path not specified");
                 };
                 action = Some(Action::Run(path));
             }
+            "debug" => {
+                let Some(path) = args.next() else {
+                    error!("Path not specified");
+                };
+                action = Some(Action::Debug(path));
+            }
+            "profile" => {
+                let Some(path) = args.next() else {
+                    error!("Path not specified");
+                };
+                action = Some(Action::Profile(path));
+            }
+            "test" => action = Some(Action::Test(Vec::new())),
             "c" | "check" => {
                 let Some(path) = args.next() else {
                     bprintln!(LRed, "Path not specified");
@@ -92,12 +145,51 @@ fn main() -> ExitCode {
                     error!("Missing --format, possible values are [pretty, json]");
                 }
             },
+            "--allow-env" => user_args.permissions.env = true,
+            "--trace" => user_args.trace = true,
+            "--folded" => user_args.folded = true,
+            "--coverage" => user_args.coverage = true,
+            "--lcov" => match args.next() {
+                Some(p) => user_args.lcov = Some(p),
+                None => error!("Missing --lcov path"),
+            },
+            "--filter" => match args.next() {
+                Some(f) => user_args.filter = Some(f),
+                None => error!("Missing --filter pattern"),
+            },
+            "--seed" => match args.next() {
+                Some(s) => match s.parse::<u64>() {
+                    Ok(s) => user_args.seed = Some(s),
+                    Err(_) => error!("Invalid --seed: `{s}`, expected a non-negative integer"),
+                },
+                None => error!("Missing --seed"),
+            },
             "--" => {
                 let items = args.collect::<Vec<_>>();
+                if let Some(Action::Run(_) | Action::Debug(_) | Action::Profile(_)) = action {
+                    user_args.script_args.extend(items);
+                    break;
+                }
                 return eval_args(&user_args, &items);
             }
             a => {
-                error!("Invalid argument: `{a}`");
+                if let Some(path) = a.strip_prefix("--allow-read=") {
+                    user_args.permissions.allow_read(path);
+                } else if let Some(path) = a.strip_prefix("--allow-write=") {
+                    user_args.permissions.allow_write(path);
+                } else if a.starts_with('-') {
+                    error!("Invalid argument: `{a}`");
+                } else {
+                    match action {
+                        // `cods <file>` is a shorthand for `cods run <file>`
+                        None => action = Some(Action::Run(a.to_owned())),
+                        Some(Action::Run(_) | Action::Debug(_) | Action::Profile(_)) => {
+                            user_args.script_args.push(a.to_owned())
+                        }
+                        Some(Action::Test(ref mut paths)) => paths.push(a.to_owned()),
+                        _ => error!("Invalid argument: `{a}`"),
+                    }
+                }
             }
         }
     }
@@ -105,7 +197,12 @@ fn main() -> ExitCode {
     match action {
         Some(Action::Run(path)) => eval_path(&user_args, &path),
         Some(Action::Check(path)) => check_path(&user_args, &path),
+        Some(Action::Debug(path)) => debug::run(&user_args, &path),
+        Some(Action::Profile(path)) => profile::run(&user_args, &path),
+        Some(Action::Test(paths)) => test_runner::run(&user_args, &paths),
         Some(Action::Interactive) => repl(&mut user_args),
+        Some(Action::Lsp) => lsp::run(),
+        Some(Action::Dap) => dap::run(),
         Some(Action::Help) => {
             help();
             ExitCode::SUCCESS
@@ -130,7 +227,7 @@ fn repl(args: &mut Args) -> ExitCode {
     let mut output = io::stdout();
     let input = io::stdin();
     let mut buf = String::new();
-    let mut state = State::default();
+    let mut state = State::new(args);
     loop {
         buf.clear();
         state.ctx.clear_errors();
@@ -155,6 +252,9 @@ fn repl(args: &mut Args) -> ExitCode {
             }
             _ => {
                 print_eval(&mut state, &buf, args);
+                if state.exit_code.is_some() {
+                    return state.exit_code();
+                }
             }
         }
     }
@@ -165,9 +265,26 @@ fn repl(args: &mut Args) -> ExitCode {
 fn eval_path(args: &Args, path: &str) -> ExitCode {
     match std::fs::read_to_string(path) {
         Ok(input) => {
-            let mut state = State::default();
-            match print_eval(&mut state, &input, args) {
-                Some(_) => ExitCode::SUCCESS,
+            let mut state = State::new(args);
+            if !args.coverage && args.lcov.is_none() {
+                return match print_eval(&mut state, &input, args) {
+                    Some(_) => state.exit_code(),
+                    None => ExitCode::FAILURE,
+                };
+            }
+
+            let coverage = Rc::new(RefCell::new(Coverage::default()));
+            let mut started = false;
+            let res = print_eval_with(&mut state, &input, args, |state, asts, env| {
+                *coverage.borrow_mut() = Coverage::new(asts, &state.checker.funs);
+                env.observer = Some(Box::new(Rc::clone(&coverage)));
+                started = true;
+            });
+            if started && !coverage::report(args, &[(path, &input, &coverage.borrow())]) {
+                return ExitCode::FAILURE;
+            }
+            match res {
+                Some(_) => state.exit_code(),
                 None => ExitCode::FAILURE,
             }
         }
@@ -181,7 +298,7 @@ fn eval_path(args: &Args, path: &str) -> ExitCode {
 fn check_path(args: &Args, path: &str) -> ExitCode {
     match std::fs::read_to_string(path) {
         Ok(input) => {
-            let mut state = State::default();
+            let mut state = State::new(args);
             match print_check(&mut state, &input, args) {
                 Some(_) => ExitCode::SUCCESS,
                 None => ExitCode::FAILURE,
@@ -196,25 +313,56 @@ fn check_path(args: &Args, path: &str) -> ExitCode {
 
 fn eval_args(args: &Args, items: &[String]) -> ExitCode {
     let input = items.join(" ");
-    let mut state = State::default();
+    let mut state = State::new(args);
     match print_eval(&mut state, &input, args) {
-        Some(_) => ExitCode::SUCCESS,
+        Some(_) => state.exit_code(),
         None => ExitCode::FAILURE,
     }
 }
 
 fn print_eval(state: &mut State, input: &str, args: &Args) -> Option<Val> {
+    print_eval_with(state, input, args, |_, _, _| ())
+}
+
+/// Like [`print_eval`], `setup` can modify the environment after the input has been checked.
+fn print_eval_with(
+    state: &mut State,
+    input: &str,
+    args: &Args,
+    setup: impl FnOnce(&State, &Asts, &mut Env<'_>),
+) -> Option<Val> {
     if let Some(asts) = print_check(state, input, args) {
-        match cods::eval_with(&mut state.stack, &state.checker.funs, &asts) {
+        let mut env = Env {
+            rng: state.rng.clone(),
+            permissions: args.permissions.clone(),
+            args: args.script_args.clone(),
+            ..Default::default()
+        };
+        if args.trace {
+            env.observer = Some(Box::new(Tracer::new(io::stderr(), input)));
+        }
+        setup(state, &asts, &mut env);
+        let res = cods::eval_with_env(&mut state.stack, &state.checker.funs, &mut env, &asts);
+        state.rng = env.rng;
+        state.exit_code = env.exit_code;
+        match res {
             Ok(v) => {
                 if v != Val::Unit {
                     println!("{v}");
                 }
                 return Some(v);
             }
-            Err(e) => {
-                println!("{}", e.display(input));
-            }
+            Err(e) => match args.format {
+                OutputFormat::Pretty => {
+                    println!("{}", e.display(input));
+                    print!("{}", display_trace(&env.trace, input));
+                }
+                OutputFormat::Json => {
+                    let mut buf = String::new();
+                    let _ = json::write_runtime_error(&mut buf, &e, &env.trace);
+                    println!("{buf}");
+                }
+            },
         }
     }
 
@@ -286,19 +434,37 @@ fn help() {
 
 {yellow}USAGE:{esc}
     cods [COMMAND][OPTIONS] [-- EXPRESSION]
+    cods [run] <file> [OPTIONS] [ARGS]... [-- ARGS]
 
 {yellow}EXPRESSION:{esc}
     An expression that will be evaluated
 
+{yellow}ARGS:{esc}
+    Arguments passed to the script, accessible using `arg_count()` and `arg(i)`
+
 {yellow}COMMANDS:{esc}
-    {green}r{esc}, {green}run   <file>{esc}         Run a file
+    {green}r{esc}, {green}run   <file>{esc}         Run a file, the command can be omitted
     {green}c{esc}, {green}check <file>{esc}         Check a file
+    {green}debug <file>{esc}            Run a file in an interactive debugger
+    {green}profile <file>{esc}          Run a file and report where time was spent to stderr
+    {green}test [paths]...{esc}         Run the test functions in files or directories [default: \".\"]
     {green}i{esc}, {green}interactive{esc}          Start an interactive repl
+    {green}lsp{esc}                     Start a language server communicating over stdio
+    {green}dap{esc}                     Start a debug adapter communicating over stdio
 
 {yellow}OPTIONS:{esc}
     {green}-h{esc}, {green}--help{esc}              Show this help message
     {green}-v{esc}, {green}--version{esc}           Print the version
     {green}-f{esc}, {green}--format <format>{esc}   The output format [default: \"pretty\"] [possible values: \"pretty\", \"json\"]
+    {green}--allow-read=<path>{esc}     Allow reading files inside the path
+    {green}--allow-write=<path>{esc}    Allow writing files inside the path
+    {green}--allow-env{esc}             Allow reading environment variables
+    {green}--seed <seed>{esc}           Seed the random number generator
+    {green}--trace{esc}                 Log evaluated statements and function calls to stderr
+    {green}--folded{esc}                Print the profile in the folded stacks format used by flamegraph tools
+    {green}--coverage{esc}              Print the source annotated with line and branch coverage to stderr
+    {green}--lcov <path>{esc}           Write line and branch coverage in the lcov format
+    {green}--filter <pattern>{esc}      Only run tests whose name contains the pattern
 ",
         vers = env!("CARGO_PKG_VERSION"),
         authors = env!("CARGO_PKG_AUTHORS"),
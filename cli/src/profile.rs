@@ -0,0 +1,253 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::ops::ControlFlow;
+use std::process::ExitCode;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use cods::{Ast, DebugInfo, EvalState, FunRef, Observer, Val};
+
+use crate::style::{Color, LRed};
+use crate::{bprintln, print_eval_with, Args, State, ANSI_ESC};
+
+/// The number of lines shown in the table of hot lines.
+const HOT_LINES: usize = 10;
+
+/// Run a script and report where time was spent on stderr.
+pub fn run(args: &Args, path: &str) -> ExitCode {
+    let Ok(input) = std::fs::read_to_string(path) else {
+        bprintln!(LRed, "Error reading file: {path}");
+        return ExitCode::FAILURE;
+    };
+
+    let profiler = Rc::new(RefCell::new(Profiler::default()));
+    let mut state = State::new(args);
+    let res = print_eval_with(&mut state, &input, args, |_, _, env| {
+        env.observer = Some(Box::new(Rc::clone(&profiler)));
+    });
+
+    let mut profiler = profiler.borrow_mut();
+    if profiler.started() {
+        profiler.finish();
+        let debug = &state.checker.debug;
+        let report = match args.folded {
+            true => profiler.folded(debug),
+            false => profiler.table(&input, debug),
+        };
+        eprint!("{report}");
+    }
+
+    match res {
+        Some(_) => state.exit_code(),
+        None => ExitCode::FAILURE,
+    }
+}
+
+#[derive(Default)]
+struct FunStats {
+    calls: usize,
+    /// Time spent inside the function including its callees. Recursive calls are only counted
+    /// once.
+    total: Duration,
+    /// Time spent inside the function excluding its callees.
+    own: Duration,
+}
+
+#[derive(Default)]
+struct LineStats {
+    hits: usize,
+    /// Time spent evaluating the line excluding function calls made from it.
+    own: Duration,
+}
+
+/// An active call, the innermost one is last.
+struct Frame {
+    /// `None` for top-level code.
+    fun: Option<FunRef>,
+    /// The interned call stack ending with this frame.
+    stack: usize,
+    start: Instant,
+    /// The line that is currently evaluated inside this frame.
+    line: Option<u32>,
+}
+
+/// A call stack, stored as the innermost function and the stack of its caller.
+struct StackNode {
+    parent: Option<usize>,
+    fun: Option<FunRef>,
+    /// Exclusive time spent in this call stack.
+    own: Duration,
+}
+
+/// Measures time by attributing the time that elapsed between two observer events to the line
+/// and call stack that were active in between.
+#[derive(Default)]
+struct Profiler {
+    last: Option<Instant>,
+    frames: Vec<Frame>,
+    funs: HashMap<FunRef, FunStats>,
+    /// The number of active calls of each function, used to detect recursion.
+    active: HashMap<FunRef, usize>,
+    lines: HashMap<u32, LineStats>,
+    /// Interned call stacks, so events don't have to build the path of the stack.
+    stacks: Vec<StackNode>,
+    stack_ids: HashMap<(Option<usize>, Option<FunRef>), usize>,
+}
+
+impl Profiler {
+    fn started(&self) -> bool {
+        self.last.is_some()
+    }
+
+    /// Attribute the time elapsed since the last event to the current line and call stack.
+    fn tick(&mut self) -> Instant {
+        let now = Instant::now();
+        let Some(last) = self.last.replace(now) else {
+            return now;
+        };
+        let elapsed = now - last;
+
+        if let Some(frame) = self.frames.last() {
+            if let Some(line) = frame.line {
+                self.lines.entry(line).or_default().own += elapsed;
+            }
+            if let Some(fun) = frame.fun {
+                self.funs.entry(fun).or_default().own += elapsed;
+            }
+            self.stacks[frame.stack].own += elapsed;
+        }
+        now
+    }
+
+    /// Attribute the remaining time after the last statement.
+    fn finish(&mut self) {
+        self.tick();
+    }
+
+    fn push_frame(&mut self, fun: Option<FunRef>, start: Instant) {
+        let parent = self.frames.last().map(|f| f.stack);
+        let stack = *self.stack_ids.entry((parent, fun)).or_insert_with(|| {
+            self.stacks.push(StackNode {
+                parent,
+                fun,
+                own: Duration::ZERO,
+            });
+            self.stacks.len() - 1
+        });
+        self.frames.push(Frame {
+            fun,
+            stack,
+            start,
+            line: None,
+        });
+    }
+
+    /// The functions sorted by exclusive time, followed by the hottest lines.
+    fn table(&self, input: &str, debug: &DebugInfo) -> String {
+        let mut funs = self.funs.iter().collect::<Vec<_>>();
+        funs.sort_by(|(a_fun, a), (b_fun, b)| b.own.cmp(&a.own).then(a_fun.cmp(b_fun)));
+        let mut lines = self.lines.iter().collect::<Vec<_>>();
+        lines.sort_by(|(a_line, a), (b_line, b)| b.own.cmp(&a.own).then(a_line.cmp(b_line)));
+
+        let mut buf = String::new();
+        let _ = writeln!(buf, "Functions:");
+        let _ = writeln!(buf, "{:>8} {:>12} {:>12}  name", "calls", "total", "self");
+        for (&fun, f) in funs {
+            let (total, own) = (fmt_duration(f.total), fmt_duration(f.own));
+            let name = debug.fun_name(Some(fun));
+            // functions with the same name can be nested inside different functions
+            let line = debug.fun(fun).map_or(0, |f| f.span.start.line) + 1;
+            let _ = writeln!(
+                buf,
+                "{:>8} {total:>12} {own:>12}  {name} (line {line})",
+                f.calls
+            );
+        }
+
+        let _ = writeln!(buf, "\nHot lines:");
+        let _ = writeln!(buf, "{:>8} {:>12}  line", "hits", "self");
+        for (&line, l) in lines.into_iter().take(HOT_LINES) {
+            let text = input.lines().nth(line as usize).unwrap_or_default().trim();
+            let own = fmt_duration(l.own);
+            let _ = writeln!(buf, "{:>8} {own:>12}  {}: {text}", l.hits, line + 1);
+        }
+        buf
+    }
+
+    /// The folded stacks format understood by flamegraph tools, the values are microseconds.
+    fn folded(&self, debug: &DebugInfo) -> String {
+        let mut stacks = (self.stacks.iter().enumerate())
+            .map(|(i, s)| (self.stack_path(i, debug), s.own))
+            .collect::<Vec<_>>();
+        stacks.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        let mut buf = String::new();
+        for (stack, time) in stacks {
+            let _ = writeln!(buf, "{stack} {}", time.as_micros());
+        }
+        buf
+    }
+
+    /// The names of the functions of the stack joined by `;`, the outermost one is first.
+    fn stack_path(&self, mut index: usize, debug: &DebugInfo) -> String {
+        let mut names = Vec::new();
+        loop {
+            let node = &self.stacks[index];
+            names.push(debug.fun_name(node.fun));
+            match node.parent {
+                Some(p) => index = p,
+                None => break,
+            }
+        }
+        names.reverse();
+        names.join(";")
+    }
+}
+
+impl Observer for Profiler {
+    fn statement(&mut self, state: &EvalState<'_>, ast: &Ast) -> ControlFlow<u8> {
+        let now = self.tick();
+        if self.frames.is_empty() {
+            self.push_frame(state.calls[0].fun, now);
+        }
+
+        let line = ast.span.start.line;
+        self.lines.entry(line).or_default().hits += 1;
+        if let Some(frame) = self.frames.last_mut() {
+            frame.line = Some(line);
+        }
+        ControlFlow::Continue(())
+    }
+
+    fn enter_fun(&mut self, state: &EvalState<'_>) {
+        let now = self.tick();
+        let fun = state.calls[state.calls.len() - 1].fun;
+        if let Some(f) = fun {
+            self.funs.entry(f).or_default().calls += 1;
+            *self.active.entry(f).or_default() += 1;
+        }
+        self.push_frame(fun, now);
+    }
+
+    fn exit_fun(&mut self, _state: &EvalState<'_>, _ret: Option<&Val>) {
+        let now = self.tick();
+        let Some(Frame {
+            fun: Some(fun),
+            start,
+            ..
+        }) = self.frames.pop()
+        else {
+            return;
+        };
+        let active = self.active.entry(fun).or_default();
+        *active -= 1;
+        if *active == 0 {
+            self.funs.entry(fun).or_default().total += now - start;
+        }
+    }
+}
+
+fn fmt_duration(d: Duration) -> String {
+    format!("{:.3}ms", d.as_secs_f64() * 1000.0)
+}
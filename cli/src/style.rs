@@ -1,3 +1,5 @@
+#![allow(dead_code)]
+
 use std::fmt::{self, Display};
 
 pub const ANSI_ESC: &str = "\x1B[0m";
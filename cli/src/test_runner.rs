@@ -0,0 +1,259 @@
+use std::cell::RefCell;
+use std::io;
+use std::path::Path;
+use std::process::ExitCode;
+use std::rc::Rc;
+
+use cods::{Asts, Coverage, Env, Error, FunRef, Io, Stack, TraceFrame};
+
+use crate::display::{display_trace, DisplayUserFacing};
+use crate::style::{Color, LBlue, LGreen, LRed};
+use crate::{bprintln, check, coverage, json, Args, OutputFormat, State, ANSI_ESC};
+
+/// The result of running the tests of a single file.
+pub struct FileReport {
+    pub path: String,
+    pub input: String,
+    /// Errors that prevented the file from being checked, no tests were run in that case.
+    pub errors: Vec<Error>,
+    pub tests: Vec<TestResult>,
+    /// The number of tests that didn't match the filter.
+    pub filtered: usize,
+    pub coverage: Option<Coverage>,
+}
+
+pub struct TestResult {
+    pub name: String,
+    /// Everything the test wrote to stdout.
+    pub output: String,
+    pub outcome: Outcome,
+}
+
+pub enum Outcome {
+    Passed,
+    Error(Error, Vec<TraceFrame>),
+    /// The test called `exit` with a non-zero code.
+    Exit(u8),
+}
+
+impl Outcome {
+    pub fn passed(&self) -> bool {
+        matches!(self, Self::Passed)
+    }
+}
+
+/// Run all test functions inside the files, or the `.cods` files inside directories, that
+/// match the filter.
+pub fn run(args: &Args, paths: &[String]) -> ExitCode {
+    let mut files = Vec::new();
+    for p in paths {
+        if collect_files(Path::new(p), &mut files).is_err() {
+            bprintln!(LRed, "Error reading path: {p}");
+            return ExitCode::FAILURE;
+        }
+    }
+    if paths.is_empty() && collect_files(Path::new("."), &mut files).is_err() {
+        bprintln!(LRed, "Error reading current directory");
+        return ExitCode::FAILURE;
+    }
+
+    let mut reports = Vec::with_capacity(files.len());
+    for path in files {
+        let Ok(input) = std::fs::read_to_string(&path) else {
+            bprintln!(LRed, "Error reading file: {path}");
+            return ExitCode::FAILURE;
+        };
+        reports.push(run_file(args, path, input));
+    }
+
+    match args.format {
+        OutputFormat::Pretty => print_summary(&reports),
+        OutputFormat::Json => {
+            let mut buf = String::new();
+            let _ = json::write_test_reports(&mut buf, &reports);
+            println!("{buf}");
+        }
+    }
+
+    if args.coverage || args.lcov.is_some() {
+        let files = (reports.iter())
+            .filter_map(|r| Some((r.path.as_str(), r.input.as_str(), r.coverage.as_ref()?)))
+            .collect::<Vec<_>>();
+        if !coverage::report(args, &files) {
+            return ExitCode::FAILURE;
+        }
+    }
+
+    let failed = reports
+        .iter()
+        .any(|r| !r.errors.is_empty() || r.tests.iter().any(|t| !t.outcome.passed()));
+    match failed {
+        true => ExitCode::FAILURE,
+        false => ExitCode::SUCCESS,
+    }
+}
+
+fn collect_files(path: &Path, files: &mut Vec<String>) -> io::Result<()> {
+    if !path.is_dir() {
+        files.push(path.to_string_lossy().into_owned());
+        return Ok(());
+    }
+
+    let mut entries = std::fs::read_dir(path)?
+        .map(|e| e.map(|e| e.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+    entries.sort();
+    for p in entries {
+        if p.is_dir() || p.extension().is_some_and(|e| e == "cods") {
+            collect_files(&p, files)?;
+        }
+    }
+    Ok(())
+}
+
+fn run_file(args: &Args, path: String, input: String) -> FileReport {
+    let mut state = State::new(args);
+    let mut report = FileReport {
+        path,
+        input,
+        errors: Vec::new(),
+        tests: Vec::new(),
+        filtered: 0,
+        coverage: None,
+    };
+
+    let asts = match check(&mut state, &report.input) {
+        Ok(asts) => asts,
+        Err(e) => {
+            state.ctx.errors.push(e);
+            report.errors = std::mem::take(&mut state.ctx.errors);
+            if args.format == OutputFormat::Pretty {
+                bprintln!(LRed, "Error checking {}", report.path);
+                for e in report.errors.iter().rev() {
+                    println!("{}\n", e.display(&report.input));
+                }
+            }
+            return report;
+        }
+    };
+
+    let coverage = (args.coverage || args.lcov.is_some())
+        .then(|| Rc::new(RefCell::new(Coverage::new(&asts, &state.checker.funs))));
+
+    let tests = state.checker.funs.tests();
+    let selected = (tests.iter())
+        .filter(|(name, _)| args.filter.as_ref().is_none_or(|f| name.contains(f)))
+        .collect::<Vec<_>>();
+    report.filtered = tests.len() - selected.len();
+
+    if args.format == OutputFormat::Pretty {
+        let test_s = if selected.len() == 1 { "" } else { "s" };
+        let (count, path) = (selected.len(), &report.path);
+        bprintln!(LBlue, "running {count} test{test_s} in {path}");
+    }
+    for (name, fun_ref) in selected {
+        let result = run_test(&state, &asts, name, *fun_ref, args, coverage.as_ref());
+        if args.format == OutputFormat::Pretty {
+            match result.outcome.passed() {
+                true => println!("test {name} ... {}ok{ANSI_ESC}", LGreen::bold()),
+                false => println!("test {name} ... {}FAILED{ANSI_ESC}", LRed::bold()),
+            }
+        }
+        report.tests.push(result);
+    }
+
+    report.coverage = coverage.map(|c| c.take());
+    report
+}
+
+/// Evaluate the top-level code and then call the test function, with a fresh stack and
+/// environment. Output to stdout is captured.
+fn run_test(
+    state: &State,
+    asts: &Asts,
+    name: &str,
+    fun_ref: FunRef,
+    args: &Args,
+    coverage: Option<&Rc<RefCell<Coverage>>>,
+) -> TestResult {
+    let funs = &state.checker.funs;
+    let mut output = Vec::new();
+    let mut stack = Stack::default();
+    let mut env = Env {
+        io: Io::new(io::empty(), &mut output),
+        rng: state.rng.clone(),
+        permissions: args.permissions.clone(),
+        ..Default::default()
+    };
+    if let Some(c) = coverage {
+        env.observer = Some(Box::new(Rc::clone(c)));
+    }
+
+    let mut res = cods::eval_with_env(&mut stack, funs, &mut env, asts);
+    if res.is_ok() && env.exit_code.is_none() {
+        res = cods::call_fun(&mut stack, funs, &mut env, fun_ref, &[]);
+    }
+    let outcome = match res {
+        Err(e) => Outcome::Error(e, std::mem::take(&mut env.trace)),
+        Ok(_) => match env.exit_code {
+            Some(code) if code != 0 => Outcome::Exit(code),
+            _ => Outcome::Passed,
+        },
+    };
+    drop(env);
+
+    TestResult {
+        name: name.to_owned(),
+        output: String::from_utf8_lossy(&output).into_owned(),
+        outcome,
+    }
+}
+
+fn print_summary(reports: &[FileReport]) {
+    let failures = (reports.iter())
+        .flat_map(|r| r.tests.iter().map(move |t| (r, t)))
+        .filter(|(_, t)| !t.outcome.passed())
+        .collect::<Vec<_>>();
+
+    if !failures.is_empty() {
+        println!();
+        bprintln!(LRed, "failures:");
+        for (report, test) in failures.iter() {
+            println!();
+            let (name, path) = (&test.name, &report.path);
+            bprintln!(LRed, "---- {name} in {path} ----");
+            print!("{}", test.output);
+            match &test.outcome {
+                Outcome::Passed => (),
+                Outcome::Error(e, trace) => {
+                    println!("{}", e.display(&report.input));
+                    print!("{}", display_trace(trace, &report.input));
+                }
+                Outcome::Exit(code) => println!("exited with code {code}"),
+            }
+        }
+    }
+
+    let passed = reports
+        .iter()
+        .flat_map(|r| r.tests.iter())
+        .filter(|t| t.outcome.passed())
+        .count();
+    let filtered = reports.iter().map(|r| r.filtered).sum::<usize>();
+    let errors = reports.iter().filter(|r| !r.errors.is_empty()).count();
+
+    println!();
+    match failures.is_empty() && errors == 0 {
+        true => print!("test result: {}ok{ANSI_ESC}.", LGreen::bold()),
+        false => print!("test result: {}FAILED{ANSI_ESC}.", LRed::bold()),
+    }
+    print!(
+        " {passed} passed; {} failed; {filtered} filtered out",
+        failures.len()
+    );
+    if errors > 0 {
+        let file_s = if errors == 1 { "" } else { "s" };
+        print!("; {errors} file{file_s} with errors");
+    }
+    println!();
+}
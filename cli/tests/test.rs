@@ -1,4 +1,7 @@
+use std::io;
+
 use assert_cmd::Command;
+use cods::{Context, Env, Io};
 
 #[test]
 fn dyn_capture() {
@@ -74,3 +77,643 @@ fn builtin_fun_signature() {
         .failure()
         .stdout(output);
 }
+
+#[test]
+fn captured_output() {
+    let input = "\
+let x = 3
+print(\"x is\", x)
+println()
+flush()
+spill()
+";
+    let output = "\
+x is 3
+x = 3
+";
+
+    let mut ctx = Context::default();
+    let program = ctx.compile(input).unwrap();
+    let mut stdout = Vec::new();
+    let mut env = Env::new(Io::new(io::empty(), &mut stdout));
+    let mut stack = program.stack();
+    program.eval_with_env(&mut stack, &mut env).unwrap();
+    drop(env);
+
+    assert_eq!(String::from_utf8(stdout).unwrap(), output);
+}
+
+#[test]
+fn piped_stdin() {
+    Command::cargo_bin("cods")
+        .unwrap()
+        .arg("--")
+        .arg("println(read_int() * 2)")
+        .write_stdin("21\n")
+        .assert()
+        .success()
+        .stdout("42\n");
+}
+
+#[test]
+fn allow_read() {
+    Command::cargo_bin("cods")
+        .unwrap()
+        .arg("--allow-read=.")
+        .arg("--")
+        .arg("exists(\"Cargo.toml\")")
+        .assert()
+        .success()
+        .stdout("true\n");
+
+    Command::cargo_bin("cods")
+        .unwrap()
+        .arg("--")
+        .arg("exists(\"Cargo.toml\")")
+        .assert()
+        .failure();
+}
+
+#[test]
+fn run_with_args_and_exit_code() {
+    let path = std::env::temp_dir().join(format!("cods_exit_{}.cods", std::process::id()));
+    std::fs::write(
+        &path,
+        "\
+for i in 0..arg_count() {
+    println(arg(i))
+}
+exit(arg_count())
+",
+    )
+    .unwrap();
+
+    let assert = Command::cargo_bin("cods")
+        .unwrap()
+        .arg("run")
+        .arg(&path)
+        .arg("--")
+        .args(["a", "b", "c"])
+        .assert();
+    std::fs::remove_file(&path).unwrap();
+    assert.code(3).stdout("a\nb\nc\n");
+}
+
+#[test]
+fn exit_code_out_of_range() {
+    let output = "\
+[1;94m1 │[0m exit(256)
+  [1;94m│[0m      [1;91m^^^[0m
+  [1;94m│[0m [1;91mExit code `256` is outside of the range `0..=255`[0m[0m
+";
+
+    Command::cargo_bin("cods")
+        .unwrap()
+        .args(["--", "exit(256)"])
+        .assert()
+        .code(1)
+        .stdout(output);
+}
+
+#[test]
+fn run_file_with_shebang() {
+    let path = std::env::temp_dir().join(format!("cods_shebang_{}.cods", std::process::id()));
+    std::fs::write(
+        &path,
+        "\
+#!/usr/bin/env -S cods run
+println(arg(0))
+exit(arg_count())
+",
+    )
+    .unwrap();
+
+    let assert = Command::cargo_bin("cods")
+        .unwrap()
+        .arg(&path)
+        .arg("hello")
+        .assert();
+    std::fs::remove_file(&path).unwrap();
+    assert.code(1).stdout("hello\n");
+}
+
+#[test]
+fn seeded_random() {
+    let run = |seed: &str| {
+        let output = Command::cargo_bin("cods")
+            .unwrap()
+            .args(["--seed", seed, "--", "random_int(0, 1000000000)"])
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    assert_eq!(run("1234"), run("1234"));
+    assert_ne!(run("1234"), run("4321"));
+}
+
+#[test]
+fn trace() {
+    let path = std::env::temp_dir().join(format!("cods_trace_{}.cods", std::process::id()));
+    std::fs::write(
+        &path,
+        "\
+fn sqr(a: int) -> int {
+    a * a
+}
+let x = sqr(3)
+println(x)
+",
+    )
+    .unwrap();
+
+    let assert = Command::cargo_bin("cods")
+        .unwrap()
+        .arg("run")
+        .arg(&path)
+        .arg("--trace")
+        .assert();
+    std::fs::remove_file(&path).unwrap();
+    assert.success().stdout("9\n").stderr(
+        "\
+4: let x = sqr(3)
+  call sqr(3)
+  2: a * a
+  return sqr = 9
+5: println(x)
+",
+    );
+}
+
+#[test]
+fn profile() {
+    let path = std::env::temp_dir().join(format!("cods_profile_{}.cods", std::process::id()));
+    std::fs::write(
+        &path,
+        "\
+fn fib(n: int) -> int {
+    if n < 2 {
+        return n
+    }
+    fib(n - 1) + fib(n - 2)
+}
+println(fib(5))
+",
+    )
+    .unwrap();
+
+    let run = |folded: bool| {
+        let mut cmd = Command::cargo_bin("cods").unwrap();
+        cmd.arg("profile").arg(&path);
+        if folded {
+            cmd.arg("--folded");
+        }
+        let output = cmd.output().unwrap();
+        assert!(output.status.success());
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), "5\n");
+        String::from_utf8(output.stderr).unwrap()
+    };
+    let table = run(false);
+    let folded = run(true);
+    std::fs::remove_file(&path).unwrap();
+
+    let mut lines = table.lines();
+    assert_eq!(lines.next(), Some("Functions:"));
+    assert!(lines.nth(1).unwrap().starts_with("      15 "));
+    assert!(table.contains("\nHot lines:\n"));
+    assert!(table.contains("      15 "));
+    assert!(table.contains("  2: if n < 2 {\n"));
+    assert!(table.contains("  7: println(fib(5))\n"));
+
+    let stacks = folded
+        .lines()
+        .map(|l| l.rsplit_once(' ').unwrap().0)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        stacks,
+        [
+            "<main>",
+            "<main>;fib",
+            "<main>;fib;fib",
+            "<main>;fib;fib;fib",
+            "<main>;fib;fib;fib;fib",
+            "<main>;fib;fib;fib;fib;fib",
+        ]
+    );
+}
+
+#[test]
+fn profile_same_name() {
+    let path = std::env::temp_dir().join(format!("cods_profile_name_{}.cods", std::process::id()));
+    std::fs::write(
+        &path,
+        "\
+fn a() -> int {
+    fn helper() -> int {
+        1
+    }
+    helper()
+}
+fn b() -> int {
+    fn helper() -> int {
+        2
+    }
+    helper() + helper()
+}
+println(a() + b())
+",
+    )
+    .unwrap();
+
+    let output = Command::cargo_bin("cods")
+        .unwrap()
+        .arg("profile")
+        .arg(&path)
+        .output()
+        .unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(output.status.success());
+    let table = String::from_utf8(output.stderr).unwrap();
+
+    let helpers = (table.lines())
+        .filter(|l| l.contains("  helper "))
+        .map(|l| {
+            let calls = l.split_whitespace().next().unwrap();
+            (calls, l.rsplit_once("  ").unwrap().1)
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(helpers.len(), 2);
+    assert!(helpers.contains(&("1", "helper (line 2)")));
+    assert!(helpers.contains(&("2", "helper (line 8)")));
+}
+
+#[test]
+fn coverage() {
+    let dir = std::env::temp_dir();
+    let path = dir.join(format!("cods_coverage_{}.cods", std::process::id()));
+    let lcov_path = dir.join(format!("cods_coverage_{}.lcov", std::process::id()));
+    std::fs::write(
+        &path,
+        "\
+fn sign(a: int) -> int {
+    if a < 0 {
+        return -1
+    }
+    1
+}
+println(sign(4))
+",
+    )
+    .unwrap();
+
+    let output = Command::cargo_bin("cods")
+        .unwrap()
+        .arg("run")
+        .arg(&path)
+        .arg("--coverage")
+        .arg("--lcov")
+        .arg(&lcov_path)
+        .output()
+        .unwrap();
+    let lcov = std::fs::read_to_string(&lcov_path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    std::fs::remove_file(&lcov_path).unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "1\n");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("     if a < 0 {  "));
+    assert!(stderr.contains("[branches: 0, 1]"));
+    assert!(stderr.contains(" 3/4 (75.0%)\n"));
+    assert!(stderr.contains(" 1/2 (50.0%)\n"));
+
+    let expected = format!(
+        "\
+TN:
+SF:{}
+DA:2,1
+DA:3,0
+DA:5,1
+DA:7,1
+LF:4
+LH:3
+BRDA:2,0,0,0
+BRDA:2,0,1,1
+BRF:2
+BRH:1
+end_of_record
+",
+        path.display()
+    );
+    assert_eq!(lcov, expected);
+}
+
+#[test]
+fn test_runner() {
+    let dir = std::env::temp_dir().join(format!("cods_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("math.cods");
+    std::fs::write(
+        &path,
+        "\
+fn add(a: int, b: int) -> int {
+    a + b
+}
+let base = 10
+
+test fn adds() {
+    assert_eq(add(base, 2), 12)
+}
+
+test fn fails() {
+    println(\"computing\")
+    assert_eq(add(1, 2), 4)
+}
+",
+    )
+    .unwrap();
+
+    let pretty = Command::cargo_bin("cods")
+        .unwrap()
+        .arg("test")
+        .arg(&dir)
+        .output()
+        .unwrap();
+    let filtered = Command::cargo_bin("cods")
+        .unwrap()
+        .args(["test", "--filter", "add", "-f", "json"])
+        .arg(&dir)
+        .output()
+        .unwrap();
+    let json = Command::cargo_bin("cods")
+        .unwrap()
+        .args(["test", "-f", "json"])
+        .arg(&path)
+        .output()
+        .unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(pretty.status.code(), Some(1));
+    let stdout = String::from_utf8(pretty.stdout).unwrap();
+    assert!(stdout.contains(&format!("running 2 tests in {}", path.display())));
+    assert!(stdout.contains("test adds ... \x1b[1;92mok"));
+    assert!(stdout.contains("test fails ... \x1b[1;91mFAILED"));
+    assert!(stdout.contains(&format!("---- fails in {} ----", path.display())));
+    assert!(stdout.contains("computing\n"));
+    assert!(stdout.contains("     assert_eq(add(1, 2), 4)\n"));
+    assert!(stdout.contains(". 1 passed; 1 failed; 0 filtered out\n"));
+
+    assert!(filtered.status.success());
+    let expected = format!(
+        r#"{{"tests":[{{"path":"{}","name":"adds","passed":true,"output":""}}],"errors":[],"passed":1,"failed":0,"filtered":1}}
+"#,
+        path.display()
+    );
+    assert_eq!(String::from_utf8(filtered.stdout).unwrap(), expected);
+
+    assert_eq!(json.status.code(), Some(1));
+    let stdout = String::from_utf8(json.stdout).unwrap();
+    assert!(stdout
+        .contains(r#""name":"fails","passed":false,"output":"computing\n","error":{"spans":"#));
+    assert!(stdout.contains(
+        r#""desc":"Assertion failed, values are not equal\nleft │ right\n3    ≠ 4","trace":[]"#
+    ));
+    assert!(stdout.ends_with(
+        r#""errors":[],"passed":1,"failed":1,"filtered":0}
+"#
+    ));
+}
+
+fn lsp_message(body: &str) -> String {
+    format!("Content-Length: {}\r\n\r\n{body}", body.len())
+}
+
+#[test]
+fn lsp() {
+    let uri = "file:///test.cods";
+    let text = r#"let x = 3\nfn sqr(a: int) -> int { a * a }\nprintln(sqr(x), \"ä\", y)"#;
+    let messages = [
+        r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{}}"#.to_owned(),
+        r#"{"jsonrpc":"2.0","method":"initialized","params":{}}"#.to_owned(),
+        format!(
+            r#"{{"jsonrpc":"2.0","method":"textDocument/didOpen","params":{{"textDocument":{{"uri":"{uri}","languageId":"cods","version":1,"text":"{text}"}}}}}}"#
+        ),
+        // replace the undefined `y` with `x`, after a non ASCII char
+        format!(
+            r#"{{"jsonrpc":"2.0","method":"textDocument/didChange","params":{{"textDocument":{{"uri":"{uri}","version":2}},"contentChanges":[{{"range":{{"start":{{"line":2,"character":21}},"end":{{"line":2,"character":22}}}},"text":"x"}}]}}}}"#
+        ),
+        format!(
+            r#"{{"jsonrpc":"2.0","id":2,"method":"textDocument/hover","params":{{"textDocument":{{"uri":"{uri}"}},"position":{{"line":2,"character":9}}}}}}"#
+        ),
+        format!(
+            r#"{{"jsonrpc":"2.0","id":3,"method":"textDocument/definition","params":{{"textDocument":{{"uri":"{uri}"}},"position":{{"line":2,"character":21}}}}}}"#
+        ),
+        format!(
+            r#"{{"jsonrpc":"2.0","id":4,"method":"textDocument/completion","params":{{"textDocument":{{"uri":"{uri}"}},"position":{{"line":0,"character":0}}}}}}"#
+        ),
+        r#"{"jsonrpc":"2.0","id":5,"method":"shutdown"}"#.to_owned(),
+        r#"{"jsonrpc":"2.0","method":"exit"}"#.to_owned(),
+    ];
+    let input = messages.iter().map(|m| lsp_message(m)).collect::<String>();
+
+    let output = Command::cargo_bin("cods")
+        .unwrap()
+        .arg("lsp")
+        .write_stdin(input)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    assert!(stdout.contains(r#""id":1,"result":{"capabilities""#));
+    // after opening the document `y` is undefined
+    assert!(stdout.contains(
+        r#""diagnostics":[{"range":{"start":{"line":2,"character":21},"end":{"line":2,"character":22}},"severity":1,"source":"cods","message":"Undefined variable `y`"}]"#
+    ));
+    // after the change there are no errors left
+    assert!(stdout.contains(r#""diagnostics":[]"#));
+    assert!(stdout.contains(
+        r#""id":2,"result":{"contents":{"kind":"markdown","value":"```cods\nfn sqr(a: int) -> int\n```"},"range":{"start":{"line":2,"character":8},"end":{"line":2,"character":11}}}"#
+    ));
+    assert!(stdout.contains(
+        r#""id":3,"result":{"uri":"file:///test.cods","range":{"start":{"line":0,"character":4},"end":{"line":0,"character":5}}}"#
+    ));
+    assert!(stdout.contains(r#"{"label":"sqr","kind":3,"detail":"fn sqr(a: int) -> int"}"#));
+    assert!(stdout.contains(r#"{"label":"PI","kind":21"#));
+    assert!(stdout.contains(r#"{"label":"println","kind":3,"detail":"fn println(..any)"}"#));
+    assert!(stdout.contains(r#""id":5,"result":null"#));
+}
+
+#[test]
+fn dap() {
+    let path = std::env::temp_dir().join(format!("cods_dap_{}.cods", std::process::id()));
+    std::fs::write(
+        &path,
+        "\
+fn add(a: int, b: int) -> int {
+    let sum = a + b
+    sum
+}
+let x = 1
+let y = add(x, 2)
+println(y)
+",
+    )
+    .unwrap();
+    let path_str = path.to_str().unwrap();
+
+    let messages = [
+        r#"{"seq":1,"type":"request","command":"initialize","arguments":{}}"#.to_owned(),
+        format!(
+            r#"{{"seq":2,"type":"request","command":"launch","arguments":{{"program":"{path_str}"}}}}"#
+        ),
+        format!(
+            r#"{{"seq":3,"type":"request","command":"setBreakpoints","arguments":{{"source":{{"path":"{path_str}"}},"breakpoints":[{{"line":2}}]}}}}"#
+        ),
+        r#"{"seq":4,"type":"request","command":"configurationDone"}"#.to_owned(),
+        r#"{"seq":5,"type":"request","command":"stackTrace","arguments":{"threadId":1}}"#
+            .to_owned(),
+        r#"{"seq":6,"type":"request","command":"scopes","arguments":{"frameId":1}}"#.to_owned(),
+        r#"{"seq":7,"type":"request","command":"variables","arguments":{"variablesReference":2}}"#
+            .to_owned(),
+        r#"{"seq":8,"type":"request","command":"stepOut","arguments":{"threadId":1}}"#.to_owned(),
+        r#"{"seq":9,"type":"request","command":"variables","arguments":{"variablesReference":1}}"#
+            .to_owned(),
+        r#"{"seq":10,"type":"request","command":"continue","arguments":{"threadId":1}}"#.to_owned(),
+        r#"{"seq":11,"type":"request","command":"disconnect"}"#.to_owned(),
+    ];
+    let input = messages.iter().map(|m| lsp_message(m)).collect::<String>();
+
+    let output = Command::cargo_bin("cods")
+        .unwrap()
+        .arg("dap")
+        .write_stdin(input)
+        .output()
+        .unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    assert!(stdout.contains(r#""event":"stopped","body":{"reason":"breakpoint""#));
+    assert!(stdout.contains(r#""stackFrames":[{"id":1,"name":"add","#));
+    assert!(stdout.contains(r#""line":2,"column":5},{"id":0,"name":"<main>","#));
+    assert!(stdout.contains(
+        r#""scopes":[{"name":"Locals","variablesReference":2,"expensive":false},{"name":"Globals","variablesReference":1,"expensive":false}]"#
+    ));
+    assert!(stdout.contains(
+        r#""request_seq":7,"success":true,"command":"variables","body":{"variables":[{"name":"a","value":"1","type":"int","variablesReference":0},{"name":"b","value":"2","type":"int","variablesReference":0}]}"#
+    ));
+    // stepping out of `add` stops at the next statement of the caller
+    assert!(stdout.contains(r#""event":"stopped","body":{"reason":"step""#));
+    assert!(stdout.contains(
+        r#"{"name":"x","value":"1","type":"int","variablesReference":0},{"name":"y","value":"3","type":"int","variablesReference":0}"#
+    ));
+    assert!(stdout.contains(r#""event":"output","body":{"category":"stdout","output":"3"}"#));
+    assert!(stdout.contains(r#""event":"exited","body":{"exitCode":0}"#));
+    assert!(stdout.contains(r#""request_seq":11,"success":true,"command":"disconnect""#));
+}
+
+#[test]
+fn runtime_error_stack_trace() {
+    let input = "\
+fn div(a: int, b: int) -> int {
+    a / b
+}
+fn outer(x: int) -> int {
+    div(x, 0) + 1
+}
+outer(3)
+";
+    let output = "\
+[1;94m2 │[0m     a / b
+  [1;94m│[0m     [1;91m^[0m   [1;91m^[0m
+  [1;94m│[0m [1;91mAttempted to divide by 0[0m[0m
+  [1;94m= in `div`, called from:[0m
+[1;94m5 │[0m     div(x, 0) + 1
+  [1;94m│[0m     [1;94m^^^^^^^^^[0m
+  [1;94m= in `outer`, called from:[0m
+[1;94m7 │[0m outer(3)
+  [1;94m│[0m [1;94m^^^^^^^^[0m
+";
+
+    Command::cargo_bin("cods")
+        .unwrap()
+        .arg("--")
+        .arg(input)
+        .assert()
+        .failure()
+        .stdout(output);
+
+    let output = r#"{"errors":[],"warnings":[]}
+{"errors":[{"spans":[{"start":{"col":4,"line":1},"end":{"col":5,"line":1}},{"start":{"col":8,"line":1},"end":{"col":9,"line":1}}],"desc":"Attempted to divide by 0","trace":[{"name":"div","span":{"start":{"col":4,"line":4},"end":{"col":13,"line":4}}},{"name":"outer","span":{"start":{"col":0,"line":6},"end":{"col":8,"line":6}}}]}],"warnings":[]}
+"#;
+    Command::cargo_bin("cods")
+        .unwrap()
+        .args(["--format", "json", "--"])
+        .arg(input)
+        .assert()
+        .failure()
+        .stdout(output);
+}
+
+#[test]
+fn json_multiline_desc() {
+    let output = r#"{"errors":[],"warnings":[]}
+{"errors":[{"spans":[{"start":{"col":10,"line":0},"end":{"col":11,"line":0}},{"start":{"col":13,"line":0},"end":{"col":14,"line":0}}],"desc":"Assertion failed, values are not equal\nleft │ right\n1    ≠ 2","trace":[]}],"warnings":[]}
+"#;
+    Command::cargo_bin("cods")
+        .unwrap()
+        .args(["--format", "json", "--", "assert_eq(1, 2)"])
+        .assert()
+        .failure()
+        .stdout(output);
+}
+
+#[test]
+fn debug() {
+    let path = std::env::temp_dir().join(format!("cods_debug_{}.cods", std::process::id()));
+    std::fs::write(
+        &path,
+        "\
+fn add(a: int, b: int) -> int {
+    let sum = a + b
+    sum
+}
+let x = 1
+let y = add(x, 2)
+println(y)
+",
+    )
+    .unwrap();
+
+    let commands = "\
+break 2
+continue
+locals
+backtrace
+print a * 10 + b
+finish
+locals
+continue
+";
+    let output = Command::cargo_bin("cods")
+        .unwrap()
+        .arg("debug")
+        .arg(&path)
+        .write_stdin(commands)
+        .output()
+        .unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    assert!(stdout.contains("Breakpoint set at line 2"));
+    assert!(stdout.contains("Breakpoint hit in `add`"));
+    assert!(stdout.contains("     let sum = a + b\n"));
+    assert!(stdout.contains("a: int = 1\nb: int = 2\n"));
+    assert!(stdout.contains("#0 `add` at 2:5\n#1 `<main>` at 6:9\n"));
+    assert!(stdout.contains("12\n"));
+    assert!(stdout.contains("x: int = 1\ny: int = 3\n"));
+    assert!(stdout.ends_with("3\n"));
+}
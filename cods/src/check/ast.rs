@@ -1,6 +1,6 @@
 use std::fmt::Debug;
 
-use crate::{DataType, FunRef, Span, Val, VarRef};
+use crate::{DataType, FunRef, HostFunRef, Span, Val, VarRef};
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct Asts {
@@ -79,6 +79,7 @@ pub enum AstT {
     FunCall(FunRef, Vec<Ast>),
     Return(Box<Ast>),
     BuiltinFunCall(BuiltinFunCall, Vec<Ast>),
+    HostFunCall(HostFunRef, Vec<Ast>),
     Spill(Vec<(String, VarRef)>),
 }
 
@@ -87,33 +88,45 @@ pub enum Op {
     Not,
     NegInt,
     NegFloat,
+    NegDuration,
     RangeIn,
     RangeEx,
     AddInt,
     AddFloat,
+    AddDuration,
     SubInt,
     SubFloat,
+    SubDuration,
     MulInt,
     MulFloat,
+    MulDurationInt,
+    MulIntDuration,
     DivInt,
     DivFloat,
+    DivDurationInt,
+    DivDuration,
     PowInt,
     PowFloat,
     PowFloatInt,
     RemInt,
     RemFloat,
+    RemDuration,
     RemEuclidInt,
     FactorialInt,
     Eq,
     Ne,
     LtInt,
     LtFloat,
+    LtDuration,
     LeInt,
     LeFloat,
+    LeDuration,
     GtInt,
     GtFloat,
+    GtDuration,
     GeInt,
     GeFloat,
+    GeDuration,
     Or,
     And,
     BwOrInt,
@@ -206,21 +219,43 @@ impl ForLoop {
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct Fun {
-    pub params: Vec<VarRef>,
+    pub name: String,
+    pub params: Vec<FunParam>,
+    pub return_type: DataType,
     pub block: Vec<Ast>,
     pub frame_size: usize,
 }
 
 impl Fun {
-    pub fn new(params: Vec<VarRef>, block: Vec<Ast>, frame_size: usize) -> Self {
+    pub fn new(
+        name: String,
+        params: Vec<FunParam>,
+        return_type: DataType,
+        block: Vec<Ast>,
+        frame_size: usize,
+    ) -> Self {
         Self {
+            name,
             params,
+            return_type,
             block,
             frame_size,
         }
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FunParam {
+    pub var: VarRef,
+    pub data_type: DataType,
+}
+
+impl FunParam {
+    pub const fn new(var: VarRef, data_type: DataType) -> Self {
+        Self { var, data_type }
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum BuiltinFunCall {
     PowInt,
@@ -256,8 +291,32 @@ pub enum BuiltinFunCall {
     Round,
     Print,
     Println,
+    Format,
+    Printf,
     Flush,
+    ReadLine,
+    ReadAll,
+    ReadInt,
+    ReadFloat,
+    ReadFile,
+    WriteFile,
+    AppendFile,
+    Exists,
+    ListDir,
+    ArgCount,
+    Arg,
+    Env,
+    Exit,
     Assert,
     AssertEq,
+    AssertNe,
+    AssertApprox,
+    Panic,
+    Now,
+    Monotonic,
     Sleep,
+    Random,
+    RandomInt,
+    RandomRange,
+    Seed,
 }
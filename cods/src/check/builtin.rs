@@ -40,12 +40,36 @@ pub enum BuiltinFun {
     Round,
     Print,
     Println,
+    Format,
+    Printf,
     Flush,
+    ReadLine,
+    ReadAll,
+    ReadInt,
+    ReadFloat,
+    ReadFile,
+    WriteFile,
+    AppendFile,
+    Exists,
+    ListDir,
+    ArgCount,
+    Arg,
+    Env,
+    Exit,
     Spill,
     SpillLocal,
     Assert,
     AssertEq,
+    AssertNe,
+    AssertApprox,
+    Panic,
+    Now,
+    Monotonic,
     Sleep,
+    Random,
+    RandomInt,
+    RandomRange,
+    Seed,
 }
 
 #[derive(Clone, Copy, PartialEq, Eq)]
@@ -90,10 +114,34 @@ impl BuiltinFun {
             BuiltinFun::Round => SignatureKind::Normal(&ROUND_SIGNATURES),
             BuiltinFun::Print => SignatureKind::Normal(&PRINT_SIGNATURES),
             BuiltinFun::Println => SignatureKind::Normal(&PRINTLN_SIGNATURES),
+            BuiltinFun::Format => SignatureKind::Normal(&FORMAT_SIGNATURES),
+            BuiltinFun::Printf => SignatureKind::Normal(&PRINTF_SIGNATURES),
             BuiltinFun::Flush => SignatureKind::Normal(&FLUSH_SIGNATURES),
+            BuiltinFun::ReadLine => SignatureKind::Normal(&READ_LINE_SIGNATURES),
+            BuiltinFun::ReadAll => SignatureKind::Normal(&READ_ALL_SIGNATURES),
+            BuiltinFun::ReadInt => SignatureKind::Normal(&READ_INT_SIGNATURES),
+            BuiltinFun::ReadFloat => SignatureKind::Normal(&READ_FLOAT_SIGNATURES),
+            BuiltinFun::ReadFile => SignatureKind::Normal(&READ_FILE_SIGNATURES),
+            BuiltinFun::WriteFile => SignatureKind::Normal(&WRITE_FILE_SIGNATURES),
+            BuiltinFun::AppendFile => SignatureKind::Normal(&APPEND_FILE_SIGNATURES),
+            BuiltinFun::Exists => SignatureKind::Normal(&EXISTS_SIGNATURES),
+            BuiltinFun::ListDir => SignatureKind::Normal(&LIST_DIR_SIGNATURES),
+            BuiltinFun::ArgCount => SignatureKind::Normal(&ARG_COUNT_SIGNATURES),
+            BuiltinFun::Arg => SignatureKind::Normal(&ARG_SIGNATURES),
+            BuiltinFun::Env => SignatureKind::Normal(&ENV_SIGNATURES),
+            BuiltinFun::Exit => SignatureKind::Normal(&EXIT_SIGNATURES),
             BuiltinFun::Assert => SignatureKind::Normal(&ASSERT_SIGNATURES),
             BuiltinFun::AssertEq => SignatureKind::Normal(&ASSERT_EQ_SIGNATURES),
+            BuiltinFun::AssertNe => SignatureKind::Normal(&ASSERT_NE_SIGNATURES),
+            BuiltinFun::AssertApprox => SignatureKind::Normal(&ASSERT_APPROX_SIGNATURES),
+            BuiltinFun::Panic => SignatureKind::Normal(&PANIC_SIGNATURES),
+            BuiltinFun::Now => SignatureKind::Normal(&NOW_SIGNATURES),
+            BuiltinFun::Monotonic => SignatureKind::Normal(&MONOTONIC_SIGNATURES),
             BuiltinFun::Sleep => SignatureKind::Normal(&SLEEP_SIGNATURES),
+            BuiltinFun::Random => SignatureKind::Normal(&RANDOM_SIGNATURES),
+            BuiltinFun::RandomInt => SignatureKind::Normal(&RANDOM_INT_SIGNATURES),
+            BuiltinFun::RandomRange => SignatureKind::Normal(&RANDOM_RANGE_SIGNATURES),
+            BuiltinFun::Seed => SignatureKind::Normal(&SEED_SIGNATURES),
             BuiltinFun::Spill => SignatureKind::Spill(SpillKind::Global),
             BuiltinFun::SpillLocal => SignatureKind::Spill(SpillKind::Local),
         }
@@ -185,7 +233,7 @@ macro_rules! fun_signature {
         (
             $builtin,
             FunSignature::new(
-                &[$($params),* $last],
+                &[$($params,)* $last],
                 Repetition::ZeroOrMore,
                 $return_type,
             )
@@ -195,7 +243,7 @@ macro_rules! fun_signature {
         (
             $builtin,
             FunSignature::new(
-                &[$($params),* $last],
+                &[$($params,)* $last],
                 Repetition::OneOrMore,
                 $return_type,
             )
@@ -290,20 +338,93 @@ pub const PRINT_SIGNATURES: [(BuiltinFunCall, FunSignature); 1] = fun_signatures
 pub const PRINTLN_SIGNATURES: [(BuiltinFunCall, FunSignature); 1] = fun_signatures! {
     Println(..Any) -> Unit
 };
+pub const FORMAT_SIGNATURES: [(BuiltinFunCall, FunSignature); 1] = fun_signatures! {
+    Format(Str, ..Any) -> Str
+};
+pub const PRINTF_SIGNATURES: [(BuiltinFunCall, FunSignature); 1] = fun_signatures! {
+    Printf(Str, ..Any) -> Unit
+};
 pub const FLUSH_SIGNATURES: [(BuiltinFunCall, FunSignature); 1] = fun_signatures! {
     Flush() -> Unit
 };
-pub const ASSERT_SIGNATURES: [(BuiltinFunCall, FunSignature); 1] = fun_signatures! {
+pub const READ_LINE_SIGNATURES: [(BuiltinFunCall, FunSignature); 1] = fun_signatures! {
+    ReadLine() -> Str
+};
+pub const READ_ALL_SIGNATURES: [(BuiltinFunCall, FunSignature); 1] = fun_signatures! {
+    ReadAll() -> Str
+};
+pub const READ_INT_SIGNATURES: [(BuiltinFunCall, FunSignature); 1] = fun_signatures! {
+    ReadInt() -> Int
+};
+pub const READ_FLOAT_SIGNATURES: [(BuiltinFunCall, FunSignature); 1] = fun_signatures! {
+    ReadFloat() -> Float
+};
+pub const READ_FILE_SIGNATURES: [(BuiltinFunCall, FunSignature); 1] = fun_signatures! {
+    ReadFile(Str) -> Str
+};
+pub const WRITE_FILE_SIGNATURES: [(BuiltinFunCall, FunSignature); 1] = fun_signatures! {
+    WriteFile(Str, Str) -> Unit
+};
+pub const APPEND_FILE_SIGNATURES: [(BuiltinFunCall, FunSignature); 1] = fun_signatures! {
+    AppendFile(Str, Str) -> Unit
+};
+pub const EXISTS_SIGNATURES: [(BuiltinFunCall, FunSignature); 1] = fun_signatures! {
+    Exists(Str) -> Bool
+};
+pub const LIST_DIR_SIGNATURES: [(BuiltinFunCall, FunSignature); 1] = fun_signatures! {
+    ListDir(Str) -> Str
+};
+pub const ARG_COUNT_SIGNATURES: [(BuiltinFunCall, FunSignature); 1] = fun_signatures! {
+    ArgCount() -> Int
+};
+pub const ARG_SIGNATURES: [(BuiltinFunCall, FunSignature); 1] = fun_signatures! {
+    Arg(Int) -> Str
+};
+pub const ENV_SIGNATURES: [(BuiltinFunCall, FunSignature); 1] = fun_signatures! {
+    Env(Str) -> Str
+};
+pub const EXIT_SIGNATURES: [(BuiltinFunCall, FunSignature); 1] = fun_signatures! {
+    Exit(Int) -> Never
+};
+pub const ASSERT_SIGNATURES: [(BuiltinFunCall, FunSignature); 2] = fun_signatures! {
     Assert(Bool) -> Unit
+    Assert(Bool, Str) -> Unit
+};
+/// Both values additionally have to be of the same type.
+pub const ASSERT_EQ_SIGNATURES: [(BuiltinFunCall, FunSignature); 2] = fun_signatures! {
+    AssertEq(Any, Any) -> Unit
+    AssertEq(Any, Any, Str) -> Unit
+};
+/// Both values additionally have to be of the same type.
+pub const ASSERT_NE_SIGNATURES: [(BuiltinFunCall, FunSignature); 2] = fun_signatures! {
+    AssertNe(Any, Any) -> Unit
+    AssertNe(Any, Any, Str) -> Unit
+};
+pub const ASSERT_APPROX_SIGNATURES: [(BuiltinFunCall, FunSignature); 2] = fun_signatures! {
+    AssertApprox(Float, Float, Float) -> Unit
+    AssertApprox(Float, Float, Float, Str) -> Unit
 };
-pub const ASSERT_EQ_SIGNATURES: [(BuiltinFunCall, FunSignature); 6] = fun_signatures! {
-    AssertEq(Int, Int) -> Unit
-    AssertEq(Float, Float) -> Unit
-    AssertEq(Bool, Bool) -> Unit
-    AssertEq(Str, Str) -> Unit
-    AssertEq(Range, Range) -> Unit
-    AssertEq(Unit, Unit) -> Unit
+pub const PANIC_SIGNATURES: [(BuiltinFunCall, FunSignature); 1] = fun_signatures! {
+    Panic(Str) -> Never
+};
+pub const NOW_SIGNATURES: [(BuiltinFunCall, FunSignature); 1] = fun_signatures! {
+    Now() -> Int
+};
+pub const MONOTONIC_SIGNATURES: [(BuiltinFunCall, FunSignature); 1] = fun_signatures! {
+    Monotonic() -> Duration
 };
 pub const SLEEP_SIGNATURES: [(BuiltinFunCall, FunSignature); 1] = fun_signatures! {
-    Sleep(Int) -> Unit
+    Sleep(Duration) -> Unit
+};
+pub const RANDOM_SIGNATURES: [(BuiltinFunCall, FunSignature); 1] = fun_signatures! {
+    Random() -> Float
+};
+pub const RANDOM_INT_SIGNATURES: [(BuiltinFunCall, FunSignature); 1] = fun_signatures! {
+    RandomInt(Int, Int) -> Int
+};
+pub const RANDOM_RANGE_SIGNATURES: [(BuiltinFunCall, FunSignature); 1] = fun_signatures! {
+    RandomRange(Range) -> Int
+};
+pub const SEED_SIGNATURES: [(BuiltinFunCall, FunSignature); 1] = fun_signatures! {
+    Seed(Int) -> Unit
 };
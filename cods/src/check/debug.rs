@@ -0,0 +1,133 @@
+use crate::{DataType, FunRef, Pos, Span, VarRef};
+
+/// Information that is lost when checking a program, but needed to inspect it while it is
+/// evaluated: the names, types and live ranges of the variables stored in each stack frame
+/// slot, and the names of all functions.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DebugInfo {
+    /// Variables stored in the global frame.
+    pub globals: Vec<VarInfo>,
+    /// Functions indexed by [`FunRef`].
+    pub funs: Vec<FunInfo>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FunInfo {
+    pub name: String,
+    /// The span of the identifier where the function is defined.
+    pub span: Span,
+    /// Variables stored in the frame of the function, including parameters.
+    pub vars: Vec<VarInfo>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VarInfo {
+    pub name: String,
+    pub var: VarRef,
+    pub data_type: DataType,
+    /// The span of the identifier where the variable is defined, `None` if it was defined by
+    /// the host application.
+    pub span: Option<Span>,
+    /// The range in which the variable is accessible, from its definition to the end of the
+    /// enclosing scope. Variables of the global scope stay live until the end of the input.
+    pub live: Span,
+}
+
+impl VarInfo {
+    pub fn new(name: String, var: VarRef, data_type: DataType, span: Option<Span>) -> Self {
+        let start = span.map_or(Pos::new(0, 0), |s| s.start);
+        Self {
+            name,
+            var,
+            data_type,
+            span,
+            live: Span::new(start, Pos::new(u32::MAX, u32::MAX)),
+        }
+    }
+
+    pub fn is_live(&self, pos: Pos) -> bool {
+        self.live.start <= pos && pos < self.live.end
+    }
+
+    /// The index of the slot inside its frame.
+    pub const fn slot(&self) -> usize {
+        match self.var {
+            VarRef::Local(i) | VarRef::Global(i) => i,
+        }
+    }
+}
+
+impl DebugInfo {
+    pub fn clear(&mut self) {
+        self.globals.clear();
+        self.funs.clear();
+    }
+
+    pub fn def_fun(&mut self, fun: FunRef, name: String, span: Span) {
+        if self.funs.len() <= fun.0 {
+            self.funs.resize(fun.0 + 1, FunInfo::default());
+        }
+        let info = &mut self.funs[fun.0];
+        info.name = name;
+        info.span = span;
+    }
+
+    pub fn fun(&self, fun: FunRef) -> Option<&FunInfo> {
+        self.funs.get(fun.0)
+    }
+
+    /// The name of the function, or `<main>` for top-level code.
+    pub fn fun_name(&self, fun: Option<FunRef>) -> &str {
+        match fun.and_then(|f| self.fun(f)) {
+            Some(f) => &f.name,
+            None => "<main>",
+        }
+    }
+
+    /// Record a variable defined in the frame of `fun`, or the global frame if `None`.
+    pub fn def_var(&mut self, fun: Option<FunRef>, var: VarInfo) {
+        match fun {
+            Some(f) => {
+                if self.funs.len() <= f.0 {
+                    self.funs.resize(f.0 + 1, FunInfo::default());
+                }
+                self.funs[f.0].vars.push(var);
+            }
+            None => self.globals.push(var),
+        }
+    }
+
+    /// End the live range of a variable when its scope is left. Slots are only reused after
+    /// the scope of the previous variable has ended, so the last one using the slot is ended.
+    pub fn end_var(&mut self, fun: Option<FunRef>, var: VarRef, end: Pos) {
+        let vars = match fun {
+            Some(f) => self.funs.get_mut(f.0).map(|f| &mut f.vars),
+            None => Some(&mut self.globals),
+        };
+        if let Some(v) = vars.and_then(|vars| vars.iter_mut().rev().find(|v| v.var == var)) {
+            v.live.end = end;
+        }
+    }
+
+    /// All variables that are stored in the frame of `fun`, or the global frame if `None`.
+    pub fn frame_vars(&self, fun: Option<FunRef>) -> &[VarInfo] {
+        match fun {
+            Some(f) => self.fun(f).map_or(&[], |f| &f.vars),
+            None => &self.globals,
+        }
+    }
+
+    /// The variables of a frame that are live at `pos`, ordered by their slot.
+    pub fn live_vars(&self, fun: Option<FunRef>, pos: Pos) -> Vec<&VarInfo> {
+        let mut vars: Vec<&VarInfo> = Vec::new();
+        for v in self.frame_vars(fun).iter().filter(|v| v.is_live(pos)) {
+            // a shadowed variable in the same scope is still live, but not accessible
+            match vars.iter_mut().find(|o| o.name == v.name) {
+                Some(o) => *o = v,
+                None => vars.push(v),
+            }
+        }
+        vars.sort_by_key(|v| v.var);
+        vars
+    }
+}
@@ -1,13 +1,27 @@
+use std::fmt::{self, Debug};
+use std::sync::Arc;
+
 use crate::ast::Fun;
+use crate::{FunSignature, Val};
+
+/// The signature of a function implemented by the host application.
+pub type HostFn = dyn Fn(&[Val]) -> Result<Val, String> + Send + Sync;
 
 #[derive(Clone, Debug, Default)]
 pub struct Funs {
     items: Vec<Option<Fun>>,
+    /// Names of top-level functions.
+    names: Vec<(String, FunRef)>,
+    /// Functions marked with `test`, in definition order.
+    tests: Vec<(String, FunRef)>,
+    host: Vec<HostFun>,
 }
 
 impl Funs {
     pub fn clear(&mut self) {
         self.items.clear();
+        self.names.clear();
+        self.tests.clear();
     }
 
     pub fn push(&mut self) -> FunRef {
@@ -35,7 +49,89 @@ impl Funs {
             .as_ref()
             .expect("Expected value to be initialized")
     }
+
+    /// All initialized functions.
+    pub fn iter(&self) -> impl Iterator<Item = &Fun> {
+        self.items.iter().flatten()
+    }
+
+    pub fn def_name(&mut self, name: &str, fun_ref: FunRef) {
+        self.names.push((name.to_owned(), fun_ref));
+    }
+
+    pub fn def_test(&mut self, name: &str, fun_ref: FunRef) {
+        self.tests.push((name.to_owned(), fun_ref));
+    }
+
+    /// The names of all test functions, in definition order.
+    pub fn tests(&self) -> &[(String, FunRef)] {
+        &self.tests
+    }
+
+    /// Find a top-level function by name.
+    pub fn find(&self, name: &str) -> Option<FunRef> {
+        self.names
+            .iter()
+            .rev()
+            .find(|(n, _)| n == name)
+            .map(|(_, f)| *f)
+    }
+
+    pub fn push_host(&mut self, fun: HostFun) -> HostFunRef {
+        let fun_ref = self.host.len();
+        self.host.push(fun);
+        HostFunRef(fun_ref)
+    }
+
+    pub fn get_host(&self, fun_ref: HostFunRef) -> &HostFun {
+        self.host
+            .get(fun_ref.0)
+            .expect("Expected host function reference to be in bounds")
+    }
+
+    /// All overloads of the host function with this name.
+    pub fn find_host(&self, name: &str) -> Vec<HostFunRef> {
+        self.host
+            .iter()
+            .enumerate()
+            .filter(|(_, f)| f.name == name)
+            .map(|(i, _)| HostFunRef(i))
+            .collect()
+    }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct FunRef(pub usize);
+
+#[derive(Clone)]
+pub struct HostFun {
+    pub name: String,
+    pub signature: FunSignature,
+    pub fun: Arc<HostFn>,
+}
+
+impl Debug for HostFun {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HostFun")
+            .field("name", &self.name)
+            .field("signature", &self.signature)
+            .finish_non_exhaustive()
+    }
+}
+
+impl HostFun {
+    pub fn new(
+        name: impl Into<String>,
+        signature: FunSignature,
+        fun: impl Fn(&[Val]) -> Result<Val, String> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            signature,
+            fun: Arc::new(fun),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct HostFunRef(pub usize);
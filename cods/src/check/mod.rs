@@ -2,20 +2,26 @@ use std::cmp::max;
 use std::rc::Rc;
 
 use crate::cst::{self, Cst};
-use crate::{Context, Infix, InfixT, Postfix, PostfixT, Prefix, PrefixT, Span, VarRef};
+use crate::{
+    Context, Format, Infix, InfixT, Postfix, PostfixT, Prefix, PrefixT, Span, Val, VarRef,
+};
 
 pub use ast::{Ast, AstT, Asts, BuiltinFunCall};
 pub use builtin::{BuiltinConst, BuiltinFun, FunSignature, Repetition, SignatureKind, SpillKind};
+pub use debug::*;
 pub use funs::*;
 pub use op::OpSignature;
 pub use scope::*;
+pub use symbol::*;
 pub use types::*;
 
 pub mod ast;
 mod builtin;
+mod debug;
 mod funs;
 mod op;
 mod scope;
+mod symbol;
 #[cfg(test)]
 mod test;
 mod types;
@@ -24,12 +30,32 @@ mod types;
 pub struct Checker {
     pub scopes: Scopes,
     pub funs: Funs,
+    pub debug: DebugInfo,
+    /// The initial values of the globals defined by the host application.
+    pub host_globals: Vec<(VarRef, Val)>,
 }
 
 impl Checker {
     pub fn clear(&mut self) {
         self.scopes.clear();
         self.funs.clear();
+        self.debug.clear();
+        self.host_globals.clear();
+    }
+
+    /// Register a function implemented by the host application, which can be called from
+    /// scripts like a builtin function. Registering multiple functions with the same name
+    /// overloads them. Builtin functions can't be shadowed.
+    pub fn register_fun(
+        &mut self,
+        name: &str,
+        signature: FunSignature,
+        fun: impl Fn(&[Val]) -> Result<Val, String> + Send + Sync + 'static,
+    ) -> crate::Result<HostFunRef> {
+        if name.parse::<BuiltinFun>().is_ok() {
+            return Err(crate::Error::RedefinedBuiltinFun(name.to_owned(), None));
+        }
+        Ok(self.funs.push_host(HostFun::new(name, signature, fun)))
     }
 }
 
@@ -145,9 +171,10 @@ impl Context {
     ) -> crate::Result<Ast> {
         let span = block.span();
 
-        let (asts, returns) = self.with_new_scope(checker, ExecPolicy::Once, |ctx, checker| {
-            ctx.check_types(checker, block.csts, is_expr)
-        })?;
+        let (asts, returns) =
+            self.with_new_scope(checker, ExecPolicy::Once, span, |ctx, checker| {
+                ctx.check_types(checker, block.csts, is_expr)
+            })?;
 
         let data_type = asts
             .last()
@@ -182,8 +209,9 @@ impl Context {
 
                 cases.push(ast::CondBlock::new(cond, Vec::new()));
             } else {
+                let block_span = i.if_block.block.span();
                 let (block, block_returns) =
-                    self.with_new_scope(checker, ExecPolicy::Once, |ctx, checker| {
+                    self.with_new_scope(checker, ExecPolicy::Once, block_span, |ctx, checker| {
                         ctx.check_types(checker, i.if_block.block.csts, is_expr)
                     })?;
                 max_frame_size = checker.scopes.frame_size();
@@ -207,7 +235,7 @@ impl Context {
             for uninit_var in uninitialized_vars.iter_mut() {
                 let var = checker
                     .scopes
-                    .var_mut(uninit_var.ident.ident)
+                    .var_mut(uninit_var.ident)
                     .expect("This variable should exist");
 
                 // set the initial resultant state of initialization for this if statement
@@ -226,7 +254,7 @@ impl Context {
             } else {
                 let block_span = e.block.span();
                 let (block, block_returns) =
-                    self.with_new_scope(checker, ExecPolicy::Once, |ctx, checker| {
+                    self.with_new_scope(checker, ExecPolicy::Once, block_span, |ctx, checker| {
                         ctx.check_types(checker, e.block.csts, is_expr)
                     })?;
                 max_frame_size = max(max_frame_size, checker.scopes.frame_size());
@@ -261,7 +289,7 @@ impl Context {
             for uninit_var in uninitialized_vars.iter_mut() {
                 let var = checker
                     .scopes
-                    .var_mut(uninit_var.ident.ident)
+                    .var_mut(uninit_var.ident)
                     .expect("This variable should exist");
 
                 if var.initialized != uninit_var.result {
@@ -274,7 +302,7 @@ impl Context {
         let else_block = if let Some(e) = i.else_block {
             let block_span = e.block.span();
             let (block, block_returns) =
-                self.with_new_scope(checker, ExecPolicy::Once, |ctx, checker| {
+                self.with_new_scope(checker, ExecPolicy::Once, block_span, |ctx, checker| {
                     let b = ctx.check_types(checker, e.block.csts, is_expr)?;
                     Ok(b)
                 })?;
@@ -320,7 +348,7 @@ impl Context {
         for uninit_var in uninitialized_vars.iter_mut() {
             let var = checker
                 .scopes
-                .var_mut(uninit_var.ident.ident)
+                .var_mut(uninit_var.ident)
                 .expect("This variable should exist");
 
             if var.initialized != uninit_var.result {
@@ -333,7 +361,7 @@ impl Context {
         for uninit_var in uninitialized_vars.iter_mut() {
             let var = checker
                 .scopes
-                .var_mut(uninit_var.ident.ident)
+                .var_mut(uninit_var.ident)
                 .expect("This variable should exist");
             var.initialized = uninit_var.result;
         }
@@ -401,7 +429,7 @@ impl Context {
                         for uninit_var in uninitialized_vars.iter_mut() {
                             let var = checker
                                 .scopes
-                                .var_mut(uninit_var.ident.ident)
+                                .var_mut(uninit_var.ident)
                                 .expect("This variable should exist");
 
                             if first {
@@ -467,7 +495,7 @@ impl Context {
                 for uninit_var in uninitialized_vars.iter_mut() {
                     let var = checker
                         .scopes
-                        .var_mut(uninit_var.ident.ident)
+                        .var_mut(uninit_var.ident)
                         .expect("This variable should exist");
 
                     if first {
@@ -493,7 +521,7 @@ impl Context {
         for uninit_var in uninitialized_vars.iter_mut() {
             let var = checker
                 .scopes
-                .var_mut(uninit_var.ident.ident)
+                .var_mut(uninit_var.ident)
                 .expect("This variable should exist");
             var.initialized = uninit_var.result
         }
@@ -523,16 +551,19 @@ impl Context {
             Vec::new()
         } else {
             let uninitialized_vars = checker.scopes.uninitialized_vars();
-            let (block, _) =
-                self.with_new_scope(checker, ExecPolicy::MultipleTimes, |ctx, checker| {
-                    ctx.check_types(checker, w.block.csts, false)
-                })?;
+            let block_span = w.block.span();
+            let (block, _) = self.with_new_scope(
+                checker,
+                ExecPolicy::MultipleTimes,
+                block_span,
+                |ctx, checker| ctx.check_types(checker, w.block.csts, false),
+            )?;
 
             // Mark variables that have been initialized as possibly initialized.
             for uninit_var in uninitialized_vars.iter() {
                 let var = checker
                     .scopes
-                    .var_mut(uninit_var.ident.ident)
+                    .var_mut(uninit_var.ident)
                     .expect("variable should exist");
                 if uninit_var.prev != var.initialized {
                     var.initialized = Initialized::Maybe;
@@ -574,18 +605,23 @@ impl Context {
         let iter_type = DataType::Int;
 
         let uninitialized_vars = checker.scopes.uninitialized_vars();
-        let (inner, block) =
-            self.with_new_scope(checker, ExecPolicy::MultipleTimes, |ctx, checker| {
-                let inner = ctx.def_var(&mut checker.scopes, f.ident, iter_type, true, false);
+        let block_span = f.block.span();
+        let (inner, block) = self.with_new_scope(
+            checker,
+            ExecPolicy::MultipleTimes,
+            block_span,
+            |ctx, checker| {
+                let inner = ctx.def_var(checker, f.ident, iter_type, true, false);
                 let (block, _) = ctx.check_types(checker, f.block.csts, false)?;
                 Ok((inner, block))
-            })?;
+            },
+        )?;
 
         // Mark variables that have been initialized as possibly initialized.
         for uninit_var in uninitialized_vars.iter() {
             let var = checker
                 .scopes
-                .var_mut(uninit_var.ident.ident)
+                .var_mut(uninit_var.ident)
                 .expect("variable should exist");
             if uninit_var.prev != var.initialized {
                 var.initialized = Initialized::Maybe;
@@ -601,6 +637,14 @@ impl Context {
         checker: &mut Checker,
         f: &cst::FunDef,
     ) -> crate::Result<()> {
+        let name = self.idents.name(f.ident.ident);
+        if !checker.funs.find_host(name).is_empty() {
+            return Err(crate::Error::RedefinedHostFun(
+                name.to_owned(),
+                f.ident.span,
+            ));
+        }
+
         let mut params = Vec::with_capacity(f.params.items.len());
         for p in f.params.items.iter() {
             let typ = self.resolve_data_type(&p.typ)?;
@@ -613,12 +657,34 @@ impl Context {
             .as_ref()
             .map_or(Ok(DataType::Unit), |r| self.resolve_data_type(&r.typ))?;
 
+        if let Some(test) = f.test {
+            let valid = checker.scopes.is_global() && params.is_empty() && f.return_type.is_none();
+            if !valid {
+                let span = Span::across(test, f.params.r_par.span);
+                return Err(crate::Error::InvalidTestFun(name.to_owned(), span));
+            }
+        }
+
         // Define function before checking block to support recursive calls
         let inner = checker.funs.push();
+        checker.debug.def_fun(inner, name.to_owned(), f.ident.span);
         let ret = ReturnType::new(return_type, f.return_type.as_ref().map(|r| r.typ.span()));
         let fun = Fun::new(f.ident, params, ret, inner);
+        // Test functions are called by the test runner
+        if f.test.is_some() {
+            fun.uses.set(1);
+        }
         self.def_fun(&mut checker.scopes, fun)?;
 
+        // Only top-level functions can be called by the host
+        if checker.scopes.is_global() {
+            let name = self.idents.name(f.ident.ident);
+            checker.funs.def_name(name, inner);
+            if f.test.is_some() {
+                checker.funs.def_test(name, inner);
+            }
+        }
+
         Ok(())
     }
 
@@ -626,9 +692,11 @@ impl Context {
         let span = f.span();
         let block_span = f.block.span();
 
-        let fun = match self.resolve_fun(&checker.scopes, &f.ident)? {
+        let fun = match self.resolve_fun(checker, &f.ident)? {
             ResolvedFun::Fun(f) => f,
-            ResolvedFun::Builtin(_) => return Ok(Ast::statement(AstT::Unit, false, span)),
+            ResolvedFun::Builtin(_) | ResolvedFun::Host(_) => {
+                return Ok(Ast::statement(AstT::Unit, false, span))
+            }
         };
 
         // TODO: store variables initialized by this function block and set their initialization
@@ -638,11 +706,12 @@ impl Context {
             checker,
             ExecPolicy::MultipleTimes,
             Rc::clone(&fun),
+            block_span,
             |ctx, checker| {
                 let mut inner_params = Vec::new();
                 for p in fun.params.iter() {
-                    let param = ctx.def_var(&mut checker.scopes, p.ident, p.data_type, true, false);
-                    inner_params.push(param);
+                    let param = ctx.def_var(checker, p.ident, p.data_type, true, false);
+                    inner_params.push(ast::FunParam::new(param, p.data_type));
                 }
 
                 // Check function block
@@ -666,9 +735,16 @@ impl Context {
                 }
 
                 // Initialize function block data
+                let name = ctx.idents.name(fun.ident.ident).to_owned();
                 checker.funs.init(
                     fun.inner,
-                    ast::Fun::new(inner_params, block, checker.scopes.frame_size()),
+                    ast::Fun::new(
+                        name,
+                        inner_params,
+                        fun.return_type.data_type,
+                        block,
+                        checker.scopes.frame_size(),
+                    ),
                 );
 
                 Ok(())
@@ -678,7 +754,7 @@ impl Context {
         for uninit_var in uninitialized_vars.iter() {
             let var = checker
                 .scopes
-                .var_mut(uninit_var.ident.ident)
+                .var_mut(uninit_var.ident)
                 .expect("variable should exist");
             var.initialized = uninit_var.prev;
         }
@@ -689,13 +765,25 @@ impl Context {
     fn check_fun_call(&mut self, checker: &mut Checker, f: cst::FunCall) -> crate::Result<Ast> {
         let span = f.span();
 
-        let fun = match self.resolve_fun(&checker.scopes, &f.ident)? {
+        let name = self.idents.name(f.ident.ident).to_owned();
+        let fun = match self.resolve_fun(checker, &f.ident)? {
             ResolvedFun::Fun(f) => f,
             ResolvedFun::Builtin(b) => {
-                return self.check_builtin_fun_call(checker, b, f.args, span)
+                let symbol = Symbol::new(name, f.ident.span, SymbolKind::BuiltinFun(b));
+                self.symbols.push(symbol);
+                return self.check_builtin_fun_call(checker, b, f.args, span);
+            }
+            ResolvedFun::Host(h) => {
+                let signatures = h
+                    .iter()
+                    .map(|&h| checker.funs.get_host(h).signature.clone());
+                let kind = SymbolKind::HostFun(signatures.collect());
+                self.symbols.push(Symbol::new(name, f.ident.span, kind));
+                return self.check_host_fun_call(checker, &h, f.args, span);
             }
         };
 
+        self.push_fun_symbol(&fun, f.ident.span);
         fun.uses.set(fun.uses.get() + 1);
 
         {
@@ -724,7 +812,7 @@ impl Context {
             }
         }
         let mut args = Vec::with_capacity(f.args.items.len());
-        for (p, a) in fun.params.iter().zip(f.args.items.into_iter()) {
+        for (p, a) in fun.params.iter().zip(f.args.items) {
             let val = self.check_type(checker, a, true)?;
             let expected = p.data_type;
             let found = expect_expr(&val)?;
@@ -794,68 +882,11 @@ impl Context {
         };
 
         let mut fun = None;
-        'signatures: for (c, s) in signatures {
-            let (last, others) = match s.params.split_last() {
-                Some(params) => params,
-                None if args.is_empty() => {
-                    fun = Some((c, s));
-                    break 'signatures;
-                }
-                None => {
-                    continue 'signatures;
-                }
-            };
-
-            if args.len() < others.len() {
-                continue 'signatures;
-            }
-            for (&p, a) in others.iter().zip(args.iter()) {
-                let a_data_type = expect_expr(a)?;
-                if a_data_type.is_not(p) {
-                    continue 'signatures;
-                }
-            }
-
-            let mut args_iter = args[others.len()..].iter();
-            match s.repetition {
-                Repetition::One => {
-                    if let Some(a) = args_iter.next() {
-                        let a_data_type = expect_expr(a)?;
-                        if a_data_type.is_not(*last) {
-                            continue 'signatures;
-                        }
-                    } else {
-                        continue 'signatures;
-                    }
-                }
-                Repetition::ZeroOrMore => {
-                    for a in args_iter {
-                        let a_data_type = expect_expr(a)?;
-                        if a_data_type.is_not(*last) {
-                            continue 'signatures;
-                        }
-                    }
-                }
-                Repetition::OneOrMore => {
-                    if let Some(a) = args_iter.next() {
-                        let a_data_type = expect_expr(a)?;
-                        if a_data_type.is_not(*last) {
-                            continue 'signatures;
-                        }
-                    } else {
-                        continue 'signatures;
-                    }
-                    for a in args_iter {
-                        let a_data_type = expect_expr(a)?;
-                        if a_data_type.is_not(*last) {
-                            continue 'signatures;
-                        }
-                    }
-                }
+        for (c, s) in signatures {
+            if signature_matches(s, &args)? {
+                fun = Some((c, s));
+                break;
             }
-
-            fun = Some((c, s));
-            break 'signatures;
         }
         let (fun, signature) = match fun {
             Some(s) => s,
@@ -872,6 +903,23 @@ impl Context {
             }
         };
 
+        if matches!(fun, BuiltinFunCall::Format | BuiltinFunCall::Printf) {
+            if let Err(e) = check_format_args(&args, span) {
+                self.errors.push(e);
+            }
+        }
+        if matches!(fun, BuiltinFunCall::AssertEq | BuiltinFunCall::AssertNe) {
+            let a = expect_expr(&args[0])?;
+            let b = expect_expr(&args[1])?;
+            if a.is_not(b) && b.is_not(a) {
+                return Err(crate::Error::MismatchedType {
+                    expected: a,
+                    found: b,
+                    spans: vec![args[0].span, args[1].span],
+                });
+            }
+        }
+
         let return_type = signature.return_type;
         Ok(Ast::expr(
             AstT::BuiltinFunCall(*fun, args),
@@ -881,6 +929,54 @@ impl Context {
         ))
     }
 
+    fn check_host_fun_call(
+        &mut self,
+        checker: &mut Checker,
+        overloads: &[HostFunRef],
+        f_args: cst::FunArgs,
+        span: Span,
+    ) -> crate::Result<Ast> {
+        let mut args = Vec::with_capacity(f_args.items.len());
+        for a in f_args.items {
+            args.push(self.check_type(checker, a, true)?);
+        }
+
+        let mut fun = None;
+        for &h in overloads {
+            let host_fun = checker.funs.get_host(h);
+            if signature_matches(&host_fun.signature, &args)? {
+                fun = Some((h, host_fun));
+                break;
+            }
+        }
+        let (fun, host_fun) = match fun {
+            Some(f) => f,
+            None => {
+                let signatures = overloads
+                    .iter()
+                    .map(|&h| checker.funs.get_host(h).signature.clone())
+                    .collect();
+                return Err(crate::Error::NoMatchingHostFunSignature {
+                    name: checker.funs.get_host(overloads[0]).name.clone(),
+                    args: args
+                        .iter()
+                        .map(expect_expr)
+                        .collect::<crate::Result<Vec<DataType>>>()?,
+                    signatures,
+                    span,
+                });
+            }
+        };
+
+        let return_type = host_fun.signature.return_type;
+        Ok(Ast::expr(
+            AstT::HostFunCall(fun, args),
+            return_type,
+            false,
+            span,
+        ))
+    }
+
     fn check_return(&mut self, checker: &mut Checker, r: cst::Return) -> crate::Result<Ast> {
         let fun = match checker.scopes.fun_context() {
             Some(f) => f,
@@ -939,7 +1035,7 @@ impl Context {
                 }
 
                 let val_returns = val.returns;
-                let var_ref = self.def_var(&mut checker.scopes, v.ident, data_type, true, mutable);
+                let var_ref = self.def_var(checker, v.ident, data_type, true, mutable);
                 Ok(Ast::statement(
                     AstT::VarAssign(var_ref, Box::new(val)),
                     val_returns,
@@ -956,7 +1052,7 @@ impl Context {
                 }
 
                 let val_returns = val.returns;
-                let var_ref = self.def_var(&mut checker.scopes, v.ident, data_type, true, mutable);
+                let var_ref = self.def_var(checker, v.ident, data_type, true, mutable);
                 Ok(Ast::statement(
                     AstT::VarAssign(var_ref, Box::new(val)),
                     val_returns,
@@ -965,7 +1061,7 @@ impl Context {
             }
             cst::VarDefInner::Declaration { type_hint } => {
                 let data_type = self.resolve_data_type(&type_hint.1)?;
-                self.def_var(&mut checker.scopes, v.ident, data_type, false, mutable);
+                self.def_var(checker, v.ident, data_type, false, mutable);
 
                 Ok(Ast::statement(AstT::Unit, false, span))
             }
@@ -1305,6 +1401,7 @@ impl Context {
                         DataType::Int => a,
                         DataType::Float => a,
                         DataType::Char => a,
+                        DataType::Duration => a,
                         DataType::Any => a,
                         _ => return cast_err(),
                     },
@@ -1336,6 +1433,12 @@ impl Context {
                         DataType::Any => a,
                         _ => return cast_err(),
                     },
+                    DataType::Duration => match a_data_type {
+                        DataType::Duration => a,
+                        DataType::Int => a,
+                        DataType::Any => a,
+                        _ => return cast_err(),
+                    },
                     DataType::Unit => match a_data_type {
                         DataType::Unit => a,
                         DataType::Any => a,
@@ -1509,13 +1612,84 @@ impl Context {
         vars.iter()
             .filter(|v| v.initialized == Initialized::Yes)
             .map(|v| {
-                let name = self.idents.name(v.ident.ident).to_owned();
+                let name = self.idents.name(v.ident).to_owned();
                 (name, v.inner)
             })
             .collect()
     }
 }
 
+/// Validate the arguments of a `format` call if the format string is a literal.
+fn check_format_args(args: &[Ast], span: Span) -> crate::Result<()> {
+    let AstT::Val(Val::Str(fmt)) = &args[0].typ else {
+        return Ok(());
+    };
+    let format = match Format::parse(fmt) {
+        Ok(f) => f,
+        Err(e) => return Err(crate::Error::InvalidFormatString(e, args[0].span)),
+    };
+
+    let types = args[1..]
+        .iter()
+        .map(|a| Ok((expect_expr(a)?, a.span)))
+        .collect::<crate::Result<Vec<_>>>()?;
+    format.check_args(&types, span)
+}
+
+fn signature_matches(signature: &FunSignature, args: &[Ast]) -> crate::Result<bool> {
+    let (last, others) = match signature.params.split_last() {
+        Some(params) => params,
+        None => return Ok(args.is_empty()),
+    };
+
+    if args.len() < others.len() {
+        return Ok(false);
+    }
+    for (&p, a) in others.iter().zip(args.iter()) {
+        let a_data_type = expect_expr(a)?;
+        if a_data_type.is_not(p) {
+            return Ok(false);
+        }
+    }
+
+    let mut args_iter = args[others.len()..].iter();
+    match signature.repetition {
+        Repetition::One => {
+            let Some(a) = args_iter.next() else {
+                return Ok(false);
+            };
+            if expect_expr(a)?.is_not(*last) {
+                return Ok(false);
+            }
+            if args_iter.next().is_some() {
+                return Ok(false);
+            }
+        }
+        Repetition::ZeroOrMore => {
+            for a in args_iter {
+                if expect_expr(a)?.is_not(*last) {
+                    return Ok(false);
+                }
+            }
+        }
+        Repetition::OneOrMore => {
+            let Some(a) = args_iter.next() else {
+                return Ok(false);
+            };
+            if expect_expr(a)?.is_not(*last) {
+                return Ok(false);
+            }
+            for a in args_iter {
+                if expect_expr(a)?.is_not(*last) {
+                    return Ok(false);
+                }
+            }
+        }
+    }
+
+    Ok(true)
+}
+
 fn expect_expr(ast: &Ast) -> crate::Result<DataType> {
     ast.data_type
         .as_expr()
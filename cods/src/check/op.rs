@@ -39,9 +39,10 @@ macro_rules! op_signature {
 pub const NOT_SIGNATURES: [(Op, OpSignature<1>); 1] = op_signatures! {
     Not(Bool) -> Bool
 };
-pub const NEG_SIGNATURES: [(Op, OpSignature<1>); 2] = op_signatures! {
+pub const NEG_SIGNATURES: [(Op, OpSignature<1>); 3] = op_signatures! {
     NegInt(Int) -> Int
     NegFloat(Float) -> Float
+    NegDuration(Duration) -> Duration
 };
 
 pub const FACTORIAL_SIGNATURES: [(Op, OpSignature<1>); 1] = op_signatures! {
@@ -54,49 +55,60 @@ pub const RANGE_EX_SIGNATURES: [(Op, OpSignature<2>); 1] = op_signatures! {
 pub const RANGE_IN_SIGNATURES: [(Op, OpSignature<2>); 1] = op_signatures! {
     RangeIn(Int, Int) -> Range
 };
-pub const ADD_SIGNATURES: [(Op, OpSignature<2>); 2] = op_signatures! {
+pub const ADD_SIGNATURES: [(Op, OpSignature<2>); 3] = op_signatures! {
     AddInt(Int, Int) -> Int
     AddFloat(Float, Float) -> Float
+    AddDuration(Duration, Duration) -> Duration
 };
-pub const SUB_SIGNATURES: [(Op, OpSignature<2>); 2] = op_signatures! {
+pub const SUB_SIGNATURES: [(Op, OpSignature<2>); 3] = op_signatures! {
     SubInt(Int, Int) -> Int
     SubFloat(Float, Float) -> Float
+    SubDuration(Duration, Duration) -> Duration
 };
-pub const MUL_SIGNATURES: [(Op, OpSignature<2>); 2] = op_signatures! {
+pub const MUL_SIGNATURES: [(Op, OpSignature<2>); 4] = op_signatures! {
     MulInt(Int, Int) -> Int
     MulFloat(Float, Float) -> Float
+    MulDurationInt(Duration, Int) -> Duration
+    MulIntDuration(Int, Duration) -> Duration
 };
-pub const DIV_SIGNATURES: [(Op, OpSignature<2>); 2] = op_signatures! {
+pub const DIV_SIGNATURES: [(Op, OpSignature<2>); 4] = op_signatures! {
     DivInt(Int, Int) -> Int
     DivFloat(Float, Float) -> Float
+    DivDurationInt(Duration, Int) -> Duration
+    DivDuration(Duration, Duration) -> Int
 };
 pub const POW_SIGNATURES: [(Op, OpSignature<2>); 3] = op_signatures! {
     PowInt(Int, Int) -> Int
     PowFloat(Float, Float) -> Float
     PowFloatInt(Float, Int) -> Float
 };
-pub const REM_SIGNATURES: [(Op, OpSignature<2>); 2] = op_signatures! {
+pub const REM_SIGNATURES: [(Op, OpSignature<2>); 3] = op_signatures! {
     RemInt(Int, Int) -> Int
     RemFloat(Float, Float) -> Float
+    RemDuration(Duration, Duration) -> Duration
 };
 pub const REM_EUCLID_SIGNATURES: [(Op, OpSignature<2>); 1] = op_signatures! {
     RemEuclidInt(Int, Int) -> Int
 };
-pub const LT_SIGNATURES: [(Op, OpSignature<2>); 2] = op_signatures! {
+pub const LT_SIGNATURES: [(Op, OpSignature<2>); 3] = op_signatures! {
     LtInt(Int, Int) -> Bool
     LtFloat(Float, Float) -> Bool
+    LtDuration(Duration, Duration) -> Bool
 };
-pub const LE_SIGNATURES: [(Op, OpSignature<2>); 2] = op_signatures! {
+pub const LE_SIGNATURES: [(Op, OpSignature<2>); 3] = op_signatures! {
     LeInt(Int, Int) -> Bool
     LeFloat(Float, Float) -> Bool
+    LeDuration(Duration, Duration) -> Bool
 };
-pub const GT_SIGNATURES: [(Op, OpSignature<2>); 2] = op_signatures! {
+pub const GT_SIGNATURES: [(Op, OpSignature<2>); 3] = op_signatures! {
     GtInt(Int, Int) -> Bool
     GtFloat(Float, Float) -> Bool
+    GtDuration(Duration, Duration) -> Bool
 };
-pub const GE_SIGNATURES: [(Op, OpSignature<2>); 2] = op_signatures! {
+pub const GE_SIGNATURES: [(Op, OpSignature<2>); 3] = op_signatures! {
     GeInt(Int, Int) -> Bool
     GeFloat(Float, Float) -> Bool
+    GeDuration(Duration, Duration) -> Bool
 };
 pub const OR_SIGNATURES: [(Op, OpSignature<2>); 1] = op_signatures! {
     Or(Bool, Bool) -> Bool
@@ -2,13 +2,15 @@ use std::cell::Cell;
 use std::rc::Rc;
 
 use crate::{
-    Ast, BuiltinConst, BuiltinFun, Checker, Context, DataType, FunRef, Ident, IdentSpan, Span,
-    VarRef,
+    Ast, BuiltinConst, BuiltinFun, Checker, Context, DataType, FunRef, HostFunRef, Ident,
+    IdentSpan, Span, Stack, Symbol, SymbolKind, Val, VarInfo, VarRef,
 };
 
 pub enum ResolvedFun {
     Fun(Rc<Fun>),
     Builtin(BuiltinFun),
+    /// All overloads of a host function.
+    Host(Vec<HostFunRef>),
 }
 
 pub enum ResolvedVar<'a> {
@@ -23,13 +25,18 @@ pub enum ResolveError {
 }
 
 impl Context {
-    pub fn resolve_fun(&self, scopes: &Scopes, id: &IdentSpan) -> crate::Result<ResolvedFun> {
+    pub fn resolve_fun(&self, checker: &Checker, id: &IdentSpan) -> crate::Result<ResolvedFun> {
         let name = self.idents.name(id.ident);
         if let Ok(b) = name.parse::<BuiltinFun>() {
             return Ok(ResolvedFun::Builtin(b));
         }
 
-        match scopes.fun(id.ident) {
+        let host = checker.funs.find_host(name);
+        if !host.is_empty() {
+            return Ok(ResolvedFun::Host(host));
+        }
+
+        match checker.scopes.fun(id.ident) {
             Some(f) => Ok(ResolvedFun::Fun(f)),
             None => {
                 let name = self.idents.name(id.ident);
@@ -49,24 +56,58 @@ impl Context {
             }
         }
 
+        self.push_fun_symbol(&fun, id.span);
         scopes.funs.push((id.ident, Rc::new(fun)));
 
         Ok(())
     }
 
+    pub fn push_fun_symbol(&mut self, fun: &Fun, span: Span) {
+        let params = (fun.params.iter())
+            .map(|p| (self.idents.name(p.ident.ident).to_owned(), p.data_type))
+            .collect();
+        let kind = SymbolKind::Fun {
+            params,
+            return_type: fun.return_type.data_type,
+            def: fun.ident.span,
+        };
+        let name = self.idents.name(fun.ident.ident).to_owned();
+        self.symbols.push(Symbol::new(name, span, kind));
+    }
+
+    fn push_var_symbol(&mut self, var: &Var, span: Span) {
+        let kind = SymbolKind::Var {
+            data_type: var.data_type,
+            mutable: var.mutable,
+            def: var.span,
+        };
+        let name = self.idents.name(var.ident).to_owned();
+        self.symbols.push(Symbol::new(name, span, kind));
+    }
+
+    fn push_const_symbol(&mut self, c: BuiltinConst, span: Span) {
+        let name = c.to_string();
+        self.symbols
+            .push(Symbol::new(name, span, SymbolKind::Const(c)));
+    }
+
     /// Resolve the var belonging to the identifier.
     pub fn resolve_var<'a>(
-        &self,
+        &mut self,
         scopes: &'a mut Scopes,
         id: &IdentSpan,
     ) -> crate::Result<ResolvedVar<'a>> {
         let name = self.idents.name(id.ident);
         if let Ok(b) = name.parse::<BuiltinConst>() {
+            self.push_const_symbol(b, id.span);
             return Ok(ResolvedVar::Const(b));
         }
 
         match scopes.var_mut(id.ident) {
-            Ok(v) => Ok(ResolvedVar::Var(v)),
+            Ok(v) => {
+                self.push_var_symbol(v, id.span);
+                Ok(ResolvedVar::Var(v))
+            }
             Err(ResolveError::DynCapture(s)) => Err(crate::Error::NotImplemented(
                 "Capturing variables from a dynamic scope is not yet implemented",
                 vec![s, id.span],
@@ -92,12 +133,13 @@ impl Context {
 
     /// Resolve the var and make sure it is initialized
     pub fn get_var<'a>(
-        &self,
+        &mut self,
         scopes: &'a mut Scopes,
         id: &IdentSpan,
     ) -> crate::Result<ResolvedVar<'a>> {
         let name = self.idents.name(id.ident);
         if let Ok(b) = name.parse::<BuiltinConst>() {
+            self.push_const_symbol(b, id.span);
             return Ok(ResolvedVar::Const(b));
         }
 
@@ -123,23 +165,59 @@ impl Context {
             ));
         }
 
+        self.push_var_symbol(var, id.span);
         var.reads += 1;
 
         Ok(ResolvedVar::Var(var))
     }
 
     pub fn def_var(
-        &self,
-        scopes: &mut Scopes,
+        &mut self,
+        checker: &mut Checker,
         ident: IdentSpan,
         data_type: DataType,
         initialized: bool,
         mutable: bool,
     ) -> VarRef {
-        let inner = scopes.var_ref();
+        let inner = checker.scopes.var_ref();
         let var = Var::new(ident, data_type, initialized, mutable, inner);
+        self.push_var_symbol(&var, ident.span);
+
+        let name = self.idents.name(ident.ident).to_owned();
+        let fun = checker.scopes.fun_context().map(|f| f.inner);
+        let info = VarInfo::new(name, inner, data_type, Some(ident.span));
+        checker.debug.def_var(fun, info);
+
+        checker.scopes.extend_frame(1);
+        checker.scopes.vars.push(var);
+        inner
+    }
+
+    /// Define a global variable provided by the host application and store its value on the
+    /// stack. This has to be done before checking the script, which will see the variable
+    /// like a `let` binding. The returned reference can be used to read the final value back
+    /// from the stack after evaluation.
+    pub fn def_global(
+        &mut self,
+        checker: &mut Checker,
+        stack: &mut Stack,
+        name: &str,
+        val: Val,
+        mutable: bool,
+    ) -> VarRef {
+        let scopes = &mut checker.scopes;
+        let ident = self.idents.push(name);
+        let inner = scopes.var_ref();
+        let data_type = val.data_type();
+        let var = Var::host(ident, data_type, mutable, inner);
         scopes.extend_frame(1);
         scopes.vars.push(var);
+        stack.resize(scopes.frame_size());
+        stack.set(&inner, val.clone());
+        checker.host_globals.push((inner, val));
+
+        let info = VarInfo::new(name.to_owned(), inner, data_type, None);
+        checker.debug.def_var(None, info);
         inner
     }
 
@@ -199,58 +277,69 @@ impl Context {
         checker: &mut Checker,
         exec_policy: ExecPolicy,
         fun: Rc<Fun>,
+        span: Span,
         f: impl FnOnce(&mut Self, &mut Checker) -> T,
     ) -> T {
         checker.scopes.push_frame(fun);
-        let r = self.with_new_scope(checker, exec_policy, f);
+        let r = self.with_new_scope(checker, exec_policy, span, f);
         checker.scopes.pop_frame();
         r
     }
 
+    /// Check `f` inside a new scope, `span` is the span of the scope, which ends the live
+    /// range of the variables defined inside it.
     pub fn with_new_scope<T>(
         &mut self,
         checker: &mut Checker,
         exec_policy: ExecPolicy,
+        span: Span,
         f: impl FnOnce(&mut Self, &mut Checker) -> T,
     ) -> T {
         checker.scopes.push(exec_policy);
         let r = f(self, checker);
         self.check_unused(&checker.scopes);
+
+        let fun = checker.scopes.fun_context().map(|f| f.inner);
+        for v in checker.scopes.current_vars() {
+            checker.debug.end_var(fun, v.inner, span.end);
+        }
         checker.scopes.pop();
         r
     }
 
     pub fn check_unused(&mut self, scopes: &Scopes) {
         for v in scopes.current_vars() {
+            let Some(span) = v.span else {
+                continue;
+            };
+
             if v.reads == 0 {
                 if v.writes <= 1 {
-                    let name = self.idents.name(v.ident.ident);
+                    let name = self.idents.name(v.ident);
                     if name.starts_with('_') {
                         continue;
                     }
 
                     self.warnings
-                        .push(crate::Warning::UnusedVar(name.to_owned(), v.ident.span));
+                        .push(crate::Warning::UnusedVar(name.to_owned(), span));
                 } else {
-                    let name = self.idents.name(v.ident.ident);
+                    let name = self.idents.name(v.ident);
                     if name.starts_with('_') {
                         continue;
                     }
 
                     self.warnings
-                        .push(crate::Warning::UnreadVar(name.to_owned(), v.ident.span));
+                        .push(crate::Warning::UnreadVar(name.to_owned(), span));
                 }
             } else if v.mutable && v.writes == 1 {
-                let name = self.idents.name(v.ident.ident);
+                let name = self.idents.name(v.ident);
                 if name.starts_with('_') {
                     continue;
                 }
 
                 // TODO: use span of `mut` instead of ident
-                self.warnings.push(crate::Warning::RedundantMutVar(
-                    name.to_owned(),
-                    v.ident.span,
-                ));
+                self.warnings
+                    .push(crate::Warning::RedundantMutVar(name.to_owned(), span));
             }
         }
 
@@ -280,7 +369,7 @@ pub enum ExecPolicy {
 }
 
 pub struct UninitializedVar {
-    pub ident: IdentSpan,
+    pub ident: Ident,
     pub prev: Initialized,
     pub result: Initialized,
 }
@@ -415,7 +504,7 @@ impl Scopes {
     pub fn var_index(&self, id: Ident) -> Result<usize, ResolveError> {
         let current_frame = self.scopes[self.current_frame().scope_index].var;
         for (i, v) in self.vars[current_frame..].iter().enumerate().rev() {
-            if v.ident.ident == id {
+            if v.ident == id {
                 return Ok(current_frame + i);
             }
         }
@@ -423,13 +512,15 @@ impl Scopes {
             let second_frame = self.scopes[f.scope_index].var;
             // frames in between
             for v in self.vars[second_frame..current_frame].iter().rev() {
-                if v.ident.ident == id {
-                    return Err(ResolveError::DynCapture(v.ident.span));
+                if v.ident == id {
+                    // host variables are only defined in the global frame
+                    let span = v.span.expect("variable of a function frame has a span");
+                    return Err(ResolveError::DynCapture(span));
                 }
             }
             // global frame
             for (i, v) in self.vars[..second_frame].iter().enumerate().rev() {
-                if v.ident.ident == id {
+                if v.ident == id {
                     return Ok(i);
                 }
             }
@@ -448,6 +539,16 @@ impl Scopes {
             .expect("Expected stack frames to be non empty")
     }
 
+    /// The variables defined in the global scope.
+    pub fn global_vars(&self) -> &[Var] {
+        let end = self.scopes.get(1).map_or(self.vars.len(), |s| s.var);
+        &self.vars[..end]
+    }
+
+    pub fn is_global(&self) -> bool {
+        self.scopes.len() == 1
+    }
+
     pub fn frame_size(&self) -> usize {
         self.current_frame().size
     }
@@ -541,7 +642,10 @@ impl ReturnType {
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Var {
-    pub ident: IdentSpan,
+    pub ident: Ident,
+    /// The span of the identifier where the variable is defined, `None` if it was defined by
+    /// the host application.
+    pub span: Option<Span>,
     pub data_type: DataType,
     pub initialized: Initialized,
     pub mutable: bool,
@@ -577,7 +681,8 @@ impl Var {
         let writes = initialized as u32;
         let initialized = Initialized::from_bool(initialized);
         Self {
-            ident,
+            ident: ident.ident,
+            span: Some(ident.span),
             data_type,
             initialized,
             mutable,
@@ -586,4 +691,18 @@ impl Var {
             inner,
         }
     }
+
+    /// A variable defined by the host application, it is always initialized.
+    pub fn host(ident: Ident, data_type: DataType, mutable: bool, inner: VarRef) -> Self {
+        Self {
+            ident,
+            span: None,
+            data_type,
+            initialized: Initialized::Yes,
+            mutable,
+            reads: 0,
+            writes: 1,
+            inner,
+        }
+    }
 }
@@ -0,0 +1,43 @@
+use crate::{BuiltinConst, BuiltinFun, DataType, FunSignature, Span};
+
+/// A definition of, or reference to, a named item encountered while checking. These are
+/// collected in [`Context::symbols`](crate::Context::symbols) for editor tooling.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Symbol {
+    pub name: String,
+    pub span: Span,
+    pub kind: SymbolKind,
+}
+
+impl Symbol {
+    pub const fn new(name: String, span: Span, kind: SymbolKind) -> Self {
+        Self { name, span, kind }
+    }
+
+    /// The span of the definition, if it is defined inside the checked input.
+    pub fn def_span(&self) -> Option<Span> {
+        match &self.kind {
+            SymbolKind::Var { def, .. } => *def,
+            SymbolKind::Fun { def, .. } => Some(*def),
+            SymbolKind::Const(_) | SymbolKind::BuiltinFun(_) | SymbolKind::HostFun(_) => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SymbolKind {
+    Var {
+        data_type: DataType,
+        mutable: bool,
+        /// `None` for globals defined by the host application.
+        def: Option<Span>,
+    },
+    Const(BuiltinConst),
+    Fun {
+        params: Vec<(String, DataType)>,
+        return_type: DataType,
+        def: Span,
+    },
+    BuiltinFun(BuiltinFun),
+    HostFun(Vec<FunSignature>),
+}
@@ -112,7 +112,7 @@ fn cannot_redefine_builtin_function() {
     let error = ctx.parse_and_eval(input).unwrap_err();
     assert_eq!(
         error,
-        crate::Error::RedefinedBuiltinFun("sqrt".into(), Span::cols(0, 3, 7)),
+        crate::Error::RedefinedBuiltinFun("sqrt".into(), Some(Span::cols(0, 3, 7))),
     );
 }
 
@@ -675,6 +675,54 @@ fn unused_fun() {
     );
 }
 
+#[test]
+fn test_fun() {
+    let input = "
+        let test = 3
+        test fn a() { assert(test == 3) }
+        fn test() -> int { test }
+    ";
+    let mut ctx = Context::default();
+    let program = ctx.compile(input).unwrap();
+    assert_eq!(
+        ctx.warnings,
+        vec![crate::Warning::UnusedFun(
+            "test".into(),
+            Span::cols(3, 11, 15)
+        )],
+    );
+    let tests = program.funs.tests();
+    assert_eq!(tests.len(), 1);
+    assert_eq!(tests[0].0, "a");
+    assert_eq!(program.funs.get(tests[0].1).name, "a");
+}
+
+#[test]
+fn invalid_test_fun() {
+    let mut ctx = Context::default();
+    let error = ctx.parse_and_eval("test fn a(i: int) { }").unwrap_err();
+    assert_eq!(
+        error,
+        crate::Error::InvalidTestFun("a".into(), Span::cols(0, 0, 17)),
+    );
+
+    let mut ctx = Context::default();
+    let error = ctx.parse_and_eval("test fn a() -> int { 1 }").unwrap_err();
+    assert_eq!(
+        error,
+        crate::Error::InvalidTestFun("a".into(), Span::cols(0, 0, 11)),
+    );
+
+    let mut ctx = Context::default();
+    let error = ctx
+        .parse_and_eval("fn a() { test fn b() { } }")
+        .unwrap_err();
+    assert_eq!(
+        error,
+        crate::Error::InvalidTestFun("b".into(), Span::cols(0, 9, 20)),
+    );
+}
+
 #[test]
 fn code_after_return_is_unreachable() {
     let input = r#"
@@ -1094,3 +1142,86 @@ fn type_check_is_always_true() {
         )],
     );
 }
+
+#[test]
+fn format_literal_is_checked() {
+    let mut ctx = Context::default();
+    let error = ctx.parse_and_eval(r#"format("{:x}", "str")"#).unwrap_err();
+    assert_eq!(
+        error,
+        crate::Error::FormatArgType {
+            placeholder: "{:x}".into(),
+            data_type: DataType::Str,
+            span: Span::cols(0, 15, 20),
+        }
+    );
+
+    let mut ctx = Context::default();
+    let error = ctx.parse_and_eval(r#"printf("{}{}", 1)"#).unwrap_err();
+    assert_eq!(
+        error,
+        crate::Error::FormatArgCount {
+            expected: 2,
+            found: 1,
+            span: Span::cols(0, 0, 17),
+        }
+    );
+
+    let mut ctx = Context::default();
+    let error = ctx.parse_and_eval(r#"format("{:?}", 1)"#).unwrap_err();
+    assert_eq!(
+        error,
+        crate::Error::InvalidFormatString(
+            crate::FormatError::InvalidSpec("?".into()),
+            Span::cols(0, 7, 13),
+        )
+    );
+
+    let mut ctx = Context::default();
+    let error = ctx
+        .parse_and_eval(r#"format("{:99999999999999999999}", 1)"#)
+        .unwrap_err();
+    assert_eq!(
+        error,
+        crate::Error::InvalidFormatString(
+            crate::FormatError::InvalidSpec("99999999999999999999".into()),
+            Span::cols(0, 7, 32),
+        )
+    );
+}
+
+#[test]
+fn symbols() {
+    let input = "\
+fn add(a: int, b: int) -> int { a + b }
+let mut x = add(1, 2)
+x = x * PI as int
+println(x)";
+    let mut ctx = Context::default();
+    ctx.parse_and_eval(input).unwrap();
+
+    let fun = crate::SymbolKind::Fun {
+        params: vec![("a".into(), DataType::Int), ("b".into(), DataType::Int)],
+        return_type: DataType::Int,
+        def: Span::cols(0, 3, 6),
+    };
+    let x = crate::SymbolKind::Var {
+        data_type: DataType::Int,
+        mutable: true,
+        def: Some(Span::cols(1, 8, 9)),
+    };
+    let find = |span| ctx.symbols.iter().find(|s| s.span == span).map(|s| &s.kind);
+    assert_eq!(find(Span::cols(0, 3, 6)), Some(&fun));
+    assert_eq!(find(Span::cols(1, 12, 15)), Some(&fun));
+    assert_eq!(find(Span::cols(1, 8, 9)), Some(&x));
+    assert_eq!(find(Span::cols(2, 0, 1)), Some(&x));
+    assert_eq!(find(Span::cols(2, 4, 5)), Some(&x));
+    assert_eq!(
+        find(Span::cols(2, 8, 10)),
+        Some(&crate::SymbolKind::Const(BuiltinConst::Pi))
+    );
+    assert_eq!(
+        find(Span::cols(3, 0, 7)),
+        Some(&crate::SymbolKind::BuiltinFun(crate::BuiltinFun::Println))
+    );
+}
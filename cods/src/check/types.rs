@@ -11,6 +11,7 @@ impl Val {
             Self::Char(_) => DataType::Char,
             Self::Str(_) => DataType::Str,
             Self::Range(_) => DataType::Range,
+            Self::Duration(_) => DataType::Duration,
             Self::Unit => DataType::Unit,
         }
     }
@@ -25,6 +26,7 @@ pub enum DataType {
     Char,
     Str,
     Range,
+    Duration,
     #[cods(rename = "()")]
     Unit,
     Any,
@@ -2,8 +2,8 @@ use std::error;
 use std::fmt::{self, Debug, Display};
 
 use crate::{
-    BuiltinConst, DataType, FunSignature, InfixT, Initialized, Item, Kw, KwT, Op, OpSignature, OpT,
-    Par, PctT, PostfixT, PrefixT, Span, ValSpan,
+    Access, BuiltinConst, DataType, FormatError, FunSignature, InfixT, Initialized, Item, Kw, KwT,
+    Op, OpSignature, OpT, Par, PctT, PostfixT, PrefixT, Span, Val, ValSpan,
 };
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -32,6 +32,10 @@ pub enum Error {
     IntOverflow(Span),
     InvalidFloatLiteral(Span),
     TrailingFloatLitChars(Span),
+    InvalidDurationUnit(String, Span),
+    MissingDurationUnit(Span),
+    DurationOverflow(Span),
+    DurationTooPrecise(Span),
     InvalidEscapeChar(char, Span),
     MissingEscapeChar(Span),
     InvalidUnicodeEscapeChar(char, Span),
@@ -93,13 +97,32 @@ pub enum Error {
     RedefinedBuiltinConst(String, Span),
     UndefinedFun(String, Span),
     RedefinedFun(String, Span, Span),
-    RedefinedBuiltinFun(String, Span),
+    RedefinedBuiltinFun(String, Option<Span>),
+    RedefinedHostFun(String, Span),
+    InvalidTestFun(String, Span),
+    InvalidFormatString(FormatError, Span),
+    FormatArgCount {
+        expected: usize,
+        found: usize,
+        span: Span,
+    },
+    FormatArgType {
+        placeholder: String,
+        data_type: DataType,
+        span: Span,
+    },
     NoMatchingBuiltinFunSignature {
         name: String,
         args: Vec<DataType>,
         signatures: Vec<FunSignature>,
         span: Span,
     },
+    NoMatchingHostFunSignature {
+        name: String,
+        args: Vec<DataType>,
+        signatures: Vec<FunSignature>,
+        span: Span,
+    },
     NoMatchingInfixSignature {
         infix: InfixT,
         a: DataType,
@@ -140,7 +163,9 @@ pub enum Error {
     AddOverflow(Span, Span),
     SubOverflow(Span, Span),
     MulOverflow(Span, Span),
+    DivOverflow(Span, Span),
     DivideByZero(Span, Span),
+    RemOverflow(Span, Span),
     RemainderByZero(Span, Span),
     PowOverflow(Span, Span),
     NegativeIntPow(Span, Span),
@@ -150,9 +175,39 @@ pub enum Error {
     NegativeNcr(ValSpan),
     InvalidNcr(ValSpan, ValSpan),
     InvalidClampBounds(ValSpan, ValSpan),
-    AssertFailed(Span),
-    AssertEqFailed(ValSpan, ValSpan),
+    AssertFailed(Option<String>, Span),
+    AssertEqFailed(ValSpan, ValSpan, Option<String>),
+    AssertNeFailed(ValSpan, ValSpan, Option<String>),
+    AssertApproxFailed {
+        a: ValSpan,
+        b: ValSpan,
+        eps: f64,
+        msg: Option<String>,
+    },
+    InvalidTolerance(ValSpan),
+    Panic(String, Span),
     NegativeSleepDuration(ValSpan),
+    HostFunFailed(String, Span),
+    Io(String, Span),
+    InvalidInput(String, DataType, Span),
+    PermissionDenied(String, Access, Span),
+    EnvVarNotPresent(String, Span),
+    EnvVarNotUnicode(String, Span),
+    ArgOutOfBounds(ValSpan, usize),
+    InvalidExitCode(ValSpan),
+    EmptyRandomRange(ValSpan),
+    UnknownFun(String),
+    CallArgCount {
+        name: String,
+        expected: usize,
+        found: usize,
+    },
+    CallArgType {
+        name: String,
+        index: usize,
+        expected: DataType,
+        found: DataType,
+    },
 }
 
 impl error::Error for Error {}
@@ -186,6 +241,17 @@ impl UserFacing for Error {
             Self::IntOverflow(_) => write!(f, "Integer literal overflow"),
             Self::InvalidFloatLiteral(_) => write!(f, "Invalid float literal"),
             Self::TrailingFloatLitChars(_) => write!(f, "Trailing characters in float literal"),
+            Self::InvalidDurationUnit(u, _) => {
+                write!(
+                    f,
+                    "Invalid duration unit `{u}`, expected one of `h`, `m`, `s`, `ms`, `us`, `ns`"
+                )
+            }
+            Self::MissingDurationUnit(_) => write!(f, "Missing duration unit"),
+            Self::DurationOverflow(_) => write!(f, "Duration literal overflow"),
+            Self::DurationTooPrecise(_) => {
+                write!(f, "Duration literal is more precise than a nanosecond")
+            }
             Self::InvalidEscapeChar(c, _) => {
                 write!(f, "Invalid escape character: `{}`", c.escape_default())
             }
@@ -278,9 +344,11 @@ impl UserFacing for Error {
             Self::NotIterable(t, _) => write!(f, "Value of type `{t}` is not iterable"),
             Self::UndefinedVar(name, _) => write!(f, "Undefined variable `{name}`"),
             Self::UninitializedVar(name, initialized, _) => {
-                let possibly = (*initialized == Initialized::Maybe)
-                    .then_some("possibly ")
-                    .unwrap_or_default();
+                let possibly = if *initialized == Initialized::Maybe {
+                    "possibly "
+                } else {
+                    ""
+                };
                 write!(f, "Variable `{name}` is {possibly}not initialized")
             }
             Self::RedefinedBuiltinConst(name, _) => {
@@ -289,45 +357,59 @@ impl UserFacing for Error {
             Self::UndefinedFun(name, _) => write!(f, "Undefined function `{name}`"),
             Self::RedefinedFun(name, _, _) => write!(f, "Redefined function `{name}`"),
             Self::RedefinedBuiltinFun(name, _) => write!(f, "Redefined builtin function `{name}`"),
+            Self::RedefinedHostFun(name, _) => write!(f, "Redefined host function `{name}`"),
+            Self::InvalidTestFun(name, _) => write!(
+                f,
+                "Test function `{name}` must be defined at the top-level, without parameters or a return type"
+            ),
+            Self::InvalidFormatString(e, _) => write!(f, "Invalid format string: {e}"),
+            Self::FormatArgCount {
+                expected, found, ..
+            } => {
+                let arg_s = if *expected == 1 { "" } else { "s" };
+                write!(
+                    f,
+                    "Format string expects {expected} argument{arg_s}, but found {found}"
+                )
+            }
+            Self::FormatArgType {
+                placeholder,
+                data_type,
+                ..
+            } => {
+                write!(
+                    f,
+                    "Placeholder `{placeholder}` cannot format a value of type `{data_type}`"
+                )
+            }
             Self::NoMatchingBuiltinFunSignature {
                 name,
                 args,
                 signatures,
                 ..
-            } => {
-                writeln!(
-                    f,
-                    "No matching signature for builtin function `{name}`:{line_suffix}"
-                )?;
-                for s in signatures.iter() {
-                    write!(f, "{line_prefix}    {name}(")?;
-                    if let Some((last, others)) = s.params.split_last() {
-                        for p in others {
-                            write!(f, "{p}, ")?;
-                        }
-                        write!(f, "{last}")?;
-                    }
-                    write!(f, ")")?;
-
-                    if s.return_type != DataType::Unit {
-                        write!(f, " -> {}", s.return_type)?;
-                    }
-                    writeln!(f, "{line_suffix}")?;
-                }
-                writeln!(f, "{line_prefix}{line_suffix}")?;
-
-                writeln!(f, "{line_prefix}Called with args of type:{line_suffix}")?;
-                write!(f, "{line_prefix}    {name}(")?;
-                if let Some((last, others)) = args.split_last() {
-                    for a in others {
-                        write!(f, "{a}, ")?;
-                    }
-                    write!(f, "{last}")?;
-                }
-                writeln!(f, "){line_suffix}")?;
-
-                Ok(())
-            }
+            } => write_fun_signature_mismatch(
+                f,
+                "builtin",
+                name,
+                args,
+                signatures,
+                line_prefix,
+                line_suffix,
+            ),
+            Self::NoMatchingHostFunSignature {
+                name,
+                args,
+                signatures,
+                ..
+            } => write_fun_signature_mismatch(
+                f,
+                "host",
+                name,
+                args,
+                signatures,
+                line_prefix,
+                line_suffix,
+            ),
             Self::NoMatchingInfixSignature {
                 infix,
                 a,
@@ -434,9 +516,11 @@ impl UserFacing for Error {
                 write!(f, "Cannot assign to something that is not a variable")
             }
             Self::ImmutableAssign(name, initialized, _, _) => {
-                let possibly_initialized = (*initialized == Initialized::Maybe)
-                    .then_some("possibly initialized ")
-                    .unwrap_or_default();
+                let possibly_initialized = if *initialized == Initialized::Maybe {
+                    "possibly initialized "
+                } else {
+                    ""
+                };
                 write!(
                     f,
                     "Cannot assign twice to {possibly_initialized}immutable variable `{name}`"
@@ -461,7 +545,9 @@ impl UserFacing for Error {
             Self::AddOverflow(_, _) => write!(f, "Addition would overflow"),
             Self::SubOverflow(_, _) => write!(f, "Subtraction would overflow"),
             Self::MulOverflow(_, _) => write!(f, "Multiplication would overflow"),
+            Self::DivOverflow(_, _) => write!(f, "Division would overflow"),
             Self::DivideByZero(_, _) => write!(f, "Attempted to divide by 0"),
+            Self::RemOverflow(_, _) => write!(f, "Remainder would overflow"),
             Self::RemainderByZero(_, _) => {
                 write!(
                     f,
@@ -501,21 +587,86 @@ impl UserFacing for Error {
                     "Invalid clamp bounds min: `{min}` is greater than max: `{max}`"
                 )
             }
-            Self::AssertFailed(_) => {
-                write!(f, "Assertion failed")
+            Self::AssertFailed(msg, _) => {
+                write!(f, "Assertion failed{}", fmt_assert_msg(msg))
+            }
+            Self::AssertEqFailed(a, b, msg) => {
+                write!(f, "Assertion failed, values are not equal")?;
+                write!(f, "{}", fmt_assert_msg(msg))?;
+                write_side_by_side(f, &a.val, &b.val, line_prefix, line_suffix)
+            }
+            Self::AssertNeFailed(a, b, msg) => {
+                write!(f, "Assertion failed, values are equal")?;
+                write!(f, "{}", fmt_assert_msg(msg))?;
+                write_side_by_side(f, &a.val, &b.val, line_prefix, line_suffix)
+            }
+            Self::AssertApproxFailed { a, b, eps, msg } => {
+                write!(f, "Assertion failed, values differ by more than `{eps}`")?;
+                write!(f, "{}", fmt_assert_msg(msg))?;
+                write_side_by_side(f, &a.val, &b.val, line_prefix, line_suffix)
+            }
+            Self::InvalidTolerance(eps) => {
+                write!(f, "Tolerance `{eps}` is not a non-negative number")
             }
-            Self::AssertEqFailed(a, b) => {
+            Self::Panic(msg, _) => write!(f, "Panicked: {msg}"),
+            Self::NegativeSleepDuration(d) => {
+                write!(f, "Attempted to sleep for a negative duration `{d}`")
+            }
+            Self::HostFunFailed(msg, _) => write!(f, "{msg}"),
+            Self::Io(msg, _) => write!(f, "IO error: {msg}"),
+            Self::InvalidInput(input, t, _) => {
+                write!(f, "Input `{input}` could not be parsed as `{t}`")
+            }
+            Self::PermissionDenied(path, access, _) => {
                 write!(
                     f,
-                    "Assertion failed, values are not equal{ls}\n\
-                    {lp} left: `{a}`{ls}\n\
-                    {lp}right: `{b}`",
-                    lp = line_prefix,
-                    ls = line_suffix,
+                    "Permission denied, {access} access to `{path}` isn't allowed"
                 )
             }
-            Self::NegativeSleepDuration(d) => {
-                write!(f, "Attempted to sleep for a negative duration `{d}`ns")
+            Self::EnvVarNotPresent(name, _) => {
+                write!(f, "Environment variable `{name}` isn't set")
+            }
+            Self::EnvVarNotUnicode(name, _) => {
+                write!(f, "Environment variable `{name}` isn't valid unicode")
+            }
+            Self::ArgOutOfBounds(index, len) => {
+                let arg_s = if *len == 1 { "" } else { "s" };
+                let were_was = if *len == 1 { "was" } else { "were" };
+                write!(
+                    f,
+                    "Argument index `{index}` is out of bounds, {len} argument{arg_s} {were_was} passed"
+                )
+            }
+            Self::InvalidExitCode(c) => {
+                write!(f, "Exit code `{c}` is outside of the range `0..=255`")
+            }
+            Self::EmptyRandomRange(r) => {
+                write!(f, "Cannot pick a random value from the empty range `{r}`")
+            }
+            Self::UnknownFun(name) => write!(f, "Unknown function `{name}`"),
+            Self::CallArgCount {
+                name,
+                expected,
+                found,
+            } => {
+                let arg_s = if *expected == 1 { "" } else { "s" };
+                let were_was = if *found == 1 { "was" } else { "were" };
+                write!(
+                    f,
+                    "Function `{name}` takes {expected} argument{arg_s}, but {found} {were_was} supplied"
+                )
+            }
+            Self::CallArgType {
+                name,
+                index,
+                expected,
+                found,
+            } => {
+                let nr = index + 1;
+                write!(
+                    f,
+                    "Argument {nr} of function `{name}` expected a value of type `{expected}`, found `{found}`"
+                )
             }
         }?;
         f.write_str(line_suffix)
@@ -535,6 +686,10 @@ impl UserFacing for Error {
             Self::IntOverflow(s) => vec![*s],
             Self::InvalidFloatLiteral(s) => vec![*s],
             Self::TrailingFloatLitChars(s) => vec![*s],
+            Self::InvalidDurationUnit(_, s) => vec![*s],
+            Self::MissingDurationUnit(s) => vec![*s],
+            Self::DurationOverflow(s) => vec![*s],
+            Self::DurationTooPrecise(s) => vec![*s],
             Self::InvalidEscapeChar(_, s) => vec![*s],
             Self::MissingEscapeChar(s) => vec![*s],
             Self::MissingUnicodeEscapeChar { span, .. } => vec![*span],
@@ -579,8 +734,14 @@ impl UserFacing for Error {
             Self::RedefinedBuiltinConst(_, s) => vec![*s],
             Self::UndefinedFun(_, s) => vec![*s],
             Self::RedefinedFun(_, a, b) => vec![*a, *b],
-            Self::RedefinedBuiltinFun(_, s) => vec![*s],
+            Self::RedefinedBuiltinFun(_, s) => s.iter().copied().collect(),
+            Self::RedefinedHostFun(_, s) => vec![*s],
+            Self::InvalidTestFun(_, s) => vec![*s],
+            Self::InvalidFormatString(_, s) => vec![*s],
+            Self::FormatArgCount { span, .. } => vec![*span],
+            Self::FormatArgType { span, .. } => vec![*span],
             Self::NoMatchingBuiltinFunSignature { span, .. } => vec![*span],
+            Self::NoMatchingHostFunSignature { span, .. } => vec![*span],
             Self::NoMatchingInfixSignature { span, .. } => vec![*span],
             Self::NoMatchingInfixAssignSignature { span, .. } => vec![*span],
             Self::NoMatchingPrefixSignature { span, .. } => vec![*span],
@@ -599,7 +760,9 @@ impl UserFacing for Error {
             Self::AddOverflow(a, b) => vec![*a, *b],
             Self::SubOverflow(a, b) => vec![*a, *b],
             Self::MulOverflow(a, b) => vec![*a, *b],
+            Self::DivOverflow(a, b) => vec![*a, *b],
             Self::DivideByZero(a, b) => vec![*a, *b],
+            Self::RemOverflow(a, b) => vec![*a, *b],
             Self::RemainderByZero(a, b) => vec![*a, *b],
             Self::PowOverflow(a, b) => vec![*a, *b],
             Self::NegativeIntPow(a, b) => vec![*a, *b],
@@ -609,9 +772,25 @@ impl UserFacing for Error {
             Self::NegativeNcr(a) => vec![a.span],
             Self::InvalidNcr(a, b) => vec![a.span, b.span],
             Self::InvalidClampBounds(min, max) => vec![min.span, max.span],
-            Self::AssertFailed(s) => vec![*s],
-            Self::AssertEqFailed(a, b) => vec![a.span, b.span],
+            Self::AssertFailed(_, s) => vec![*s],
+            Self::AssertEqFailed(a, b, _) => vec![a.span, b.span],
+            Self::AssertNeFailed(a, b, _) => vec![a.span, b.span],
+            Self::AssertApproxFailed { a, b, .. } => vec![a.span, b.span],
+            Self::InvalidTolerance(eps) => vec![eps.span],
+            Self::Panic(_, s) => vec![*s],
             Self::NegativeSleepDuration(a) => vec![a.span],
+            Self::HostFunFailed(_, s) => vec![*s],
+            Self::Io(_, s) => vec![*s],
+            Self::InvalidInput(_, _, s) => vec![*s],
+            Self::PermissionDenied(_, _, s) => vec![*s],
+            Self::EnvVarNotPresent(_, s) => vec![*s],
+            Self::EnvVarNotUnicode(_, s) => vec![*s],
+            Self::ArgOutOfBounds(i, _) => vec![i.span],
+            Self::InvalidExitCode(c) => vec![c.span],
+            Self::EmptyRandomRange(r) => vec![r.span],
+            Self::UnknownFun(_) => vec![],
+            Self::CallArgCount { .. } => vec![],
+            Self::CallArgType { .. } => vec![],
         }
     }
 }
@@ -672,3 +851,80 @@ impl UserFacing for Warning {
         }
     }
 }
+
+fn write_fun_signature_mismatch(
+    f: &mut impl fmt::Write,
+    kind: &str,
+    name: &str,
+    args: &[DataType],
+    signatures: &[FunSignature],
+    line_prefix: &str,
+    line_suffix: &str,
+) -> fmt::Result {
+    writeln!(
+        f,
+        "No matching signature for {kind} function `{name}`:{line_suffix}"
+    )?;
+    for s in signatures.iter() {
+        write!(f, "{line_prefix}    {name}(")?;
+        if let Some((last, others)) = s.params.split_last() {
+            for p in others {
+                write!(f, "{p}, ")?;
+            }
+            write!(f, "{last}")?;
+        }
+        write!(f, ")")?;
+
+        if s.return_type != DataType::Unit {
+            write!(f, " -> {}", s.return_type)?;
+        }
+        writeln!(f, "{line_suffix}")?;
+    }
+    writeln!(f, "{line_prefix}{line_suffix}")?;
+
+    writeln!(f, "{line_prefix}Called with args of type:{line_suffix}")?;
+    write!(f, "{line_prefix}    {name}(")?;
+    if let Some((last, others)) = args.split_last() {
+        for a in others {
+            write!(f, "{a}, ")?;
+        }
+        write!(f, "{last}")?;
+    }
+    writeln!(f, "){line_suffix}")?;
+
+    Ok(())
+}
+
+fn fmt_assert_msg(msg: &Option<String>) -> String {
+    match msg {
+        Some(m) => format!(": {m}"),
+        None => String::new(),
+    }
+}
+
+/// Write the values in two columns, lines that differ are separated by `≠` instead of `│`.
+fn write_side_by_side(
+    f: &mut impl fmt::Write,
+    a: &Val,
+    b: &Val,
+    line_prefix: &str,
+    line_suffix: &str,
+) -> fmt::Result {
+    let (a, b) = (a.to_string(), b.to_string());
+    let a_lines = a.split('\n').collect::<Vec<_>>();
+    let b_lines = b.split('\n').collect::<Vec<_>>();
+    let width = (a_lines.iter())
+        .map(|l| l.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max("left".len());
+
+    write!(f, "{line_suffix}\n{line_prefix}{:width$} │ right", "left")?;
+    for i in 0..a_lines.len().max(b_lines.len()) {
+        let (l, r) = (a_lines.get(i), b_lines.get(i));
+        let sep = if l == r { '│' } else { '≠' };
+        let (l, r) = (l.unwrap_or(&""), r.unwrap_or(&""));
+        write!(f, "{line_suffix}\n{line_prefix}{l:width$} {sep} {r}")?;
+    }
+    Ok(())
+}
@@ -0,0 +1,275 @@
+//! Conversions between rust types and [`Val`].
+//!
+//! There are no tuple or struct values in the language yet, so tuples and user defined types
+//! can't be converted and there is no derive macro. These can be added once script tuples and
+//! structs exist.
+
+use std::error;
+use std::fmt::{self, Display};
+use std::time::Duration;
+
+use crate::{Checker, DataType, FunSignature, HostFunRef, Range, Repetition, Val};
+
+/// Conversion of a [`Val`] into a rust type.
+pub trait FromVal: Sized {
+    /// The type of values this can be converted from.
+    const DATA_TYPE: DataType;
+
+    fn from_val(val: Val) -> Result<Self, FromValError>;
+}
+
+/// Conversion of a rust type into a [`Val`].
+pub trait IntoVal {
+    /// The type of values this is converted into.
+    const DATA_TYPE: DataType;
+
+    fn into_val(self) -> Val;
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum FromValError {
+    MismatchedType { expected: DataType, found: DataType },
+    IntOutOfRange { val: i128, typ: &'static str },
+    NegativeDuration(Val),
+}
+
+impl error::Error for FromValError {}
+
+impl Display for FromValError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MismatchedType { expected, found } => {
+                write!(f, "Expected value of type `{expected}`, found `{found}`")
+            }
+            Self::IntOutOfRange { val, typ } => {
+                write!(f, "Value `{val}` is out of range for `{typ}`")
+            }
+            Self::NegativeDuration(val) => write!(f, "Duration `{val}` is negative"),
+        }
+    }
+}
+
+impl FromVal for Val {
+    const DATA_TYPE: DataType = DataType::Any;
+
+    fn from_val(val: Val) -> Result<Self, FromValError> {
+        Ok(val)
+    }
+}
+
+impl IntoVal for Val {
+    const DATA_TYPE: DataType = DataType::Any;
+
+    fn into_val(self) -> Val {
+        self
+    }
+}
+
+macro_rules! impl_val_conversion {
+    ($typ:ty, $data_type:ident, $variant:ident) => {
+        impl FromVal for $typ {
+            const DATA_TYPE: DataType = DataType::$data_type;
+
+            fn from_val(val: Val) -> Result<Self, FromValError> {
+                match val {
+                    Val::$variant(v) => Ok(v),
+                    v => Err(FromValError::MismatchedType {
+                        expected: <Self as FromVal>::DATA_TYPE,
+                        found: v.data_type(),
+                    }),
+                }
+            }
+        }
+
+        impl IntoVal for $typ {
+            const DATA_TYPE: DataType = DataType::$data_type;
+
+            fn into_val(self) -> Val {
+                Val::$variant(self)
+            }
+        }
+    };
+}
+
+impl_val_conversion!(i128, Int, Int);
+impl_val_conversion!(f64, Float, Float);
+impl_val_conversion!(bool, Bool, Bool);
+impl_val_conversion!(char, Char, Char);
+impl_val_conversion!(String, Str, Str);
+impl_val_conversion!(Range, Range, Range);
+
+macro_rules! impl_int_conversion {
+    ($($typ:ty),*) => {
+        $(
+            impl FromVal for $typ {
+                const DATA_TYPE: DataType = DataType::Int;
+
+                fn from_val(val: Val) -> Result<Self, FromValError> {
+                    let i = i128::from_val(val)?;
+                    <$typ>::try_from(i).map_err(|_| FromValError::IntOutOfRange {
+                        val: i,
+                        typ: stringify!($typ),
+                    })
+                }
+            }
+
+            impl IntoVal for $typ {
+                const DATA_TYPE: DataType = DataType::Int;
+
+                fn into_val(self) -> Val {
+                    Val::Int(self as i128)
+                }
+            }
+        )*
+    };
+}
+
+impl_int_conversion!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+
+impl FromVal for f32 {
+    const DATA_TYPE: DataType = DataType::Float;
+
+    fn from_val(val: Val) -> Result<Self, FromValError> {
+        f64::from_val(val).map(|f| f as f32)
+    }
+}
+
+impl IntoVal for f32 {
+    const DATA_TYPE: DataType = DataType::Float;
+
+    fn into_val(self) -> Val {
+        Val::Float(self as f64)
+    }
+}
+
+impl FromVal for Duration {
+    const DATA_TYPE: DataType = DataType::Duration;
+
+    fn from_val(val: Val) -> Result<Self, FromValError> {
+        match val {
+            Val::Duration(d) if d < 0 => Err(FromValError::NegativeDuration(val)),
+            Val::Duration(d) => Ok(super::duration_from_nanos(d)),
+            v => Err(FromValError::MismatchedType {
+                expected: <Self as FromVal>::DATA_TYPE,
+                found: v.data_type(),
+            }),
+        }
+    }
+}
+
+impl IntoVal for Duration {
+    const DATA_TYPE: DataType = DataType::Duration;
+
+    fn into_val(self) -> Val {
+        Val::Duration(self.as_nanos() as i128)
+    }
+}
+
+impl IntoVal for &str {
+    const DATA_TYPE: DataType = DataType::Str;
+
+    fn into_val(self) -> Val {
+        Val::Str(self.to_owned())
+    }
+}
+
+impl FromVal for () {
+    const DATA_TYPE: DataType = DataType::Unit;
+
+    fn from_val(val: Val) -> Result<Self, FromValError> {
+        match val {
+            Val::Unit => Ok(()),
+            v => Err(FromValError::MismatchedType {
+                expected: <Self as FromVal>::DATA_TYPE,
+                found: v.data_type(),
+            }),
+        }
+    }
+}
+
+impl IntoVal for () {
+    const DATA_TYPE: DataType = DataType::Unit;
+
+    fn into_val(self) -> Val {
+        Val::Unit
+    }
+}
+
+/// The return value of a typed host function, either a value or a result with an error
+/// message.
+pub trait IntoHostResult {
+    const DATA_TYPE: DataType;
+
+    fn into_host_result(self) -> Result<Val, String>;
+}
+
+impl<T: IntoVal> IntoHostResult for T {
+    const DATA_TYPE: DataType = <T as IntoVal>::DATA_TYPE;
+
+    fn into_host_result(self) -> Result<Val, String> {
+        Ok(self.into_val())
+    }
+}
+
+impl<T: IntoVal, E: Display> IntoHostResult for Result<T, E> {
+    const DATA_TYPE: DataType = <T as IntoVal>::DATA_TYPE;
+
+    fn into_host_result(self) -> Result<Val, String> {
+        self.map(IntoVal::into_val).map_err(|e| e.to_string())
+    }
+}
+
+/// A host function with typed rust parameters, see [`Checker::register_typed_fun`].
+pub trait TypedHostFun<Args>: Send + Sync + 'static {
+    fn signature(&self) -> FunSignature;
+
+    fn call(&self, args: &[Val]) -> Result<Val, String>;
+}
+
+macro_rules! impl_typed_host_fun {
+    ($($arg:ident),*) => {
+        impl<F, R, $($arg),*> TypedHostFun<($($arg,)*)> for F
+        where
+            F: Fn($($arg),*) -> R + Send + Sync + 'static,
+            R: IntoHostResult,
+            $($arg: FromVal,)*
+        {
+            fn signature(&self) -> FunSignature {
+                let params = &const { [$(<$arg as FromVal>::DATA_TYPE),*] };
+                FunSignature::new(params, Repetition::One, <R as IntoHostResult>::DATA_TYPE)
+            }
+
+            #[allow(non_snake_case, unused_variables, unused_mut)]
+            fn call(&self, args: &[Val]) -> Result<Val, String> {
+                let mut args = args.iter().cloned();
+                $(
+                    let $arg = match args.next() {
+                        Some(a) => $arg::from_val(a).map_err(|e| e.to_string())?,
+                        None => return Err("Missing argument".into()),
+                    };
+                )*
+                self($($arg),*).into_host_result()
+            }
+        }
+    };
+}
+
+impl_typed_host_fun!();
+impl_typed_host_fun!(A);
+impl_typed_host_fun!(A, B);
+impl_typed_host_fun!(A, B, C);
+impl_typed_host_fun!(A, B, C, D);
+impl_typed_host_fun!(A, B, C, D, E);
+
+impl Checker {
+    /// Register a host function with typed rust parameters, the signature is derived from the
+    /// [`FromVal`] and [`IntoVal`] implementations of the parameters and return type.
+    pub fn register_typed_fun<Args>(
+        &mut self,
+        name: &str,
+        fun: impl TypedHostFun<Args>,
+    ) -> crate::Result<HostFunRef> {
+        let signature = fun.signature();
+        self.register_fun(name, signature, move |args| fun.call(args))
+    }
+}
@@ -0,0 +1,156 @@
+use std::cmp::Ordering;
+use std::ops::ControlFlow;
+
+use crate::{Ast, AstT, Asts, EvalState, Funs, Observer, Span};
+
+/// Collects how often each statement was evaluated and each branch was taken.
+///
+/// All statements and branches of the program are known up front, so the ones that were
+/// never reached are reported with a count of zero.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Coverage {
+    /// The statements and their hit counts, sorted by position.
+    pub statements: Vec<StatementCoverage>,
+    /// The `if` and `match` expressions and the hit counts of their branches, sorted by
+    /// position.
+    pub branches: Vec<BranchCoverage>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StatementCoverage {
+    pub span: Span,
+    pub hits: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BranchCoverage {
+    pub span: Span,
+    /// The hit count of each case or arm, followed by the `else` or default branch.
+    pub hits: Vec<u32>,
+}
+
+impl BranchCoverage {
+    /// Whether the expression itself was ever evaluated.
+    pub fn reached(&self) -> bool {
+        self.hits.iter().any(|h| *h > 0)
+    }
+}
+
+impl Coverage {
+    /// Collect the statements and branches of the top-level code and all functions.
+    pub fn new(asts: &Asts, funs: &Funs) -> Self {
+        let mut coverage = Self::default();
+        coverage.visit_block(&asts.asts);
+        for f in funs.iter() {
+            coverage.visit_block(&f.block);
+        }
+        coverage
+            .statements
+            .sort_by(|a, b| cmp_spans(a.span, b.span));
+        coverage.branches.sort_by(|a, b| cmp_spans(a.span, b.span));
+        coverage
+    }
+
+    /// The hit count of each line that contains the start of a statement, sorted by line.
+    /// Lines with multiple statements report the highest count.
+    pub fn lines(&self) -> Vec<(u32, u32)> {
+        let mut lines: Vec<(u32, u32)> = Vec::new();
+        for s in self.statements.iter() {
+            let line = s.span.start.line;
+            match lines.last_mut() {
+                Some((l, hits)) if *l == line => *hits = (*hits).max(s.hits),
+                _ => lines.push((line, s.hits)),
+            }
+        }
+        lines
+    }
+
+    fn visit_block(&mut self, asts: &[Ast]) {
+        for a in asts {
+            // function definitions and empty statements aren't evaluated
+            if let AstT::Unit = a.typ {
+                continue;
+            }
+            self.statements.push(StatementCoverage {
+                span: a.span,
+                hits: 0,
+            });
+            self.visit(a);
+        }
+    }
+
+    fn visit(&mut self, ast: &Ast) {
+        match &ast.typ {
+            AstT::Error | AstT::Var(_) | AstT::Val(_) | AstT::Unit | AstT::Spill(_) => (),
+            AstT::Op(_, args)
+            | AstT::FunCall(_, args)
+            | AstT::BuiltinFunCall(_, args)
+            | AstT::HostFunCall(_, args) => args.iter().for_each(|a| self.visit(a)),
+            AstT::Is(a, _) | AstT::Cast(a, _) | AstT::VarAssign(_, a) | AstT::Return(a) => {
+                self.visit(a)
+            }
+            AstT::Block(b) => self.visit_block(b),
+            AstT::IfExpr(i) => {
+                self.branches.push(BranchCoverage {
+                    span: ast.span,
+                    hits: vec![0; i.cases.len() + 1],
+                });
+                for c in i.cases.iter() {
+                    self.visit(&c.cond);
+                    self.visit_block(&c.block);
+                }
+                if let Some(b) = &i.else_block {
+                    self.visit_block(b);
+                }
+            }
+            AstT::MatchExpr(m) => {
+                self.branches.push(BranchCoverage {
+                    span: ast.span,
+                    hits: vec![0; m.arms.len() + 1],
+                });
+                self.visit(&m.value);
+                for a in m.arms.iter() {
+                    self.visit(&a.cond);
+                    self.visit(&a.expr);
+                }
+                if let Some(a) = &m.default_arm {
+                    self.visit(a);
+                }
+            }
+            AstT::WhileLoop(w) => {
+                self.visit(&w.cond);
+                self.visit_block(&w.block);
+            }
+            AstT::ForLoop(f) => {
+                self.visit(&f.iter);
+                self.visit_block(&f.block);
+            }
+        }
+    }
+}
+
+impl Observer for Coverage {
+    fn statement(&mut self, _state: &EvalState<'_>, ast: &Ast) -> ControlFlow<u8> {
+        let found = self
+            .statements
+            .binary_search_by(|s| cmp_spans(s.span, ast.span));
+        if let Ok(i) = found {
+            self.statements[i].hits += 1;
+        }
+        ControlFlow::Continue(())
+    }
+
+    fn branch(&mut self, _state: &EvalState<'_>, span: Span, index: usize) {
+        let found = self.branches.binary_search_by(|b| cmp_spans(b.span, span));
+        if let Some(hits) = found
+            .ok()
+            .and_then(|i| self.branches[i].hits.get_mut(index))
+        {
+            *hits += 1;
+        }
+    }
+}
+
+fn cmp_spans(a: Span, b: Span) -> Ordering {
+    a.start.cmp(&b.start).then(a.end.cmp(&b.end))
+}
@@ -0,0 +1,249 @@
+use std::fmt::{self, Display};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use super::{Observer, Rng};
+use crate::Span;
+
+/// The environment a program is evaluated in.
+#[derive(Default)]
+pub struct Env<'a> {
+    pub io: Io<'a>,
+    pub clock: Box<dyn Clock + 'a>,
+    pub rng: Rng,
+    pub permissions: Permissions,
+    /// Notified while the program is evaluated, used by debuggers.
+    pub observer: Option<Box<dyn Observer + 'a>>,
+    /// Arguments passed to the script.
+    pub args: Vec<String>,
+    /// Set if the script called `exit`.
+    pub exit_code: Option<u8>,
+    /// The calls that were being evaluated when a runtime error occurred, the innermost call
+    /// is first.
+    pub trace: Vec<TraceFrame>,
+}
+
+impl<'a> Env<'a> {
+    pub fn new(io: Io<'a>) -> Self {
+        Self {
+            io,
+            clock: Box::new(SystemClock::default()),
+            rng: Rng::default(),
+            permissions: Permissions::default(),
+            observer: None,
+            args: Vec::new(),
+            exit_code: None,
+            trace: Vec::new(),
+        }
+    }
+}
+
+/// A function call in the stack trace of a runtime error.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TraceFrame {
+    pub name: String,
+    /// The span of the call expression.
+    pub span: Span,
+}
+
+impl TraceFrame {
+    pub const fn new(name: String, span: Span) -> Self {
+        Self { name, span }
+    }
+}
+
+/// The reader and writer input and output of a program is routed through.
+pub struct Io<'a> {
+    pub stdin: Box<dyn BufRead + 'a>,
+    pub stdout: Box<dyn Write + 'a>,
+}
+
+impl Default for Io<'_> {
+    fn default() -> Self {
+        Self {
+            // the lock of stdin is only held during each read, so the host can still read
+            // from it while the environment exists
+            stdin: Box::new(BufReader::new(io::stdin())),
+            stdout: Box::new(io::stdout()),
+        }
+    }
+}
+
+impl<'a> Io<'a> {
+    pub fn new(stdin: impl BufRead + 'a, stdout: impl Write + 'a) -> Self {
+        Self {
+            stdin: Box::new(stdin),
+            stdout: Box::new(stdout),
+        }
+    }
+
+    /// Provide no input and discard all output.
+    pub fn silent() -> Self {
+        Self::new(io::empty(), io::sink())
+    }
+}
+
+/// The source of time used by `now`, `monotonic` and `sleep`.
+pub trait Clock {
+    /// Nanoseconds since the unix epoch.
+    fn now(&self) -> i128;
+
+    /// Nanoseconds since an unspecified point in time, never decreases.
+    fn monotonic(&self) -> i128;
+
+    fn sleep(&mut self, duration: Duration);
+}
+
+impl Default for Box<dyn Clock + '_> {
+    fn default() -> Self {
+        Box::new(SystemClock::default())
+    }
+}
+
+impl<C: Clock + ?Sized> Clock for &mut C {
+    fn now(&self) -> i128 {
+        (**self).now()
+    }
+
+    fn monotonic(&self) -> i128 {
+        (**self).monotonic()
+    }
+
+    fn sleep(&mut self, duration: Duration) {
+        (**self).sleep(duration)
+    }
+}
+
+/// The clock of the operating system.
+#[derive(Clone, Copy, Debug)]
+pub struct SystemClock {
+    start: Instant,
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> i128 {
+        match SystemTime::now().duration_since(UNIX_EPOCH) {
+            Ok(d) => d.as_nanos() as i128,
+            Err(e) => -(e.duration().as_nanos() as i128),
+        }
+    }
+
+    fn monotonic(&self) -> i128 {
+        self.start.elapsed().as_nanos() as i128
+    }
+
+    fn sleep(&mut self, duration: Duration) {
+        std::thread::sleep(duration);
+    }
+}
+
+/// A clock that only advances when sleeping, without actually waiting.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FakeClock {
+    /// Nanoseconds since the unix epoch.
+    pub time: i128,
+    /// Nanoseconds slept in total.
+    pub elapsed: i128,
+}
+
+impl FakeClock {
+    pub const fn new(time: i128) -> Self {
+        Self { time, elapsed: 0 }
+    }
+}
+
+impl Clock for FakeClock {
+    fn now(&self) -> i128 {
+        self.time + self.elapsed
+    }
+
+    fn monotonic(&self) -> i128 {
+        self.elapsed
+    }
+
+    fn sleep(&mut self, duration: Duration) {
+        self.elapsed += duration.as_nanos() as i128;
+    }
+}
+
+/// Access to the file system and environment variables, everything is denied by default.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Permissions {
+    read: Vec<PathBuf>,
+    write: Vec<PathBuf>,
+    /// Allow reading environment variables.
+    pub env: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+    Env,
+}
+
+impl Display for Access {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Read => write!(f, "read"),
+            Self::Write => write!(f, "write"),
+            Self::Env => write!(f, "env"),
+        }
+    }
+}
+
+impl Permissions {
+    /// Allow reading files inside this path.
+    pub fn allow_read(&mut self, path: impl AsRef<Path>) {
+        self.read.extend(resolve_path(path.as_ref()));
+    }
+
+    /// Allow writing files inside this path.
+    pub fn allow_write(&mut self, path: impl AsRef<Path>) {
+        self.write.extend(resolve_path(path.as_ref()));
+    }
+
+    pub fn is_allowed(&self, access: Access, path: &Path) -> bool {
+        let allowed = match access {
+            Access::Read => &self.read,
+            Access::Write => &self.write,
+            Access::Env => return self.env,
+        };
+        let Some(path) = resolve_path(path) else {
+            return false;
+        };
+        allowed.iter().any(|a| path.starts_with(a))
+    }
+}
+
+/// Resolve symlinks and relative components of a path, that might not exist yet. The longest
+/// existing ancestor is canonicalized first, so `..` components are applied to the target of
+/// symlinks like the OS does. Returns `None` if a `..` component follows a part of the path that
+/// doesn't exist.
+fn resolve_path(path: &Path) -> Option<PathBuf> {
+    let absolute = std::path::absolute(path).ok()?;
+    let components = absolute.components().collect::<Vec<_>>();
+    let (mut resolved, rest) = (0..=components.len()).rev().find_map(|i| {
+        let existing = components[..i].iter().collect::<PathBuf>();
+        let resolved = existing.canonicalize().ok()?;
+        Some((resolved, &components[i..]))
+    })?;
+
+    for c in rest {
+        match c {
+            Component::Normal(name) => resolved.push(name),
+            Component::CurDir => (),
+            _ => return None,
+        }
+    }
+    Some(resolved)
+}
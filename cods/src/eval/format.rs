@@ -0,0 +1,358 @@
+use std::fmt::{self, Display, Write as _};
+
+use crate::{DataType, Span, Val};
+
+/// A parsed format string, as used by `format` and `printf`.
+///
+/// Placeholders are written like `{}`, `{1}` or `{:>8.3}`, literal braces are escaped as `{{` and
+/// `}}`. The specifier follows the syntax `[[fill]align][+][0][width][.precision][type]`, where
+/// `align` is one of `<`, `^`, `>` and `type` is one of `x`, `X`, `o`, `b`, `e`, `E`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Format {
+    pub segments: Vec<FormatSegment>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum FormatSegment {
+    Str(String),
+    Arg(FormatArg),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct FormatArg {
+    /// The index of the argument that is formatted.
+    pub index: usize,
+    pub spec: FormatSpec,
+    /// The placeholder as written in the format string.
+    pub text: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FormatSpec {
+    pub fill: char,
+    pub align: Option<Align>,
+    pub sign: bool,
+    pub zero: bool,
+    pub width: Option<usize>,
+    pub precision: Option<usize>,
+    pub kind: FormatKind,
+}
+
+impl Default for FormatSpec {
+    fn default() -> Self {
+        Self {
+            fill: ' ',
+            align: None,
+            sign: false,
+            zero: false,
+            width: None,
+            precision: None,
+            kind: FormatKind::Display,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FormatKind {
+    Display,
+    LowerHex,
+    UpperHex,
+    Octal,
+    Binary,
+    LowerExp,
+    UpperExp,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FormatError {
+    UnclosedPlaceholder,
+    UnmatchedClosingBrace,
+    InvalidIndex(String),
+    InvalidSpec(String),
+}
+
+impl Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnclosedPlaceholder => write!(f, "Unclosed `{{` placeholder"),
+            Self::UnmatchedClosingBrace => {
+                write!(f, "Unmatched `}}`, use `}}}}` to write a literal brace")
+            }
+            Self::InvalidIndex(i) => write!(f, "Invalid argument index `{i}`"),
+            Self::InvalidSpec(s) => write!(f, "Invalid format specifier `{s}`"),
+        }
+    }
+}
+
+impl Format {
+    pub fn parse(input: &str) -> Result<Self, FormatError> {
+        let mut segments = Vec::new();
+        let mut str = String::new();
+        let mut next_index = 0;
+        let mut chars = input.char_indices().peekable();
+
+        while let Some((start, c)) = chars.next() {
+            match c {
+                '{' if chars.next_if(|(_, c)| *c == '{').is_some() => str.push('{'),
+                '}' if chars.next_if(|(_, c)| *c == '}').is_some() => str.push('}'),
+                '}' => return Err(FormatError::UnmatchedClosingBrace),
+                '{' => {
+                    let Some((end, _)) = chars.find(|(_, c)| *c == '}') else {
+                        return Err(FormatError::UnclosedPlaceholder);
+                    };
+                    let inner = &input[start + 1..end];
+                    let (index, spec) = match inner.split_once(':') {
+                        Some((i, s)) => (i, s),
+                        None => (inner, ""),
+                    };
+
+                    let index = if index.is_empty() {
+                        next_index += 1;
+                        next_index - 1
+                    } else {
+                        index
+                            .parse()
+                            .map_err(|_| FormatError::InvalidIndex(index.to_owned()))?
+                    };
+                    let spec = parse_spec(spec)?;
+
+                    if !str.is_empty() {
+                        segments.push(FormatSegment::Str(std::mem::take(&mut str)));
+                    }
+                    segments.push(FormatSegment::Arg(FormatArg {
+                        index,
+                        spec,
+                        text: input[start..=end].to_owned(),
+                    }));
+                }
+                _ => str.push(c),
+            }
+        }
+
+        if !str.is_empty() {
+            segments.push(FormatSegment::Str(str));
+        }
+
+        Ok(Self { segments })
+    }
+
+    pub fn args(&self) -> impl Iterator<Item = &FormatArg> {
+        self.segments.iter().filter_map(|s| match s {
+            FormatSegment::Arg(a) => Some(a),
+            FormatSegment::Str(_) => None,
+        })
+    }
+
+    /// The number of arguments this format string expects.
+    pub fn arg_count(&self) -> usize {
+        self.args().map(|a| a.index + 1).max().unwrap_or(0)
+    }
+
+    /// Validate the number and types of the arguments, `span` is the span of the whole call.
+    pub fn check_args(&self, args: &[(DataType, Span)], span: Span) -> crate::Result<()> {
+        let expected = self.arg_count();
+        if args.len() != expected {
+            return Err(crate::Error::FormatArgCount {
+                expected,
+                found: args.len(),
+                span,
+            });
+        }
+
+        for a in self.args() {
+            let (data_type, span) = args[a.index];
+            if !a.spec.supports(data_type) {
+                return Err(crate::Error::FormatArgType {
+                    placeholder: a.text.clone(),
+                    data_type,
+                    span,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Format the arguments, they have to be validated using [`Format::check_args`] before.
+    pub fn write(&self, out: &mut String, args: &[Val]) {
+        for s in self.segments.iter() {
+            match s {
+                FormatSegment::Str(s) => out.push_str(s),
+                FormatSegment::Arg(a) => a.spec.write(out, &args[a.index]),
+            }
+        }
+    }
+}
+
+fn parse_spec(input: &str) -> Result<FormatSpec, FormatError> {
+    let invalid = || FormatError::InvalidSpec(input.to_owned());
+    let mut spec = FormatSpec::default();
+    let mut chars = input.chars().peekable();
+
+    let parse_align = |c| match c {
+        '<' => Some(Align::Left),
+        '^' => Some(Align::Center),
+        '>' => Some(Align::Right),
+        _ => None,
+    };
+    let mut lookahead = input.chars();
+    match (lookahead.next(), lookahead.next()) {
+        (Some(fill), Some(a)) if parse_align(a).is_some() => {
+            spec.fill = fill;
+            spec.align = parse_align(a);
+            chars.next();
+            chars.next();
+        }
+        (Some(a), _) if parse_align(a).is_some() => {
+            spec.align = parse_align(a);
+            chars.next();
+        }
+        _ => (),
+    }
+
+    spec.sign = chars.next_if_eq(&'+').is_some();
+    spec.zero = chars.next_if_eq(&'0').is_some();
+    spec.width = parse_num(&mut chars).map_err(|_| invalid())?;
+    if chars.next_if_eq(&'.').is_some() {
+        let precision = parse_num(&mut chars).map_err(|_| invalid())?;
+        spec.precision = Some(precision.ok_or_else(invalid)?);
+    }
+
+    spec.kind = match chars.next() {
+        None => FormatKind::Display,
+        Some('x') => FormatKind::LowerHex,
+        Some('X') => FormatKind::UpperHex,
+        Some('o') => FormatKind::Octal,
+        Some('b') => FormatKind::Binary,
+        Some('e') => FormatKind::LowerExp,
+        Some('E') => FormatKind::UpperExp,
+        Some(_) => return Err(invalid()),
+    };
+    if chars.next().is_some() {
+        return Err(invalid());
+    }
+
+    Ok(spec)
+}
+
+/// The largest allowed width and precision, larger ones would pad values to enormous strings.
+const MAX_WIDTH: usize = u16::MAX as usize;
+
+/// Parse a width or precision, fails if it is larger than [`MAX_WIDTH`].
+fn parse_num(chars: &mut std::iter::Peekable<std::str::Chars<'_>>) -> Result<Option<usize>, ()> {
+    let mut num = None;
+    while let Some(c) = chars.next_if(char::is_ascii_digit) {
+        let digit = (c as u32 - '0' as u32) as usize;
+        let n = num.unwrap_or(0usize) * 10 + digit;
+        if n > MAX_WIDTH {
+            return Err(());
+        }
+        num = Some(n);
+    }
+    Ok(num)
+}
+
+impl FormatSpec {
+    /// Whether this specifier can format values of the data type.
+    pub fn supports(&self, data_type: DataType) -> bool {
+        use DataType::*;
+
+        let numeric = matches!(data_type, Int | Float | Any);
+        if (self.sign || self.zero) && !numeric {
+            return false;
+        }
+        let exp = matches!(self.kind, FormatKind::LowerExp | FormatKind::UpperExp);
+        let precise = matches!(data_type, Float | Str | Any) || (exp && data_type == Int);
+        if self.precision.is_some() && !precise {
+            return false;
+        }
+        match self.kind {
+            FormatKind::Display => true,
+            FormatKind::LowerHex
+            | FormatKind::UpperHex
+            | FormatKind::Octal
+            | FormatKind::Binary => matches!(data_type, Int | Any),
+            FormatKind::LowerExp | FormatKind::UpperExp => numeric,
+        }
+    }
+
+    fn write(&self, out: &mut String, val: &Val) {
+        let mut sign = "";
+        let body = match val {
+            Val::Int(i) => {
+                if *i < 0 {
+                    sign = "-";
+                } else if self.sign {
+                    sign = "+";
+                }
+                let i = i.unsigned_abs();
+                match self.kind {
+                    FormatKind::Display => i.to_string(),
+                    FormatKind::LowerHex => format!("{i:x}"),
+                    FormatKind::UpperHex => format!("{i:X}"),
+                    FormatKind::Octal => format!("{i:o}"),
+                    FormatKind::Binary => format!("{i:b}"),
+                    FormatKind::LowerExp => match self.precision {
+                        Some(p) => format!("{i:.p$e}"),
+                        None => format!("{i:e}"),
+                    },
+                    FormatKind::UpperExp => match self.precision {
+                        Some(p) => format!("{i:.p$E}"),
+                        None => format!("{i:E}"),
+                    },
+                }
+            }
+            Val::Float(f) => {
+                if f.is_sign_negative() && !f.is_nan() {
+                    sign = "-";
+                } else if self.sign {
+                    sign = "+";
+                }
+                let f = f.abs();
+                match (self.kind, self.precision) {
+                    (FormatKind::LowerExp, Some(p)) => format!("{f:.p$e}"),
+                    (FormatKind::LowerExp, None) => format!("{f:e}"),
+                    (FormatKind::UpperExp, Some(p)) => format!("{f:.p$E}"),
+                    (FormatKind::UpperExp, None) => format!("{f:E}"),
+                    (_, Some(p)) => format!("{f:.p$}"),
+                    (_, None) => f.to_string(),
+                }
+            }
+            Val::Str(s) => match self.precision {
+                Some(p) => s.chars().take(p).collect(),
+                None => s.clone(),
+            },
+            v => v.to_string(),
+        };
+
+        let len = sign.chars().count() + body.chars().count();
+        let padding = self.width.unwrap_or(0).saturating_sub(len);
+        if self.zero {
+            out.push_str(sign);
+            out.extend(std::iter::repeat_n('0', padding));
+            out.push_str(&body);
+            return;
+        }
+
+        let numeric = matches!(val, Val::Int(_) | Val::Float(_));
+        let align = match self.align {
+            Some(a) => a,
+            None if numeric => Align::Right,
+            None => Align::Left,
+        };
+        let (before, after) = match align {
+            Align::Left => (0, padding),
+            Align::Center => (padding / 2, padding - padding / 2),
+            Align::Right => (padding, 0),
+        };
+        out.extend(std::iter::repeat_n(self.fill, before));
+        let _ = write!(out, "{sign}{body}");
+        out.extend(std::iter::repeat_n(self.fill, after));
+    }
+}
@@ -1,11 +1,27 @@
-use std::io::Write;
+use std::env::VarError;
+use std::fmt;
+use std::io::{BufRead, Write};
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 
 use crate::ast::{BuiltinFunCall, ForLoop, IfExpr, MatchExpr, Op, WhileLoop};
-use crate::{Ast, AstT, Asts, DataType, FunRef, Funs, Range, Span, Val, ValSpan};
+use crate::{Ast, AstT, Asts, DataType, FunRef, Funs, HostFunRef, Range, Span, Val, ValSpan};
 
+pub use convert::*;
+pub use coverage::*;
+pub use env::*;
+pub use format::*;
+pub use observer::*;
+pub use random::*;
 pub use stack::*;
 
+mod convert;
+mod coverage;
+mod env;
+mod format;
+mod observer;
+mod random;
 mod stack;
 #[cfg(test)]
 mod test;
@@ -15,743 +31,1268 @@ type EvalResult<T> = std::result::Result<T, EvalError>;
 
 enum EvalError {
     Return(Val),
+    Exit(u8),
     Error(crate::Error),
 }
 
-pub fn eval(funs: &Funs, asts: &Asts) -> crate::Result<Val> {
-    let mut stack = Stack::default();
-    eval_with(&mut stack, funs, asts)
+struct Evaluator<'a, 'b> {
+    stack: &'a mut Stack,
+    funs: &'a Funs,
+    env: &'a mut Env<'b>,
+    calls: Vec<CallFrame>,
+}
+
+impl<'a, 'b> Evaluator<'a, 'b> {
+    fn new(stack: &'a mut Stack, funs: &'a Funs, env: &'a mut Env<'b>) -> Self {
+        Self {
+            stack,
+            funs,
+            env,
+            calls: Vec::new(),
+        }
+    }
+
+    fn finish(self, res: EvalResult<Val>) -> crate::Result<Val> {
+        match res {
+            Ok(v) => Ok(v),
+            Err(EvalError::Exit(code)) => {
+                self.env.exit_code = Some(code);
+                Ok(Val::Unit)
+            }
+            Err(EvalError::Error(e)) => Err(e),
+            Err(EvalError::Return(_)) => unreachable!(),
+        }
+    }
 }
 
 pub fn eval_with(stack: &mut Stack, funs: &Funs, asts: &Asts) -> crate::Result<Val> {
+    let mut env = Env::default();
+    eval_with_env(stack, funs, &mut env, asts)
+}
+
+pub fn eval_with_env(
+    stack: &mut Stack,
+    funs: &Funs,
+    env: &mut Env<'_>,
+    asts: &Asts,
+) -> crate::Result<Val> {
     stack.resize(asts.global_frame_size);
-    match eval_asts(stack, funs, &asts.asts) {
-        Ok(v) => Ok(v),
-        Err(EvalError::Error(e)) => Err(e),
-        Err(_) => unreachable!(),
+    env.trace.clear();
+    let mut evaluator = Evaluator::new(stack, funs, env);
+    evaluator.calls.push(CallFrame::new(None, Span::pos(0, 0)));
+    let res = evaluator.eval_asts(&asts.asts);
+    evaluator.finish(res)
+}
+
+/// Call a top-level script function by name, with arguments supplied by the host application.
+/// The stack has to contain the global frame the function was checked against, for example by
+/// evaluating the script with [`eval_with`] first.
+pub fn call(stack: &mut Stack, funs: &Funs, name: &str, args: &[Val]) -> crate::Result<Val> {
+    let mut env = Env::default();
+    call_with_env(stack, funs, &mut env, name, args)
+}
+
+pub fn call_with_env(
+    stack: &mut Stack,
+    funs: &Funs,
+    env: &mut Env<'_>,
+    name: &str,
+    args: &[Val],
+) -> crate::Result<Val> {
+    match funs.find(name) {
+        Some(fun_ref) => call_fun(stack, funs, env, fun_ref, args),
+        None => Err(crate::Error::UnknownFun(name.to_owned())),
     }
 }
 
-fn eval_asts(stack: &mut Stack, funs: &Funs, asts: &[Ast]) -> EvalResult<Val> {
-    match asts.split_last() {
-        Some((last, others)) => {
-            for a in others {
-                eval_ast(stack, funs, a)?;
+/// Call a script function with arguments supplied by the host application, validating them
+/// against the function signature.
+pub fn call_fun(
+    stack: &mut Stack,
+    funs: &Funs,
+    env: &mut Env<'_>,
+    fun_ref: FunRef,
+    args: &[Val],
+) -> crate::Result<Val> {
+    let fun = funs.get(fun_ref);
+    if args.len() != fun.params.len() {
+        return Err(crate::Error::CallArgCount {
+            name: fun.name.clone(),
+            expected: fun.params.len(),
+            found: args.len(),
+        });
+    }
+    for (i, (p, a)) in fun.params.iter().zip(args.iter()).enumerate() {
+        if a.data_type().is_not(p.data_type) {
+            return Err(crate::Error::CallArgType {
+                name: fun.name.clone(),
+                index: i,
+                expected: p.data_type,
+                found: a.data_type(),
+            });
+        }
+    }
+
+    env.trace.clear();
+    let mut evaluator = Evaluator::new(stack, funs, env);
+    let res = evaluator.eval_fun(fun_ref, args.to_vec(), Span::pos(0, 0));
+    evaluator.finish(res)
+}
+
+impl Evaluator<'_, '_> {
+    fn eval_asts(&mut self, asts: &[Ast]) -> EvalResult<Val> {
+        match asts.split_last() {
+            Some((last, others)) => {
+                for a in others {
+                    self.observe_statement(a)?;
+                    self.eval_ast(a)?;
+                }
+
+                self.observe_statement(last)?;
+                self.eval_ast(last)
             }
+            None => Ok(Val::Unit),
+        }
+    }
 
-            eval_ast(stack, funs, last)
+    fn observe_statement(&mut self, ast: &Ast) -> EvalResult<()> {
+        // function definitions and empty statements don't do anything at runtime
+        if let AstT::Unit = ast.typ {
+            return Ok(());
+        }
+        if let Some(c) = self.calls.last_mut() {
+            c.span = ast.span;
+        }
+        if let Some(observer) = self.env.observer.as_mut() {
+            let state = EvalState::new(self.stack, self.funs, &self.calls);
+            if let ControlFlow::Break(code) = observer.statement(&state, ast) {
+                return Err(EvalError::Exit(code));
+            }
         }
-        None => Ok(Val::Unit),
+        Ok(())
     }
-}
 
-fn eval_ast(stack: &mut Stack, funs: &Funs, ast: &Ast) -> EvalResult<Val> {
-    match &ast.typ {
-        AstT::Error => err(crate::Error::Parsing(ast.span)),
-        AstT::Var(v) => Ok(stack.get(v)),
-        AstT::Val(v) => Ok(v.clone()),
-        AstT::Op(o, a) => eval_op(stack, funs, o, a),
-        AstT::Is(a, t) => eval_is(stack, funs, a, *t),
-        AstT::Cast(a, t) => eval_cast(stack, funs, a, *t),
-        AstT::Unit => Ok(Val::Unit),
-        AstT::Block(b) => eval_asts(stack, funs, b),
-        AstT::IfExpr(i) => eval_if_expr(stack, funs, i),
-        AstT::MatchExpr(m) => eval_match_expr(stack, funs, m),
-        AstT::WhileLoop(w) => eval_while_loop(stack, funs, w),
-        AstT::ForLoop(f) => eval_for_loop(stack, funs, f),
-        AstT::VarAssign(v, e) => eval_var_assign(stack, funs, v, e),
-        AstT::FunCall(f, a) => eval_fun_call(stack, funs, *f, a),
-        AstT::Return(v) => eval_return(stack, funs, v),
-        AstT::BuiltinFunCall(f, a) => eval_builtin_fun_call(stack, funs, *f, a),
-        AstT::Spill(v) => eval_spill(stack, v),
+    fn observe_branch(&mut self, span: Span, index: usize) {
+        if let Some(observer) = self.env.observer.as_mut() {
+            let state = EvalState::new(self.stack, self.funs, &self.calls);
+            observer.branch(&state, span, index);
+        }
     }
-}
 
-fn eval_op(stack: &mut Stack, funs: &Funs, op: &Op, args: &[Ast]) -> EvalResult<Val> {
-    let val = match op {
-        Op::Not => {
-            let va = eval_ast(stack, funs, &args[0])?.unwrap_bool();
-            Val::Bool(!va)
-        }
-        Op::NegInt => {
-            let va = eval_ast(stack, funs, &args[0])?.unwrap_int();
-            Val::Int(-va)
-        }
-        Op::NegFloat => {
-            let va = eval_ast(stack, funs, &args[0])?.unwrap_float();
-            Val::Float(-va)
-        }
-        Op::RangeEx => {
-            let va = eval_ast(stack, funs, &args[0])?.unwrap_int();
-            let vb = eval_ast(stack, funs, &args[1])?.unwrap_int();
-            Val::Range(Range::Exclusive(va, vb))
-        }
-        Op::RangeIn => {
-            let va = eval_ast(stack, funs, &args[0])?.unwrap_int();
-            let vb = eval_ast(stack, funs, &args[1])?.unwrap_int();
-            Val::Range(Range::Inclusive(va, vb))
-        }
-        Op::AddInt => {
-            let va = eval_ast(stack, funs, &args[0])?.unwrap_int();
-            let vb = eval_ast(stack, funs, &args[1])?.unwrap_int();
-            match va.checked_add(vb) {
-                Some(i) => Val::Int(i),
-                None => return err(crate::Error::AddOverflow(args[0].span, args[1].span)),
-            }
-        }
-        Op::AddFloat => {
-            let va = eval_ast(stack, funs, &args[0])?.unwrap_float();
-            let vb = eval_ast(stack, funs, &args[1])?.unwrap_float();
-            Val::Float(va + vb)
-        }
-        Op::SubInt => {
-            let va = eval_ast(stack, funs, &args[0])?.unwrap_int();
-            let vb = eval_ast(stack, funs, &args[1])?.unwrap_int();
-            match va.checked_sub(vb) {
-                Some(i) => Val::Int(i),
-                None => return err(crate::Error::SubOverflow(args[0].span, args[1].span)),
-            }
-        }
-        Op::SubFloat => {
-            let va = eval_ast(stack, funs, &args[0])?.unwrap_float();
-            let vb = eval_ast(stack, funs, &args[1])?.unwrap_float();
-            Val::Float(va - vb)
-        }
-        Op::MulInt => {
-            let va = eval_ast(stack, funs, &args[0])?.unwrap_int();
-            let vb = eval_ast(stack, funs, &args[1])?.unwrap_int();
-            match va.checked_mul(vb) {
-                Some(i) => Val::Int(i),
-                None => return err(crate::Error::MulOverflow(args[0].span, args[1].span)),
-            }
-        }
-        Op::MulFloat => {
-            let va = eval_ast(stack, funs, &args[0])?.unwrap_float();
-            let vb = eval_ast(stack, funs, &args[1])?.unwrap_float();
-            Val::Float(va * vb)
-        }
-        Op::DivInt => {
-            let va = eval_ast(stack, funs, &args[0])?.unwrap_int();
-            let vb = eval_ast(stack, funs, &args[1])?.unwrap_int();
-            match va.checked_div(vb) {
-                Some(i) => Val::Int(i),
-                None => return err(crate::Error::DivideByZero(args[0].span, args[1].span)),
-            }
-        }
-        Op::DivFloat => {
-            let va = eval_ast(stack, funs, &args[0])?.unwrap_float();
-            let vb = eval_ast(stack, funs, &args[1])?.unwrap_float();
-            Val::Float(va / vb)
-        }
-        Op::PowInt => {
-            let a = &args[0];
-            let b = &args[1];
-            let base = eval_ast(stack, funs, a)?.unwrap_int();
-            let exp = eval_ast(stack, funs, b)?.unwrap_int();
-            if exp < 0 {
-                return err(crate::Error::NegativeIntPow(a.span, b.span));
-            }
-            if exp > u32::MAX as i128 {
-                return err(crate::Error::PowOverflow(a.span, b.span));
-            }
-            match base.checked_pow(exp as u32) {
-                Some(i) => Val::Int(i),
-                None => return err(crate::Error::PowOverflow(a.span, b.span)),
-            }
-        }
-        Op::PowFloat => {
-            let base = eval_ast(stack, funs, &args[0])?.unwrap_float();
-            let exp = eval_ast(stack, funs, &args[1])?.unwrap_float();
-            Val::Float(base.powf(exp))
-        }
-        Op::PowFloatInt => {
-            let a = &args[0];
-            let b = &args[1];
-            let base = eval_ast(stack, funs, a)?.unwrap_float();
-            let exp = eval_ast(stack, funs, b)?.unwrap_int();
-            if exp > i32::MAX as i128 {
-                return err(crate::Error::PowOverflow(a.span, b.span));
-            }
-            Val::Float(base.powi(exp as i32))
-        }
-        Op::RemInt => {
-            let va = eval_ast(stack, funs, &args[0])?.unwrap_int();
-            let vb = eval_ast(stack, funs, &args[1])?.unwrap_int();
-            va.checked_rem(vb)
-                .map(Val::Int)
-                .ok_or(crate::Error::RemainderByZero(args[0].span, args[1].span))
-                .map_err(EvalError::Error)?
-        }
-        Op::RemFloat => {
-            let va = eval_ast(stack, funs, &args[0])?.unwrap_float();
-            let vb = eval_ast(stack, funs, &args[1])?.unwrap_float();
-            Val::Float(va % vb)
-        }
-        Op::RemEuclidInt => {
-            let va = eval_ast(stack, funs, &args[0])?.unwrap_int();
-            let vb = eval_ast(stack, funs, &args[1])?.unwrap_int();
-            if vb == 0 {
-                return err(crate::Error::RemainderByZero(args[0].span, args[1].span));
-            }
-            let r = va % vb;
-            if (r > 0 && vb < 0) || (r < 0 && vb > 0) {
-                Val::Int(r + vb)
-            } else {
-                Val::Int(r)
-            }
-        }
-        Op::FactorialInt => {
-            let va = eval_ast(stack, funs, &args[0])?.unwrap_int();
-
-            if va < 0 {
-                return err(crate::Error::NegativeFactorial(ValSpan::new(
-                    Val::Int(va),
-                    args[0].span,
-                )));
-            }
-
-            let mut f: i128 = 1;
-            for i in 2..=va {
-                f = match f.checked_mul(i) {
-                    Some(v) => v,
-                    None => {
-                        return err(crate::Error::FactorialOverflow(ValSpan::new(
-                            Val::Int(va),
-                            args[0].span,
-                        )))
+    fn eval_ast(&mut self, ast: &Ast) -> EvalResult<Val> {
+        match &ast.typ {
+            AstT::Error => err(crate::Error::Parsing(ast.span)),
+            AstT::Var(v) => Ok(self.stack.get(v)),
+            AstT::Val(v) => Ok(v.clone()),
+            AstT::Op(o, a) => self.eval_op(o, a),
+            AstT::Is(a, t) => self.eval_is(a, *t),
+            AstT::Cast(a, t) => self.eval_cast(a, *t),
+            AstT::Unit => Ok(Val::Unit),
+            AstT::Block(b) => self.eval_asts(b),
+            AstT::IfExpr(i) => self.eval_if_expr(i, ast.span),
+            AstT::MatchExpr(m) => self.eval_match_expr(m, ast.span),
+            AstT::WhileLoop(w) => self.eval_while_loop(w),
+            AstT::ForLoop(f) => self.eval_for_loop(f),
+            AstT::VarAssign(v, e) => self.eval_var_assign(v, e),
+            AstT::FunCall(f, a) => self.eval_fun_call(*f, a, ast.span),
+            AstT::Return(v) => self.eval_return(v),
+            AstT::BuiltinFunCall(f, a) => self.eval_builtin_fun_call(*f, a, ast.span),
+            AstT::HostFunCall(f, a) => self.eval_host_fun_call(*f, a, ast.span),
+            AstT::Spill(v) => self.eval_spill(v, ast.span),
+        }
+    }
+
+    fn eval_op(&mut self, op: &Op, args: &[Ast]) -> EvalResult<Val> {
+        let val = match op {
+            Op::Not => {
+                let va = self.eval_ast(&args[0])?.unwrap_bool();
+                Val::Bool(!va)
+            }
+            Op::NegInt => {
+                let va = self.eval_ast(&args[0])?.unwrap_int();
+                Val::Int(-va)
+            }
+            Op::NegFloat => {
+                let va = self.eval_ast(&args[0])?.unwrap_float();
+                Val::Float(-va)
+            }
+            Op::NegDuration => {
+                let va = self.eval_ast(&args[0])?.unwrap_duration();
+                match va.checked_neg() {
+                    Some(d) => Val::Duration(d),
+                    None => return err(crate::Error::NegOverflow(args[0].span)),
+                }
+            }
+            Op::RangeEx => {
+                let va = self.eval_ast(&args[0])?.unwrap_int();
+                let vb = self.eval_ast(&args[1])?.unwrap_int();
+                Val::Range(Range::Exclusive(va, vb))
+            }
+            Op::RangeIn => {
+                let va = self.eval_ast(&args[0])?.unwrap_int();
+                let vb = self.eval_ast(&args[1])?.unwrap_int();
+                Val::Range(Range::Inclusive(va, vb))
+            }
+            Op::AddInt => {
+                let va = self.eval_ast(&args[0])?.unwrap_int();
+                let vb = self.eval_ast(&args[1])?.unwrap_int();
+                match va.checked_add(vb) {
+                    Some(i) => Val::Int(i),
+                    None => return err(crate::Error::AddOverflow(args[0].span, args[1].span)),
+                }
+            }
+            Op::AddFloat => {
+                let va = self.eval_ast(&args[0])?.unwrap_float();
+                let vb = self.eval_ast(&args[1])?.unwrap_float();
+                Val::Float(va + vb)
+            }
+            Op::AddDuration => {
+                let va = self.eval_ast(&args[0])?.unwrap_duration();
+                let vb = self.eval_ast(&args[1])?.unwrap_duration();
+                match va.checked_add(vb) {
+                    Some(d) => Val::Duration(d),
+                    None => return err(crate::Error::AddOverflow(args[0].span, args[1].span)),
+                }
+            }
+            Op::SubInt => {
+                let va = self.eval_ast(&args[0])?.unwrap_int();
+                let vb = self.eval_ast(&args[1])?.unwrap_int();
+                match va.checked_sub(vb) {
+                    Some(i) => Val::Int(i),
+                    None => return err(crate::Error::SubOverflow(args[0].span, args[1].span)),
+                }
+            }
+            Op::SubFloat => {
+                let va = self.eval_ast(&args[0])?.unwrap_float();
+                let vb = self.eval_ast(&args[1])?.unwrap_float();
+                Val::Float(va - vb)
+            }
+            Op::SubDuration => {
+                let va = self.eval_ast(&args[0])?.unwrap_duration();
+                let vb = self.eval_ast(&args[1])?.unwrap_duration();
+                match va.checked_sub(vb) {
+                    Some(d) => Val::Duration(d),
+                    None => return err(crate::Error::SubOverflow(args[0].span, args[1].span)),
+                }
+            }
+            Op::MulInt => {
+                let va = self.eval_ast(&args[0])?.unwrap_int();
+                let vb = self.eval_ast(&args[1])?.unwrap_int();
+                match va.checked_mul(vb) {
+                    Some(i) => Val::Int(i),
+                    None => return err(crate::Error::MulOverflow(args[0].span, args[1].span)),
+                }
+            }
+            Op::MulFloat => {
+                let va = self.eval_ast(&args[0])?.unwrap_float();
+                let vb = self.eval_ast(&args[1])?.unwrap_float();
+                Val::Float(va * vb)
+            }
+            Op::MulDurationInt => {
+                let va = self.eval_ast(&args[0])?.unwrap_duration();
+                let vb = self.eval_ast(&args[1])?.unwrap_int();
+                match va.checked_mul(vb) {
+                    Some(d) => Val::Duration(d),
+                    None => return err(crate::Error::MulOverflow(args[0].span, args[1].span)),
+                }
+            }
+            Op::MulIntDuration => {
+                let va = self.eval_ast(&args[0])?.unwrap_int();
+                let vb = self.eval_ast(&args[1])?.unwrap_duration();
+                match va.checked_mul(vb) {
+                    Some(d) => Val::Duration(d),
+                    None => return err(crate::Error::MulOverflow(args[0].span, args[1].span)),
+                }
+            }
+            Op::DivInt => {
+                let va = self.eval_ast(&args[0])?.unwrap_int();
+                let vb = self.eval_ast(&args[1])?.unwrap_int();
+                match va.checked_div(vb) {
+                    Some(i) => Val::Int(i),
+                    None => return err(crate::Error::DivideByZero(args[0].span, args[1].span)),
+                }
+            }
+            Op::DivFloat => {
+                let va = self.eval_ast(&args[0])?.unwrap_float();
+                let vb = self.eval_ast(&args[1])?.unwrap_float();
+                Val::Float(va / vb)
+            }
+            Op::DivDurationInt => {
+                let va = self.eval_ast(&args[0])?.unwrap_duration();
+                let vb = self.eval_ast(&args[1])?.unwrap_int();
+                match va.checked_div(vb) {
+                    Some(d) => Val::Duration(d),
+                    None if vb == 0 => {
+                        return err(crate::Error::DivideByZero(args[0].span, args[1].span))
                     }
-                };
+                    None => return err(crate::Error::DivOverflow(args[0].span, args[1].span)),
+                }
             }
-            Val::Int(f)
-        }
-        Op::Eq => {
-            let va = eval_ast(stack, funs, &args[0])?;
-            let vb = eval_ast(stack, funs, &args[1])?;
-            Val::Bool(va == vb)
-        }
-        Op::Ne => {
-            let va = eval_ast(stack, funs, &args[0])?;
-            let vb = eval_ast(stack, funs, &args[1])?;
-            Val::Bool(va != vb)
-        }
-        Op::LtInt => {
-            let va = eval_ast(stack, funs, &args[0])?.unwrap_int();
-            let vb = eval_ast(stack, funs, &args[1])?.unwrap_int();
-            Val::Bool(va < vb)
-        }
-        Op::LtFloat => {
-            let va = eval_ast(stack, funs, &args[0])?.unwrap_float();
-            let vb = eval_ast(stack, funs, &args[1])?.unwrap_float();
-            Val::Bool(va < vb)
-        }
-        Op::LeInt => {
-            let va = eval_ast(stack, funs, &args[0])?.unwrap_int();
-            let vb = eval_ast(stack, funs, &args[1])?.unwrap_int();
-            Val::Bool(va <= vb)
-        }
-        Op::LeFloat => {
-            let va = eval_ast(stack, funs, &args[0])?.unwrap_float();
-            let vb = eval_ast(stack, funs, &args[1])?.unwrap_float();
-            Val::Bool(va <= vb)
-        }
-        Op::GtInt => {
-            let va = eval_ast(stack, funs, &args[0])?.unwrap_int();
-            let vb = eval_ast(stack, funs, &args[1])?.unwrap_int();
-            Val::Bool(va > vb)
-        }
-        Op::GtFloat => {
-            let va = eval_ast(stack, funs, &args[0])?.unwrap_float();
-            let vb = eval_ast(stack, funs, &args[1])?.unwrap_float();
-            Val::Bool(va > vb)
-        }
-        Op::GeInt => {
-            let va = eval_ast(stack, funs, &args[0])?.unwrap_int();
-            let vb = eval_ast(stack, funs, &args[1])?.unwrap_int();
-            Val::Bool(va >= vb)
-        }
-        Op::GeFloat => {
-            let va = eval_ast(stack, funs, &args[0])?.unwrap_float();
-            let vb = eval_ast(stack, funs, &args[1])?.unwrap_float();
-            Val::Bool(va > vb)
-        }
-        Op::BwOrInt => {
-            let va = eval_ast(stack, funs, &args[0])?.unwrap_int();
-            let vb = eval_ast(stack, funs, &args[1])?.unwrap_int();
-            Val::Int(va | vb)
-        }
-        Op::BwOrBool => {
-            let va = eval_ast(stack, funs, &args[0])?.unwrap_bool();
-            let vb = eval_ast(stack, funs, &args[1])?.unwrap_bool();
-            Val::Bool(va | vb)
-        }
-        Op::XorInt => {
-            let va = eval_ast(stack, funs, &args[0])?.unwrap_int();
-            let vb = eval_ast(stack, funs, &args[1])?.unwrap_int();
-            Val::Int(va ^ vb)
-        }
-        Op::XorBool => {
-            let va = eval_ast(stack, funs, &args[0])?.unwrap_bool();
-            let vb = eval_ast(stack, funs, &args[1])?.unwrap_bool();
-            Val::Bool(va ^ vb)
-        }
-        Op::BwAndInt => {
-            let va = eval_ast(stack, funs, &args[0])?.unwrap_int();
-            let vb = eval_ast(stack, funs, &args[1])?.unwrap_int();
-            Val::Int(va & vb)
-        }
-        Op::BwAndBool => {
-            let va = eval_ast(stack, funs, &args[0])?.unwrap_bool();
-            let vb = eval_ast(stack, funs, &args[1])?.unwrap_bool();
-            Val::Bool(va & vb)
-        }
-        Op::ShlInt => {
-            let va = eval_ast(stack, funs, &args[0])?.unwrap_int();
-            let vb = eval_ast(stack, funs, &args[1])?.unwrap_int();
-            Val::Int(va << vb)
-        }
-        Op::ShrInt => {
-            let va = eval_ast(stack, funs, &args[0])?.unwrap_int();
-            let vb = eval_ast(stack, funs, &args[1])?.unwrap_int();
-            Val::Int(va >> vb)
-        }
-        Op::Or => {
-            let va = eval_ast(stack, funs, &args[0])?.unwrap_bool();
-            let vb = eval_ast(stack, funs, &args[1])?.unwrap_bool();
-            Val::Bool(va || vb)
-        }
-        Op::And => {
-            let va = eval_ast(stack, funs, &args[0])?.unwrap_bool();
-            let vb = eval_ast(stack, funs, &args[1])?.unwrap_bool();
-            Val::Bool(va && vb)
-        }
-    };
-
-    Ok(val)
-}
+            Op::DivDuration => {
+                let va = self.eval_ast(&args[0])?.unwrap_duration();
+                let vb = self.eval_ast(&args[1])?.unwrap_duration();
+                match va.checked_div(vb) {
+                    Some(i) => Val::Int(i),
+                    None if vb == 0 => {
+                        return err(crate::Error::DivideByZero(args[0].span, args[1].span))
+                    }
+                    None => return err(crate::Error::DivOverflow(args[0].span, args[1].span)),
+                }
+            }
+            Op::PowInt => {
+                let a = &args[0];
+                let b = &args[1];
+                let base = self.eval_ast(a)?.unwrap_int();
+                let exp = self.eval_ast(b)?.unwrap_int();
+                if exp < 0 {
+                    return err(crate::Error::NegativeIntPow(a.span, b.span));
+                }
+                if exp > u32::MAX as i128 {
+                    return err(crate::Error::PowOverflow(a.span, b.span));
+                }
+                match base.checked_pow(exp as u32) {
+                    Some(i) => Val::Int(i),
+                    None => return err(crate::Error::PowOverflow(a.span, b.span)),
+                }
+            }
+            Op::PowFloat => {
+                let base = self.eval_ast(&args[0])?.unwrap_float();
+                let exp = self.eval_ast(&args[1])?.unwrap_float();
+                Val::Float(base.powf(exp))
+            }
+            Op::PowFloatInt => {
+                let a = &args[0];
+                let b = &args[1];
+                let base = self.eval_ast(a)?.unwrap_float();
+                let exp = self.eval_ast(b)?.unwrap_int();
+                if exp > i32::MAX as i128 {
+                    return err(crate::Error::PowOverflow(a.span, b.span));
+                }
+                Val::Float(base.powi(exp as i32))
+            }
+            Op::RemInt => {
+                let va = self.eval_ast(&args[0])?.unwrap_int();
+                let vb = self.eval_ast(&args[1])?.unwrap_int();
+                va.checked_rem(vb)
+                    .map(Val::Int)
+                    .ok_or(crate::Error::RemainderByZero(args[0].span, args[1].span))
+                    .map_err(EvalError::Error)?
+            }
+            Op::RemFloat => {
+                let va = self.eval_ast(&args[0])?.unwrap_float();
+                let vb = self.eval_ast(&args[1])?.unwrap_float();
+                Val::Float(va % vb)
+            }
+            Op::RemDuration => {
+                let va = self.eval_ast(&args[0])?.unwrap_duration();
+                let vb = self.eval_ast(&args[1])?.unwrap_duration();
+                match va.checked_rem(vb) {
+                    Some(d) => Val::Duration(d),
+                    None if vb == 0 => {
+                        return err(crate::Error::RemainderByZero(args[0].span, args[1].span))
+                    }
+                    None => return err(crate::Error::RemOverflow(args[0].span, args[1].span)),
+                }
+            }
+            Op::RemEuclidInt => {
+                let va = self.eval_ast(&args[0])?.unwrap_int();
+                let vb = self.eval_ast(&args[1])?.unwrap_int();
+                if vb == 0 {
+                    return err(crate::Error::RemainderByZero(args[0].span, args[1].span));
+                }
+                let r = va % vb;
+                if (r > 0 && vb < 0) || (r < 0 && vb > 0) {
+                    Val::Int(r + vb)
+                } else {
+                    Val::Int(r)
+                }
+            }
+            Op::FactorialInt => {
+                let va = self.eval_ast(&args[0])?.unwrap_int();
 
-fn eval_is(stack: &mut Stack, funs: &Funs, a: &Ast, t: DataType) -> EvalResult<Val> {
-    let va = eval_ast(stack, funs, a)?;
-    Ok(Val::Bool(va.data_type().is(t)))
-}
+                if va < 0 {
+                    return err(crate::Error::NegativeFactorial(ValSpan::new(
+                        Val::Int(va),
+                        args[0].span,
+                    )));
+                }
 
-fn eval_cast(stack: &mut Stack, funs: &Funs, a: &Ast, t: DataType) -> EvalResult<Val> {
-    fn cast_err(val: Val, data_type: DataType, span: Span) -> EvalResult<Val> {
-        err(crate::Error::CastFailed((val.data_type(), span), data_type))
+                let mut f: i128 = 1;
+                for i in 2..=va {
+                    f = match f.checked_mul(i) {
+                        Some(v) => v,
+                        None => {
+                            return err(crate::Error::FactorialOverflow(ValSpan::new(
+                                Val::Int(va),
+                                args[0].span,
+                            )))
+                        }
+                    };
+                }
+                Val::Int(f)
+            }
+            Op::Eq => {
+                let va = self.eval_ast(&args[0])?;
+                let vb = self.eval_ast(&args[1])?;
+                Val::Bool(va == vb)
+            }
+            Op::Ne => {
+                let va = self.eval_ast(&args[0])?;
+                let vb = self.eval_ast(&args[1])?;
+                Val::Bool(va != vb)
+            }
+            Op::LtInt => {
+                let va = self.eval_ast(&args[0])?.unwrap_int();
+                let vb = self.eval_ast(&args[1])?.unwrap_int();
+                Val::Bool(va < vb)
+            }
+            Op::LtFloat => {
+                let va = self.eval_ast(&args[0])?.unwrap_float();
+                let vb = self.eval_ast(&args[1])?.unwrap_float();
+                Val::Bool(va < vb)
+            }
+            Op::LtDuration => {
+                let va = self.eval_ast(&args[0])?.unwrap_duration();
+                let vb = self.eval_ast(&args[1])?.unwrap_duration();
+                Val::Bool(va < vb)
+            }
+            Op::LeInt => {
+                let va = self.eval_ast(&args[0])?.unwrap_int();
+                let vb = self.eval_ast(&args[1])?.unwrap_int();
+                Val::Bool(va <= vb)
+            }
+            Op::LeFloat => {
+                let va = self.eval_ast(&args[0])?.unwrap_float();
+                let vb = self.eval_ast(&args[1])?.unwrap_float();
+                Val::Bool(va <= vb)
+            }
+            Op::LeDuration => {
+                let va = self.eval_ast(&args[0])?.unwrap_duration();
+                let vb = self.eval_ast(&args[1])?.unwrap_duration();
+                Val::Bool(va <= vb)
+            }
+            Op::GtInt => {
+                let va = self.eval_ast(&args[0])?.unwrap_int();
+                let vb = self.eval_ast(&args[1])?.unwrap_int();
+                Val::Bool(va > vb)
+            }
+            Op::GtFloat => {
+                let va = self.eval_ast(&args[0])?.unwrap_float();
+                let vb = self.eval_ast(&args[1])?.unwrap_float();
+                Val::Bool(va > vb)
+            }
+            Op::GtDuration => {
+                let va = self.eval_ast(&args[0])?.unwrap_duration();
+                let vb = self.eval_ast(&args[1])?.unwrap_duration();
+                Val::Bool(va > vb)
+            }
+            Op::GeInt => {
+                let va = self.eval_ast(&args[0])?.unwrap_int();
+                let vb = self.eval_ast(&args[1])?.unwrap_int();
+                Val::Bool(va >= vb)
+            }
+            Op::GeFloat => {
+                let va = self.eval_ast(&args[0])?.unwrap_float();
+                let vb = self.eval_ast(&args[1])?.unwrap_float();
+                Val::Bool(va > vb)
+            }
+            Op::GeDuration => {
+                let va = self.eval_ast(&args[0])?.unwrap_duration();
+                let vb = self.eval_ast(&args[1])?.unwrap_duration();
+                Val::Bool(va >= vb)
+            }
+            Op::BwOrInt => {
+                let va = self.eval_ast(&args[0])?.unwrap_int();
+                let vb = self.eval_ast(&args[1])?.unwrap_int();
+                Val::Int(va | vb)
+            }
+            Op::BwOrBool => {
+                let va = self.eval_ast(&args[0])?.unwrap_bool();
+                let vb = self.eval_ast(&args[1])?.unwrap_bool();
+                Val::Bool(va | vb)
+            }
+            Op::XorInt => {
+                let va = self.eval_ast(&args[0])?.unwrap_int();
+                let vb = self.eval_ast(&args[1])?.unwrap_int();
+                Val::Int(va ^ vb)
+            }
+            Op::XorBool => {
+                let va = self.eval_ast(&args[0])?.unwrap_bool();
+                let vb = self.eval_ast(&args[1])?.unwrap_bool();
+                Val::Bool(va ^ vb)
+            }
+            Op::BwAndInt => {
+                let va = self.eval_ast(&args[0])?.unwrap_int();
+                let vb = self.eval_ast(&args[1])?.unwrap_int();
+                Val::Int(va & vb)
+            }
+            Op::BwAndBool => {
+                let va = self.eval_ast(&args[0])?.unwrap_bool();
+                let vb = self.eval_ast(&args[1])?.unwrap_bool();
+                Val::Bool(va & vb)
+            }
+            Op::ShlInt => {
+                let va = self.eval_ast(&args[0])?.unwrap_int();
+                let vb = self.eval_ast(&args[1])?.unwrap_int();
+                Val::Int(va << vb)
+            }
+            Op::ShrInt => {
+                let va = self.eval_ast(&args[0])?.unwrap_int();
+                let vb = self.eval_ast(&args[1])?.unwrap_int();
+                Val::Int(va >> vb)
+            }
+            Op::Or => {
+                let va = self.eval_ast(&args[0])?.unwrap_bool();
+                let vb = self.eval_ast(&args[1])?.unwrap_bool();
+                Val::Bool(va || vb)
+            }
+            Op::And => {
+                let va = self.eval_ast(&args[0])?.unwrap_bool();
+                let vb = self.eval_ast(&args[1])?.unwrap_bool();
+                Val::Bool(va && vb)
+            }
+        };
+
+        Ok(val)
     }
 
-    let va = eval_ast(stack, funs, a)?;
-    // This has to be kept in sync with the implementation in `cods/check/mod.rs`
-    let val = match t {
-        DataType::Int => Val::Int(match va {
-            Val::Int(i) => i,
-            Val::Float(f) => f as i128,
-            Val::Char(c) => c as i128,
-            v => return cast_err(v, t, a.span),
-        }),
-        DataType::Float => Val::Float(match va {
-            Val::Float(f) => f,
-            Val::Int(i) => i as f64,
-            v => return cast_err(v, t, a.span),
-        }),
-        DataType::Bool => Val::Bool(match va {
-            Val::Bool(b) => b,
-            Val::Int(i) => i != 0,
-            v => return cast_err(v, t, a.span),
-        }),
-        DataType::Char => Val::Char(match va {
-            Val::Char(c) => c,
-            Val::Int(i) => {
-                if i > char::MAX as i128 {
-                    return cast_err(va, t, a.span);
-                }
-                let Some(c) = char::from_u32(i as u32) else {
-                    return cast_err(va, t, a.span);
-                };
+    fn eval_is(&mut self, a: &Ast, t: DataType) -> EvalResult<Val> {
+        let va = self.eval_ast(a)?;
+        Ok(Val::Bool(va.data_type().is(t)))
+    }
 
-                c
-            }
-            v => return cast_err(v, t, a.span),
-        }),
-        DataType::Str => Val::Str(match va {
-            Val::Str(s) => s,
-            v => return cast_err(v, t, a.span),
-        }),
-        DataType::Range => Val::Range(match va {
-            Val::Range(r) => r,
-            v => return cast_err(v, t, a.span),
-        }),
-        DataType::Unit => match va {
-            Val::Unit => Val::Unit,
-            v => return cast_err(v, t, a.span),
-        },
-        DataType::Any => va,
-        DataType::Never => unreachable!("Never has no instances"),
-    };
-    Ok(val)
-}
+    fn eval_cast(&mut self, a: &Ast, t: DataType) -> EvalResult<Val> {
+        fn cast_err(val: Val, data_type: DataType, span: Span) -> EvalResult<Val> {
+            err(crate::Error::CastFailed((val.data_type(), span), data_type))
+        }
+
+        let va = self.eval_ast(a)?;
+        // This has to be kept in sync with the implementation in `cods/check/mod.rs`
+        let val = match t {
+            DataType::Int => Val::Int(match va {
+                Val::Int(i) => i,
+                Val::Float(f) => f as i128,
+                Val::Char(c) => c as i128,
+                Val::Duration(d) => d,
+                v => return cast_err(v, t, a.span),
+            }),
+            DataType::Float => Val::Float(match va {
+                Val::Float(f) => f,
+                Val::Int(i) => i as f64,
+                v => return cast_err(v, t, a.span),
+            }),
+            DataType::Bool => Val::Bool(match va {
+                Val::Bool(b) => b,
+                Val::Int(i) => i != 0,
+                v => return cast_err(v, t, a.span),
+            }),
+            DataType::Char => Val::Char(match va {
+                Val::Char(c) => c,
+                Val::Int(i) => {
+                    if i > char::MAX as i128 {
+                        return cast_err(va, t, a.span);
+                    }
+                    let Some(c) = char::from_u32(i as u32) else {
+                        return cast_err(va, t, a.span);
+                    };
+
+                    c
+                }
+                v => return cast_err(v, t, a.span),
+            }),
+            DataType::Str => Val::Str(match va {
+                Val::Str(s) => s,
+                v => return cast_err(v, t, a.span),
+            }),
+            DataType::Range => Val::Range(match va {
+                Val::Range(r) => r,
+                v => return cast_err(v, t, a.span),
+            }),
+            DataType::Duration => Val::Duration(match va {
+                Val::Duration(d) => d,
+                Val::Int(i) => i,
+                v => return cast_err(v, t, a.span),
+            }),
+            DataType::Unit => match va {
+                Val::Unit => Val::Unit,
+                v => return cast_err(v, t, a.span),
+            },
+            DataType::Any => va,
+            DataType::Never => unreachable!("Never has no instances"),
+        };
+        Ok(val)
+    }
 
-fn eval_if_expr(stack: &mut Stack, funs: &Funs, if_expr: &IfExpr) -> EvalResult<Val> {
-    for c in if_expr.cases.iter() {
-        if eval_ast(stack, funs, &c.cond)?.unwrap_bool() {
-            return eval_asts(stack, funs, &c.block);
+    fn eval_if_expr(&mut self, if_expr: &IfExpr, span: Span) -> EvalResult<Val> {
+        for (i, c) in if_expr.cases.iter().enumerate() {
+            if self.eval_ast(&c.cond)?.unwrap_bool() {
+                self.observe_branch(span, i);
+                return self.eval_asts(&c.block);
+            }
+        }
+
+        self.observe_branch(span, if_expr.cases.len());
+        match &if_expr.else_block {
+            Some(b) => self.eval_asts(b),
+            None => Ok(Val::Unit),
         }
     }
 
-    match &if_expr.else_block {
-        Some(b) => eval_asts(stack, funs, b),
-        None => Ok(Val::Unit),
+    fn eval_match_expr(&mut self, match_expr: &MatchExpr, span: Span) -> EvalResult<Val> {
+        let value = self.eval_ast(&match_expr.value)?;
+        for (i, a) in match_expr.arms.iter().enumerate() {
+            if self.eval_ast(&a.cond)? == value {
+                self.observe_branch(span, i);
+                return self.eval_ast(&a.expr);
+            }
+        }
+
+        self.observe_branch(span, match_expr.arms.len());
+        match &match_expr.default_arm {
+            Some(a) => self.eval_ast(a),
+            None => Ok(Val::Unit),
+        }
     }
-}
 
-fn eval_match_expr(stack: &mut Stack, funs: &Funs, match_expr: &MatchExpr) -> EvalResult<Val> {
-    let value = eval_ast(stack, funs, &match_expr.value)?;
-    for a in match_expr.arms.iter() {
-        if eval_ast(stack, funs, &a.cond)? == value {
-            return eval_ast(stack, funs, &a.expr);
+    fn eval_while_loop(&mut self, whl_loop: &WhileLoop) -> EvalResult<Val> {
+        while self.eval_ast(&whl_loop.cond)?.unwrap_bool() {
+            self.eval_asts(&whl_loop.block)?;
         }
+
+        Ok(Val::Unit)
     }
 
-    match &match_expr.default_arm {
-        Some(a) => eval_ast(stack, funs, a),
-        None => Ok(Val::Unit),
+    fn eval_for_loop(&mut self, for_loop: &ForLoop) -> EvalResult<Val> {
+        let iter = self.eval_ast(&for_loop.iter)?.unwrap_range();
+
+        for i in iter.iter() {
+            self.stack.set(&for_loop.var, Val::Int(i));
+            self.eval_asts(&for_loop.block)?;
+        }
+
+        Ok(Val::Unit)
     }
-}
 
-fn eval_while_loop(stack: &mut Stack, funs: &Funs, whl_loop: &WhileLoop) -> EvalResult<Val> {
-    while eval_ast(stack, funs, &whl_loop.cond)?.unwrap_bool() {
-        eval_asts(stack, funs, &whl_loop.block)?;
+    fn eval_var_assign(&mut self, var: &VarRef, expr: &Ast) -> EvalResult<Val> {
+        let val = self.eval_ast(expr)?;
+        self.stack.set(var, val);
+        Ok(Val::Unit)
     }
 
-    Ok(Val::Unit)
-}
+    fn eval_fun_call(&mut self, fun_ref: FunRef, args: &[Ast], span: Span) -> EvalResult<Val> {
+        let mut arg_vals = Vec::with_capacity(args.len());
+        for a in args.iter() {
+            let val = self.eval_ast(a)?;
+            arg_vals.push(val);
+        }
+
+        if let Some(c) = self.calls.last_mut() {
+            c.span = span;
+        }
+        let res = self.eval_fun(fun_ref, arg_vals, span);
+        if let Err(EvalError::Error(_)) = res {
+            let name = self.funs.get(fun_ref).name.clone();
+            self.env.trace.push(TraceFrame::new(name, span));
+        }
+        res
+    }
+
+    fn eval_fun(&mut self, fun_ref: FunRef, args: Vec<Val>, span: Span) -> EvalResult<Val> {
+        let fun = self.funs.get(fun_ref);
+        self.stack.push(fun.frame_size);
+        for (p, a) in fun.params.iter().zip(args) {
+            self.stack.set(&p.var, a);
+        }
+        self.calls.push(CallFrame::new(Some(fun_ref), span));
+        if let Some(observer) = self.env.observer.as_mut() {
+            observer.enter_fun(&EvalState::new(self.stack, self.funs, &self.calls));
+        }
 
-fn eval_for_loop(stack: &mut Stack, funs: &Funs, for_loop: &ForLoop) -> EvalResult<Val> {
-    let iter = eval_ast(stack, funs, &for_loop.iter)?.unwrap_range();
+        let val = match self.eval_asts(&fun.block) {
+            Err(EvalError::Return(v)) => Ok(v),
+            r => r,
+        };
 
-    for i in iter.iter() {
-        stack.set(&for_loop.var, Val::Int(i));
-        eval_asts(stack, funs, &for_loop.block)?;
+        if let Some(observer) = self.env.observer.as_mut() {
+            let state = EvalState::new(self.stack, self.funs, &self.calls);
+            observer.exit_fun(&state, val.as_ref().ok());
+        }
+        self.calls.pop();
+        self.stack.pop();
+        val
     }
 
-    Ok(Val::Unit)
-}
+    fn eval_host_fun_call(
+        &mut self,
+        fun_ref: HostFunRef,
+        args: &[Ast],
+        span: Span,
+    ) -> EvalResult<Val> {
+        let fun = self.funs.get_host(fun_ref);
+        let mut arg_vals = Vec::with_capacity(args.len());
+        for a in args.iter() {
+            let val = self.eval_ast(a)?;
+            arg_vals.push(val);
+        }
 
-fn eval_var_assign(stack: &mut Stack, funs: &Funs, var: &VarRef, expr: &Ast) -> EvalResult<Val> {
-    let val = eval_ast(stack, funs, expr)?;
-    stack.set(var, val);
-    Ok(Val::Unit)
-}
+        let val = match (fun.fun)(&arg_vals) {
+            Ok(v) => v,
+            Err(msg) => return err(crate::Error::HostFunFailed(msg, span)),
+        };
 
-fn eval_fun_call(stack: &mut Stack, funs: &Funs, fun_ref: FunRef, args: &[Ast]) -> EvalResult<Val> {
-    let fun = funs.get(fun_ref);
-    let mut arg_vals = Vec::with_capacity(fun.params.len());
-    for (p, a) in fun.params.iter().zip(args.iter()) {
-        let val = eval_ast(stack, funs, a)?;
-        arg_vals.push((p, val));
+        let return_type = fun.signature.return_type;
+        if val.data_type().is_not(return_type) {
+            return err(crate::Error::MismatchedType {
+                expected: return_type,
+                found: val.data_type(),
+                spans: vec![span],
+            });
+        }
+
+        Ok(val)
     }
 
-    stack.push(fun.frame_size);
-    for (p, a) in arg_vals {
-        stack.set(p, a);
+    fn eval_return(&mut self, val: &Ast) -> EvalResult<Val> {
+        let val = self.eval_ast(val)?;
+        Err(EvalError::Return(val))
     }
-    let val = match eval_asts(stack, funs, &fun.block) {
-        Err(EvalError::Return(v)) => Ok(v),
-        r => r,
-    };
-    stack.pop();
-    val
-}
 
-fn eval_return(stack: &mut Stack, funs: &Funs, val: &Ast) -> EvalResult<Val> {
-    let val = eval_ast(stack, funs, val)?;
-    Err(EvalError::Return(val))
-}
+    /// The optional message of a failed assertion, which is only evaluated on failure.
+    fn eval_assert_msg(&mut self, msg: Option<&Ast>) -> EvalResult<Option<String>> {
+        match msg {
+            Some(m) => Ok(Some(self.eval_ast(m)?.unwrap_str())),
+            None => Ok(None),
+        }
+    }
 
-fn eval_builtin_fun_call(
-    stack: &mut Stack,
-    funs: &Funs,
-    fun: BuiltinFunCall,
-    args: &[Ast],
-) -> EvalResult<Val> {
-    let val = match fun {
-        BuiltinFunCall::PowInt => {
-            let a = &args[0];
-            let b = &args[1];
-            let base = eval_ast(stack, funs, a)?.unwrap_int();
-            let exp = eval_ast(stack, funs, b)?.unwrap_int();
-            if exp < 0 {
-                return err(crate::Error::NegativeIntPow(a.span, b.span));
-            }
-            if exp > u32::MAX as i128 {
-                return err(crate::Error::PowOverflow(a.span, b.span));
-            }
-            match base.checked_pow(exp as u32) {
-                Some(i) => Val::Int(i),
-                None => return err(crate::Error::PowOverflow(a.span, b.span)),
-            }
-        }
-        BuiltinFunCall::PowFloat => {
-            let base = eval_ast(stack, funs, &args[0])?.unwrap_float();
-            let exp = eval_ast(stack, funs, &args[1])?.unwrap_float();
-            Val::Float(base.powf(exp))
-        }
-        BuiltinFunCall::PowFloatInt => {
-            let a = &args[0];
-            let b = &args[1];
-            let base = eval_ast(stack, funs, a)?.unwrap_float();
-            let exp = eval_ast(stack, funs, b)?.unwrap_int();
-            if exp > i32::MAX as i128 {
-                return err(crate::Error::PowOverflow(a.span, b.span));
-            }
-            Val::Float(base.powi(exp as i32))
-        }
-        BuiltinFunCall::Ln => {
-            let num = eval_ast(stack, funs, &args[0])?.unwrap_float();
-            Val::Float(num.ln())
-        }
-        BuiltinFunCall::Log => {
-            let base = eval_ast(stack, funs, &args[0])?.unwrap_float();
-            let num = eval_ast(stack, funs, &args[1])?.unwrap_float();
-            Val::Float(num.log(base))
-        }
-        BuiltinFunCall::Sqrt => {
-            let num = eval_ast(stack, funs, &args[0])?.unwrap_float();
-            Val::Float(num.sqrt())
-        }
-        BuiltinFunCall::Ncr => {
-            let n = eval_ast(stack, funs, &args[0])?.unwrap_int();
-            let mut r = eval_ast(stack, funs, &args[1])?.unwrap_int();
-            if r < 0 {
-                return err(crate::Error::NegativeNcr(ValSpan::new(
-                    Val::Int(r),
-                    args[1].span,
-                )));
-            }
-            if n < r {
-                return err(crate::Error::InvalidNcr(
-                    ValSpan::new(Val::Int(n), args[0].span),
-                    ValSpan::new(Val::Int(r), args[1].span),
-                ));
-            }
-
-            // symmetrical: nCr(9, 2) == nCr(9, 7)
-            if r > n - r {
-                r = n - r;
-            }
-
-            let mut val = 1;
-            for i in 1..=r {
-                val *= n - r + i;
-                val /= i;
-            }
-
-            Val::Int(val)
-        }
-        BuiltinFunCall::ToDeg => {
-            let num = eval_ast(stack, funs, &args[0])?.unwrap_float();
-            Val::Float(num.to_degrees())
-        }
-        BuiltinFunCall::ToRad => {
-            let num = eval_ast(stack, funs, &args[0])?.unwrap_float();
-            Val::Float(num.to_radians())
-        }
-        BuiltinFunCall::Sin => {
-            let num = eval_ast(stack, funs, &args[0])?.unwrap_float();
-            Val::Float(num.sin())
-        }
-        BuiltinFunCall::Cos => {
-            let num = eval_ast(stack, funs, &args[0])?.unwrap_float();
-            Val::Float(num.cos())
-        }
-        BuiltinFunCall::Tan => {
-            let num = eval_ast(stack, funs, &args[0])?.unwrap_float();
-            Val::Float(num.tan())
-        }
-        BuiltinFunCall::Sinh => {
-            let num = eval_ast(stack, funs, &args[0])?.unwrap_float();
-            Val::Float(num.sinh())
-        }
-        BuiltinFunCall::Cosh => {
-            let num = eval_ast(stack, funs, &args[0])?.unwrap_float();
-            Val::Float(num.cosh())
-        }
-        BuiltinFunCall::Tanh => {
-            let num = eval_ast(stack, funs, &args[0])?.unwrap_float();
-            Val::Float(num.tanh())
-        }
-        BuiltinFunCall::Asin => {
-            let num = eval_ast(stack, funs, &args[0])?.unwrap_float();
-            Val::Float(num.asin())
-        }
-        BuiltinFunCall::Acos => {
-            let num = eval_ast(stack, funs, &args[0])?.unwrap_float();
-            Val::Float(num.acos())
-        }
-        BuiltinFunCall::Atan => {
-            let num = eval_ast(stack, funs, &args[0])?.unwrap_float();
-            Val::Float(num.atan())
-        }
-        BuiltinFunCall::Asinh => {
-            let num = eval_ast(stack, funs, &args[0])?.unwrap_float();
-            Val::Float(num.asinh())
-        }
-        BuiltinFunCall::Acosh => {
-            let num = eval_ast(stack, funs, &args[0])?.unwrap_float();
-            Val::Float(num.acosh())
-        }
-        BuiltinFunCall::Atanh => {
-            let num = eval_ast(stack, funs, &args[0])?.unwrap_float();
-            Val::Float(num.atanh())
-        }
-        BuiltinFunCall::Gcd => {
-            let mut a = eval_ast(stack, funs, &args[0])?.unwrap_int();
-            let mut b = eval_ast(stack, funs, &args[1])?.unwrap_int();
-            while b != 0 {
-                let t = b;
-                b = a % b;
-                a = t;
-            }
-            Val::Int(a)
-        }
-        BuiltinFunCall::MinInt => Val::Int(fold_eval_int(stack, funs, args, i128::min)?),
-        BuiltinFunCall::MinFloat => Val::Float(fold_eval_float(stack, funs, args, f64::min)?),
-        BuiltinFunCall::MaxInt => Val::Int(fold_eval_int(stack, funs, args, i128::max)?),
-        BuiltinFunCall::MaxFloat => Val::Float(fold_eval_float(stack, funs, args, f64::max)?),
-        BuiltinFunCall::ClampInt => {
-            let num = eval_ast(stack, funs, &args[0])?.unwrap_int();
-            let min = eval_ast(stack, funs, &args[1])?.unwrap_int();
-            let max = eval_ast(stack, funs, &args[2])?.unwrap_int();
-            if min > max {
-                return err(crate::Error::InvalidClampBounds(
-                    ValSpan::new(Val::Int(min), args[1].span),
-                    ValSpan::new(Val::Int(max), args[2].span),
-                ));
-            }
-            Val::Int(num.clamp(min, max))
-        }
-        BuiltinFunCall::ClampFloat => {
-            let num = eval_ast(stack, funs, &args[0])?.unwrap_float();
-            let min = eval_ast(stack, funs, &args[1])?.unwrap_float();
-            let max = eval_ast(stack, funs, &args[2])?.unwrap_float();
-            // floating point weirdness, negated assertion of stdlib
-            #[allow(clippy::neg_cmp_op_on_partial_ord)]
-            if !(min <= max) {
-                return err(crate::Error::InvalidClampBounds(
-                    ValSpan::new(Val::Float(min), args[1].span),
-                    ValSpan::new(Val::Float(max), args[2].span),
-                ));
-            }
-            Val::Float(num.clamp(min, max))
-        }
-        BuiltinFunCall::AbsInt => {
-            let num = eval_ast(stack, funs, &args[0])?.unwrap_int();
-            Val::Int(num.abs())
-        }
-        BuiltinFunCall::AbsFloat => {
-            let num = eval_ast(stack, funs, &args[0])?.unwrap_float();
-            Val::Float(num.abs())
-        }
-        BuiltinFunCall::Round => {
-            let num = eval_ast(stack, funs, &args[0])?.unwrap_float();
-            Val::Float(num.round())
-        }
-        BuiltinFunCall::Print => {
-            eval_print(stack, funs, args)?;
-            Val::Unit
-        }
-        BuiltinFunCall::Println => {
-            eval_print(stack, funs, args)?;
-            println!();
-            Val::Unit
-        }
-        BuiltinFunCall::Flush => {
-            std::io::stdout().flush().unwrap();
-            Val::Unit
-        }
-        BuiltinFunCall::Assert => {
-            let va = eval_ast(stack, funs, &args[0])?.unwrap_bool();
-            if !va {
-                return err(crate::Error::AssertFailed(args[0].span));
-            }
-            Val::Unit
-        }
-        BuiltinFunCall::AssertEq => {
-            let a = eval_ast(stack, funs, &args[0])?;
-            let b = eval_ast(stack, funs, &args[1])?;
-            if a != b {
-                return err(crate::Error::AssertEqFailed(
-                    ValSpan::new(a, args[0].span),
-                    ValSpan::new(b, args[1].span),
-                ));
-            }
-            Val::Unit
-        }
-        BuiltinFunCall::Sleep => {
-            const NANOS_PER_SECOND: i128 = 1_000_000_000;
-            let nanos = eval_ast(stack, funs, &args[0])?.unwrap_int();
-            if nanos < 0 {
-                return err(crate::Error::NegativeSleepDuration(ValSpan::new(
-                    Val::Int(nanos),
-                    args[0].span,
-                )));
-            }
-
-            let secs = (nanos / NANOS_PER_SECOND) as u64;
-            let subsec_nanos = (nanos % NANOS_PER_SECOND) as u32;
-            std::thread::sleep(Duration::new(secs, subsec_nanos));
-            Val::Unit
-        }
-    };
-    Ok(val)
-}
+    fn eval_builtin_fun_call(
+        &mut self,
+        fun: BuiltinFunCall,
+        args: &[Ast],
+        span: Span,
+    ) -> EvalResult<Val> {
+        let val = match fun {
+            BuiltinFunCall::PowInt => {
+                let a = &args[0];
+                let b = &args[1];
+                let base = self.eval_ast(a)?.unwrap_int();
+                let exp = self.eval_ast(b)?.unwrap_int();
+                if exp < 0 {
+                    return err(crate::Error::NegativeIntPow(a.span, b.span));
+                }
+                if exp > u32::MAX as i128 {
+                    return err(crate::Error::PowOverflow(a.span, b.span));
+                }
+                match base.checked_pow(exp as u32) {
+                    Some(i) => Val::Int(i),
+                    None => return err(crate::Error::PowOverflow(a.span, b.span)),
+                }
+            }
+            BuiltinFunCall::PowFloat => {
+                let base = self.eval_ast(&args[0])?.unwrap_float();
+                let exp = self.eval_ast(&args[1])?.unwrap_float();
+                Val::Float(base.powf(exp))
+            }
+            BuiltinFunCall::PowFloatInt => {
+                let a = &args[0];
+                let b = &args[1];
+                let base = self.eval_ast(a)?.unwrap_float();
+                let exp = self.eval_ast(b)?.unwrap_int();
+                if exp > i32::MAX as i128 {
+                    return err(crate::Error::PowOverflow(a.span, b.span));
+                }
+                Val::Float(base.powi(exp as i32))
+            }
+            BuiltinFunCall::Ln => {
+                let num = self.eval_ast(&args[0])?.unwrap_float();
+                Val::Float(num.ln())
+            }
+            BuiltinFunCall::Log => {
+                let base = self.eval_ast(&args[0])?.unwrap_float();
+                let num = self.eval_ast(&args[1])?.unwrap_float();
+                Val::Float(num.log(base))
+            }
+            BuiltinFunCall::Sqrt => {
+                let num = self.eval_ast(&args[0])?.unwrap_float();
+                Val::Float(num.sqrt())
+            }
+            BuiltinFunCall::Ncr => {
+                let n = self.eval_ast(&args[0])?.unwrap_int();
+                let mut r = self.eval_ast(&args[1])?.unwrap_int();
+                if r < 0 {
+                    return err(crate::Error::NegativeNcr(ValSpan::new(
+                        Val::Int(r),
+                        args[1].span,
+                    )));
+                }
+                if n < r {
+                    return err(crate::Error::InvalidNcr(
+                        ValSpan::new(Val::Int(n), args[0].span),
+                        ValSpan::new(Val::Int(r), args[1].span),
+                    ));
+                }
 
-fn eval_print(stack: &mut Stack, funs: &Funs, args: &[Ast]) -> EvalResult<()> {
-    if let Some((first, others)) = args.split_first() {
-        let f = eval_ast(stack, funs, first)?;
-        print!("{}", f); // TODO: Evaluator struct with stdio
+                // symmetrical: nCr(9, 2) == nCr(9, 7)
+                if r > n - r {
+                    r = n - r;
+                }
 
-        for a in others {
-            let v = eval_ast(stack, funs, a)?;
-            print!(" {}", v);
+                let mut val = 1;
+                for i in 1..=r {
+                    val *= n - r + i;
+                    val /= i;
+                }
+
+                Val::Int(val)
+            }
+            BuiltinFunCall::ToDeg => {
+                let num = self.eval_ast(&args[0])?.unwrap_float();
+                Val::Float(num.to_degrees())
+            }
+            BuiltinFunCall::ToRad => {
+                let num = self.eval_ast(&args[0])?.unwrap_float();
+                Val::Float(num.to_radians())
+            }
+            BuiltinFunCall::Sin => {
+                let num = self.eval_ast(&args[0])?.unwrap_float();
+                Val::Float(num.sin())
+            }
+            BuiltinFunCall::Cos => {
+                let num = self.eval_ast(&args[0])?.unwrap_float();
+                Val::Float(num.cos())
+            }
+            BuiltinFunCall::Tan => {
+                let num = self.eval_ast(&args[0])?.unwrap_float();
+                Val::Float(num.tan())
+            }
+            BuiltinFunCall::Sinh => {
+                let num = self.eval_ast(&args[0])?.unwrap_float();
+                Val::Float(num.sinh())
+            }
+            BuiltinFunCall::Cosh => {
+                let num = self.eval_ast(&args[0])?.unwrap_float();
+                Val::Float(num.cosh())
+            }
+            BuiltinFunCall::Tanh => {
+                let num = self.eval_ast(&args[0])?.unwrap_float();
+                Val::Float(num.tanh())
+            }
+            BuiltinFunCall::Asin => {
+                let num = self.eval_ast(&args[0])?.unwrap_float();
+                Val::Float(num.asin())
+            }
+            BuiltinFunCall::Acos => {
+                let num = self.eval_ast(&args[0])?.unwrap_float();
+                Val::Float(num.acos())
+            }
+            BuiltinFunCall::Atan => {
+                let num = self.eval_ast(&args[0])?.unwrap_float();
+                Val::Float(num.atan())
+            }
+            BuiltinFunCall::Asinh => {
+                let num = self.eval_ast(&args[0])?.unwrap_float();
+                Val::Float(num.asinh())
+            }
+            BuiltinFunCall::Acosh => {
+                let num = self.eval_ast(&args[0])?.unwrap_float();
+                Val::Float(num.acosh())
+            }
+            BuiltinFunCall::Atanh => {
+                let num = self.eval_ast(&args[0])?.unwrap_float();
+                Val::Float(num.atanh())
+            }
+            BuiltinFunCall::Gcd => {
+                let mut a = self.eval_ast(&args[0])?.unwrap_int();
+                let mut b = self.eval_ast(&args[1])?.unwrap_int();
+                while b != 0 {
+                    let t = b;
+                    b = a % b;
+                    a = t;
+                }
+                Val::Int(a)
+            }
+            BuiltinFunCall::MinInt => Val::Int(self.fold_eval_int(args, i128::min)?),
+            BuiltinFunCall::MinFloat => Val::Float(self.fold_eval_float(args, f64::min)?),
+            BuiltinFunCall::MaxInt => Val::Int(self.fold_eval_int(args, i128::max)?),
+            BuiltinFunCall::MaxFloat => Val::Float(self.fold_eval_float(args, f64::max)?),
+            BuiltinFunCall::ClampInt => {
+                let num = self.eval_ast(&args[0])?.unwrap_int();
+                let min = self.eval_ast(&args[1])?.unwrap_int();
+                let max = self.eval_ast(&args[2])?.unwrap_int();
+                if min > max {
+                    return err(crate::Error::InvalidClampBounds(
+                        ValSpan::new(Val::Int(min), args[1].span),
+                        ValSpan::new(Val::Int(max), args[2].span),
+                    ));
+                }
+                Val::Int(num.clamp(min, max))
+            }
+            BuiltinFunCall::ClampFloat => {
+                let num = self.eval_ast(&args[0])?.unwrap_float();
+                let min = self.eval_ast(&args[1])?.unwrap_float();
+                let max = self.eval_ast(&args[2])?.unwrap_float();
+                // floating point weirdness, negated assertion of stdlib
+                #[allow(clippy::neg_cmp_op_on_partial_ord)]
+                if !(min <= max) {
+                    return err(crate::Error::InvalidClampBounds(
+                        ValSpan::new(Val::Float(min), args[1].span),
+                        ValSpan::new(Val::Float(max), args[2].span),
+                    ));
+                }
+                Val::Float(num.clamp(min, max))
+            }
+            BuiltinFunCall::AbsInt => {
+                let num = self.eval_ast(&args[0])?.unwrap_int();
+                Val::Int(num.abs())
+            }
+            BuiltinFunCall::AbsFloat => {
+                let num = self.eval_ast(&args[0])?.unwrap_float();
+                Val::Float(num.abs())
+            }
+            BuiltinFunCall::Round => {
+                let num = self.eval_ast(&args[0])?.unwrap_float();
+                Val::Float(num.round())
+            }
+            BuiltinFunCall::Print => {
+                self.eval_print(args, span)?;
+                Val::Unit
+            }
+            BuiltinFunCall::Println => {
+                self.eval_print(args, span)?;
+                self.write_stdout(format_args!("\n"), span)?;
+                Val::Unit
+            }
+            BuiltinFunCall::Format => Val::Str(self.eval_format(args, span)?),
+            BuiltinFunCall::Printf => {
+                let str = self.eval_format(args, span)?;
+                self.write_stdout(format_args!("{str}"), span)?;
+                Val::Unit
+            }
+            BuiltinFunCall::Flush => {
+                if let Err(e) = self.env.io.stdout.flush() {
+                    return err(crate::Error::Io(e.to_string(), span));
+                }
+                Val::Unit
+            }
+            BuiltinFunCall::ReadLine => {
+                let mut line = self.read_line(span)?;
+                if line.ends_with('\n') {
+                    line.pop();
+                    if line.ends_with('\r') {
+                        line.pop();
+                    }
+                }
+                Val::Str(line)
+            }
+            BuiltinFunCall::ReadAll => {
+                let mut buf = String::new();
+                if let Err(e) = self.env.io.stdin.read_to_string(&mut buf) {
+                    return err(crate::Error::Io(e.to_string(), span));
+                }
+                Val::Str(buf)
+            }
+            BuiltinFunCall::ReadInt => {
+                let line = self.read_line(span)?;
+                match line.trim().parse::<i128>() {
+                    Ok(i) => Val::Int(i),
+                    Err(_) => {
+                        let input = line.trim().to_owned();
+                        return err(crate::Error::InvalidInput(input, DataType::Int, span));
+                    }
+                }
+            }
+            BuiltinFunCall::ReadFloat => {
+                let line = self.read_line(span)?;
+                match line.trim().parse::<f64>() {
+                    Ok(f) => Val::Float(f),
+                    Err(_) => {
+                        let input = line.trim().to_owned();
+                        return err(crate::Error::InvalidInput(input, DataType::Float, span));
+                    }
+                }
+            }
+            BuiltinFunCall::ReadFile => {
+                let path = self.eval_path(&args[0], Access::Read)?;
+                match std::fs::read_to_string(&path) {
+                    Ok(s) => Val::Str(s),
+                    Err(e) => return err(io_error(&path, e, span)),
+                }
+            }
+            BuiltinFunCall::WriteFile => {
+                let path = self.eval_path(&args[0], Access::Write)?;
+                let content = self.eval_ast(&args[1])?.unwrap_str();
+                if let Err(e) = std::fs::write(&path, content) {
+                    return err(io_error(&path, e, span));
+                }
+                Val::Unit
+            }
+            BuiltinFunCall::AppendFile => {
+                let path = self.eval_path(&args[0], Access::Write)?;
+                let content = self.eval_ast(&args[1])?.unwrap_str();
+                let res = std::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&path)
+                    .and_then(|mut f| f.write_all(content.as_bytes()));
+                if let Err(e) = res {
+                    return err(io_error(&path, e, span));
+                }
+                Val::Unit
+            }
+            BuiltinFunCall::Exists => {
+                let path = self.eval_path(&args[0], Access::Read)?;
+                Val::Bool(path.exists())
+            }
+            BuiltinFunCall::ListDir => {
+                let path = self.eval_path(&args[0], Access::Read)?;
+                let entries = std::fs::read_dir(&path).and_then(|entries| {
+                    entries
+                        .map(|e| e.map(|e| e.file_name().to_string_lossy().into_owned()))
+                        .collect::<std::io::Result<Vec<_>>>()
+                });
+                match entries {
+                    Ok(mut names) => {
+                        names.sort();
+                        Val::Str(names.join("\n"))
+                    }
+                    Err(e) => return err(io_error(&path, e, span)),
+                }
+            }
+            BuiltinFunCall::ArgCount => Val::Int(self.env.args.len() as i128),
+            BuiltinFunCall::Arg => {
+                let i = self.eval_ast(&args[0])?.unwrap_int();
+                match usize::try_from(i).ok().and_then(|i| self.env.args.get(i)) {
+                    Some(a) => Val::Str(a.clone()),
+                    None => {
+                        let len = self.env.args.len();
+                        let index = ValSpan::new(Val::Int(i), args[0].span);
+                        return err(crate::Error::ArgOutOfBounds(index, len));
+                    }
+                }
+            }
+            BuiltinFunCall::Env => {
+                let name = self.eval_ast(&args[0])?.unwrap_str();
+                if !self.env.permissions.env {
+                    return err(crate::Error::PermissionDenied(
+                        name,
+                        Access::Env,
+                        args[0].span,
+                    ));
+                }
+                match std::env::var(&name) {
+                    Ok(v) => Val::Str(v),
+                    Err(VarError::NotPresent) => {
+                        return err(crate::Error::EnvVarNotPresent(name, args[0].span));
+                    }
+                    Err(VarError::NotUnicode(_)) => {
+                        return err(crate::Error::EnvVarNotUnicode(name, args[0].span));
+                    }
+                }
+            }
+            BuiltinFunCall::Exit => {
+                let code = self.eval_ast(&args[0])?.unwrap_int();
+                return match u8::try_from(code) {
+                    Ok(c) => Err(EvalError::Exit(c)),
+                    Err(_) => err(crate::Error::InvalidExitCode(ValSpan::new(
+                        Val::Int(code),
+                        args[0].span,
+                    ))),
+                };
+            }
+            BuiltinFunCall::Assert => {
+                let va = self.eval_ast(&args[0])?.unwrap_bool();
+                if !va {
+                    let msg = self.eval_assert_msg(args.get(1))?;
+                    return err(crate::Error::AssertFailed(msg, args[0].span));
+                }
+                Val::Unit
+            }
+            BuiltinFunCall::AssertEq | BuiltinFunCall::AssertNe => {
+                let a = self.eval_ast(&args[0])?;
+                let b = self.eval_ast(&args[1])?;
+                let eq = fun == BuiltinFunCall::AssertEq;
+                if (a == b) != eq {
+                    let msg = self.eval_assert_msg(args.get(2))?;
+                    let a = ValSpan::new(a, args[0].span);
+                    let b = ValSpan::new(b, args[1].span);
+                    return match eq {
+                        true => err(crate::Error::AssertEqFailed(a, b, msg)),
+                        false => err(crate::Error::AssertNeFailed(a, b, msg)),
+                    };
+                }
+                Val::Unit
+            }
+            BuiltinFunCall::AssertApprox => {
+                let a = self.eval_ast(&args[0])?.unwrap_float();
+                let b = self.eval_ast(&args[1])?.unwrap_float();
+                let eps = self.eval_ast(&args[2])?.unwrap_float();
+                if eps.is_nan() || eps < 0.0 {
+                    return err(crate::Error::InvalidTolerance(ValSpan::new(
+                        Val::Float(eps),
+                        args[2].span,
+                    )));
+                }
+                // also fails if any value is NaN
+                let close = (a - b).abs() <= eps;
+                if !close {
+                    let msg = self.eval_assert_msg(args.get(3))?;
+                    return err(crate::Error::AssertApproxFailed {
+                        a: ValSpan::new(Val::Float(a), args[0].span),
+                        b: ValSpan::new(Val::Float(b), args[1].span),
+                        eps,
+                        msg,
+                    });
+                }
+                Val::Unit
+            }
+            BuiltinFunCall::Panic => {
+                let msg = self.eval_ast(&args[0])?.unwrap_str();
+                return err(crate::Error::Panic(msg, span));
+            }
+            BuiltinFunCall::Now => Val::Int(self.env.clock.now()),
+            BuiltinFunCall::Monotonic => Val::Duration(self.env.clock.monotonic()),
+            BuiltinFunCall::Sleep => {
+                let nanos = self.eval_ast(&args[0])?.unwrap_duration();
+                if nanos < 0 {
+                    return err(crate::Error::NegativeSleepDuration(ValSpan::new(
+                        Val::Duration(nanos),
+                        args[0].span,
+                    )));
+                }
+
+                self.env.clock.sleep(duration_from_nanos(nanos));
+                Val::Unit
+            }
+            BuiltinFunCall::Random => Val::Float(self.env.rng.next_f64()),
+            BuiltinFunCall::RandomInt => {
+                let lo = self.eval_ast(&args[0])?.unwrap_int();
+                let hi = self.eval_ast(&args[1])?.unwrap_int();
+                if lo > hi {
+                    let range = Val::Range(Range::Inclusive(lo, hi));
+                    let span = Span::across(args[0].span, args[1].span);
+                    return err(crate::Error::EmptyRandomRange(ValSpan::new(range, span)));
+                }
+                Val::Int(self.env.rng.next_int(lo, hi))
+            }
+            BuiltinFunCall::RandomRange => {
+                let range = self.eval_ast(&args[0])?.unwrap_range();
+                let bounds = match range {
+                    Range::Exclusive(a, b) if a < b => Some((a, b - 1)),
+                    Range::Inclusive(a, b) if a <= b => Some((a, b)),
+                    _ => None,
+                };
+                let Some((lo, hi)) = bounds else {
+                    let range = ValSpan::new(Val::Range(range), args[0].span);
+                    return err(crate::Error::EmptyRandomRange(range));
+                };
+                Val::Int(self.env.rng.next_int(lo, hi))
+            }
+            BuiltinFunCall::Seed => {
+                let seed = self.eval_ast(&args[0])?.unwrap_int();
+                self.env.rng.seed(seed as u64 ^ (seed >> 64) as u64);
+                Val::Unit
+            }
+        };
+        Ok(val)
+    }
+
+    fn eval_print(&mut self, args: &[Ast], span: Span) -> EvalResult<()> {
+        if let Some((first, others)) = args.split_first() {
+            let f = self.eval_ast(first)?;
+            self.write_stdout(format_args!("{f}"), span)?;
+
+            for a in others {
+                let v = self.eval_ast(a)?;
+                self.write_stdout(format_args!(" {v}"), span)?;
+            }
         }
+
+        Ok(())
     }
 
-    Ok(())
-}
+    fn eval_format(&mut self, args: &[Ast], span: Span) -> EvalResult<String> {
+        let fmt = self.eval_ast(&args[0])?.unwrap_str();
+        let format = match Format::parse(&fmt) {
+            Ok(f) => f,
+            Err(e) => return err(crate::Error::InvalidFormatString(e, args[0].span)),
+        };
+
+        let mut vals = Vec::with_capacity(args.len() - 1);
+        for a in &args[1..] {
+            vals.push(self.eval_ast(a)?);
+        }
+        let types = (vals.iter().zip(&args[1..]))
+            .map(|(v, a)| (v.data_type(), a.span))
+            .collect::<Vec<_>>();
+        format.check_args(&types, span).map_err(EvalError::Error)?;
 
-fn eval_spill(stack: &mut Stack, vars: &[(String, VarRef)]) -> EvalResult<Val> {
-    for (n, v) in vars {
-        println!("{n} = {}", stack.get(v));
+        let mut str = String::new();
+        format.write(&mut str, &vals);
+        Ok(str)
     }
-    Ok(Val::Unit)
-}
 
-fn fold_eval_int(
-    stack: &mut Stack,
-    funs: &Funs,
-    args: &[Ast],
-    fold: fn(i128, i128) -> i128,
-) -> EvalResult<i128> {
-    let mut current = eval_ast(stack, funs, &args[0])?.unwrap_int();
-    for a in &args[1..] {
-        let val = eval_ast(stack, funs, a)?.unwrap_int();
-        current = fold(current, val);
+    fn eval_spill(&mut self, vars: &[(String, VarRef)], span: Span) -> EvalResult<Val> {
+        for (n, v) in vars {
+            let val = self.stack.get(v);
+            self.write_stdout(format_args!("{n} = {val}\n"), span)?;
+        }
+        Ok(Val::Unit)
     }
-    Ok(current)
-}
 
-fn fold_eval_float(
-    stack: &mut Stack,
-    funs: &Funs,
-    args: &[Ast],
-    fold: fn(f64, f64) -> f64,
-) -> EvalResult<f64> {
-    let mut current = eval_ast(stack, funs, &args[0])?.unwrap_float();
-    for a in &args[1..] {
-        let val = eval_ast(stack, funs, a)?.unwrap_float();
-        current = fold(current, val);
+    /// Evaluate a path argument and make sure the access is permitted.
+    fn eval_path(&mut self, arg: &Ast, access: Access) -> EvalResult<PathBuf> {
+        let path = PathBuf::from(self.eval_ast(arg)?.unwrap_str());
+        if !self.env.permissions.is_allowed(access, &path) {
+            let path = path.to_string_lossy().into_owned();
+            return err(crate::Error::PermissionDenied(path, access, arg.span));
+        }
+        Ok(path)
     }
-    Ok(current)
+
+    fn read_line(&mut self, span: Span) -> EvalResult<String> {
+        let mut line = String::new();
+        match self.env.io.stdin.read_line(&mut line) {
+            Ok(_) => Ok(line),
+            Err(e) => err(crate::Error::Io(e.to_string(), span)),
+        }
+    }
+
+    fn write_stdout(&mut self, args: fmt::Arguments<'_>, span: Span) -> EvalResult<()> {
+        match self.env.io.stdout.write_fmt(args) {
+            Ok(()) => Ok(()),
+            Err(e) => err(crate::Error::Io(e.to_string(), span)),
+        }
+    }
+
+    fn fold_eval_int(&mut self, args: &[Ast], fold: fn(i128, i128) -> i128) -> EvalResult<i128> {
+        let mut current = self.eval_ast(&args[0])?.unwrap_int();
+        for a in &args[1..] {
+            let val = self.eval_ast(a)?.unwrap_int();
+            current = fold(current, val);
+        }
+        Ok(current)
+    }
+
+    fn fold_eval_float(&mut self, args: &[Ast], fold: fn(f64, f64) -> f64) -> EvalResult<f64> {
+        let mut current = self.eval_ast(&args[0])?.unwrap_float();
+        for a in &args[1..] {
+            let val = self.eval_ast(a)?.unwrap_float();
+            current = fold(current, val);
+        }
+        Ok(current)
+    }
+}
+
+/// Convert a non-negative amount of nanoseconds into a [`Duration`], saturating on overflow.
+fn duration_from_nanos(nanos: i128) -> Duration {
+    const NANOS_PER_SECOND: i128 = 1_000_000_000;
+    let secs = u64::try_from(nanos / NANOS_PER_SECOND).unwrap_or(u64::MAX);
+    let subsec_nanos = (nanos % NANOS_PER_SECOND) as u32;
+    Duration::new(secs, subsec_nanos)
+}
+
+fn io_error(path: &Path, error: std::io::Error, span: Span) -> crate::Error {
+    crate::Error::Io(format!("`{}`: {error}", path.display()), span)
 }
 
 #[inline(always)]
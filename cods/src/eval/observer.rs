@@ -0,0 +1,172 @@
+use std::cell::RefCell;
+use std::io::Write;
+use std::ops::ControlFlow;
+use std::rc::Rc;
+
+use crate::{Ast, FunRef, Funs, Span, Stack, Val};
+
+/// Hooks that are called while a program is evaluated, which allow debuggers and similar
+/// tools to follow along and inspect the state of the program.
+pub trait Observer {
+    /// Called before each statement of a block is evaluated, except for ones that don't do
+    /// anything like function definitions. Breaking stops the evaluation as if the script
+    /// called `exit` with the given code.
+    fn statement(&mut self, _state: &EvalState<'_>, _ast: &Ast) -> ControlFlow<u8> {
+        ControlFlow::Continue(())
+    }
+
+    /// Called after the frame of a function has been pushed and its parameters are set.
+    fn enter_fun(&mut self, _state: &EvalState<'_>) {}
+
+    /// Called after a function has been evaluated, before its frame is popped. `ret` is the
+    /// returned value, or `None` if evaluation was aborted by an error or `exit`.
+    fn exit_fun(&mut self, _state: &EvalState<'_>, _ret: Option<&Val>) {}
+
+    /// Called when a branch of the `if` or `match` expression at `span` is taken. `index` is
+    /// the index of the case or arm, the `else` or default branch comes after the last one,
+    /// even if it is omitted.
+    fn branch(&mut self, _state: &EvalState<'_>, _span: Span, _index: usize) {}
+}
+
+impl<O: Observer> Observer for &mut O {
+    fn statement(&mut self, state: &EvalState<'_>, ast: &Ast) -> ControlFlow<u8> {
+        (**self).statement(state, ast)
+    }
+
+    fn enter_fun(&mut self, state: &EvalState<'_>) {
+        (**self).enter_fun(state)
+    }
+
+    fn exit_fun(&mut self, state: &EvalState<'_>, ret: Option<&Val>) {
+        (**self).exit_fun(state, ret)
+    }
+
+    fn branch(&mut self, state: &EvalState<'_>, span: Span, index: usize) {
+        (**self).branch(state, span, index)
+    }
+}
+
+/// Allows reading the collected data after the evaluation has finished.
+impl<O: Observer> Observer for Rc<RefCell<O>> {
+    fn statement(&mut self, state: &EvalState<'_>, ast: &Ast) -> ControlFlow<u8> {
+        self.borrow_mut().statement(state, ast)
+    }
+
+    fn enter_fun(&mut self, state: &EvalState<'_>) {
+        self.borrow_mut().enter_fun(state)
+    }
+
+    fn exit_fun(&mut self, state: &EvalState<'_>, ret: Option<&Val>) {
+        self.borrow_mut().exit_fun(state, ret)
+    }
+
+    fn branch(&mut self, state: &EvalState<'_>, span: Span, index: usize) {
+        self.borrow_mut().branch(state, span, index)
+    }
+}
+
+/// A function invocation that is currently being evaluated.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CallFrame {
+    /// The called function, or `None` for the top-level code.
+    pub fun: Option<FunRef>,
+    /// The span of the statement that is currently evaluated in this frame, or of the call
+    /// expression if another function was called from here.
+    pub span: Span,
+}
+
+impl CallFrame {
+    pub const fn new(fun: Option<FunRef>, span: Span) -> Self {
+        Self { fun, span }
+    }
+}
+
+/// A read-only view of the evaluator state, passed to an [`Observer`].
+pub struct EvalState<'a> {
+    pub stack: &'a Stack,
+    pub funs: &'a Funs,
+    /// The currently evaluated calls, the innermost one is last.
+    pub calls: &'a [CallFrame],
+}
+
+impl<'a> EvalState<'a> {
+    pub const fn new(stack: &'a Stack, funs: &'a Funs, calls: &'a [CallFrame]) -> Self {
+        Self { stack, funs, calls }
+    }
+
+    /// The values stored in the frame of the call at `index`.
+    pub fn frame(&self, index: usize) -> &'a [Val] {
+        let offset = self.stack.frame_count() - self.calls.len();
+        self.stack.frame(offset + index)
+    }
+
+    /// The name of the function called at `index`.
+    pub fn fun_name(&self, index: usize) -> &'a str {
+        match self.calls[index].fun {
+            Some(f) => &self.funs.get(f).name,
+            None => "<main>",
+        }
+    }
+}
+
+/// An observer that logs every evaluated statement with its source line, and the arguments and
+/// return values of function calls. Lines are indented by the call depth.
+pub struct Tracer<'a, W: Write> {
+    out: W,
+    /// The lines of the source code.
+    lines: Vec<&'a str>,
+}
+
+impl<'a, W: Write> Tracer<'a, W> {
+    /// `input` is the source code of the evaluated program.
+    pub fn new(out: W, input: &'a str) -> Self {
+        let lines = input.lines().collect();
+        Self { out, lines }
+    }
+
+    fn indent(depth: usize) -> String {
+        "  ".repeat(depth.saturating_sub(1))
+    }
+}
+
+impl<W: Write> Observer for Tracer<'_, W> {
+    fn statement(&mut self, state: &EvalState<'_>, ast: &Ast) -> ControlFlow<u8> {
+        let line = ast.span.start.line;
+        let text = self.lines.get(line as usize).copied().unwrap_or_default();
+        let indent = Self::indent(state.calls.len());
+        let _ = writeln!(self.out, "{indent}{}: {}", line + 1, text.trim());
+        ControlFlow::Continue(())
+    }
+
+    fn enter_fun(&mut self, state: &EvalState<'_>) {
+        let i = state.calls.len() - 1;
+        let param_count = state.calls[i]
+            .fun
+            .map_or(0, |f| state.funs.get(f).params.len());
+        let args = state.frame(i)[..param_count]
+            .iter()
+            .map(fmt_val)
+            .collect::<Vec<_>>();
+        let indent = Self::indent(state.calls.len());
+        let name = state.fun_name(i);
+        let _ = writeln!(self.out, "{indent}call {name}({})", args.join(", "));
+    }
+
+    fn exit_fun(&mut self, state: &EvalState<'_>, ret: Option<&Val>) {
+        let indent = Self::indent(state.calls.len());
+        let name = state.fun_name(state.calls.len() - 1);
+        let _ = match ret {
+            Some(v) => writeln!(self.out, "{indent}return {name} = {}", fmt_val(v)),
+            None => writeln!(self.out, "{indent}abort {name}"),
+        };
+    }
+}
+
+/// Format a value for debugging output, strings and chars are quoted and escaped.
+pub fn fmt_val(val: &Val) -> String {
+    match val {
+        Val::Str(s) => format!("{s:?}"),
+        Val::Char(c) => format!("{c:?}"),
+        v => v.to_string(),
+    }
+}
@@ -45,6 +45,22 @@ impl Stack {
         self.values.truncate(len);
     }
 
+    /// The number of frames, including the global frame.
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// The values of the frame at `index`, the global frame is at index 0.
+    pub fn frame(&self, index: usize) -> &[Val] {
+        let start = self.frames[index];
+        let end = self
+            .frames
+            .get(index + 1)
+            .copied()
+            .unwrap_or(self.values.len());
+        &self.values[start..end]
+    }
+
     pub fn set(&mut self, var: &VarRef, val: Val) {
         let idx = match var {
             VarRef::Local(i) => self.frame_start() + i,
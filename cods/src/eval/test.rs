@@ -1,6 +1,14 @@
 use std::f64::consts;
+use std::io;
+use std::ops::ControlFlow;
+use std::path::Path;
 
-use crate::{Error, Par, ParT, Span, Val, ValSpan};
+use crate::{
+    Access, Ast, Checker, Clock, Context, Coverage, DataType, Env, Error, EvalState, FakeClock,
+    FromVal, FromValError, FunSignature, Initialized, IntoVal, Io, Observer, Par, ParT,
+    Permissions, Pos, Program, Range, Repetition, Rng, Span, Stack, TraceFrame, Tracer, UserFacing,
+    Val, ValSpan, VarInfo, VarRef,
+};
 
 fn assert(input: &str, expected: Val) {
     match crate::eval(input) {
@@ -266,6 +274,15 @@ fn clamp() {
     assert("clamp(31.0, 0.0, 7.0)", Val::Float(7.0));
 }
 
+#[test]
+fn clamp_extra_args() {
+    let error = crate::eval("clamp(9, -2, 23, 4)").unwrap_err();
+    assert!(matches!(
+        error,
+        Error::NoMatchingBuiltinFunSignature { span, .. } if span == Span::cols(0, 0, 19)
+    ));
+}
+
 #[test]
 fn clamp_bounds() {
     assert_err(
@@ -419,7 +436,10 @@ fn assertion() {
 
 #[test]
 fn assertion_failed() {
-    assert_err("assert(4 == 5)", Error::AssertFailed(Span::cols(0, 7, 13)));
+    assert_err(
+        "assert(4 == 5)",
+        Error::AssertFailed(None, Span::cols(0, 7, 13)),
+    );
 }
 
 #[test]
@@ -434,10 +454,120 @@ fn assertion_eq_failed() {
         Error::AssertEqFailed(
             ValSpan::new(Val::Bool(false), Span::cols(0, 10, 15)),
             ValSpan::new(Val::Bool(true), Span::cols(0, 17, 23)),
+            None,
+        ),
+    );
+}
+
+#[test]
+fn assertion_message() {
+    assert_err(
+        r#"assert(4 == 5, format("{} != {}", 4, 5))"#,
+        Error::AssertFailed(Some("4 != 5".into()), Span::cols(0, 7, 13)),
+    );
+    // the message is only evaluated if the assertion fails
+    assert(r#"assert(true, panic("unreachable"))"#, Val::Unit);
+}
+
+#[test]
+fn assertion_eq_any() {
+    assert("assert_eq('a', 'a'); assert_eq((), ())", Val::Unit);
+    assert_err(
+        r#"assert_eq("a", "b", "strings")"#,
+        Error::AssertEqFailed(
+            ValSpan::new(Val::Str("a".into()), Span::cols(0, 10, 13)),
+            ValSpan::new(Val::Str("b".into()), Span::cols(0, 15, 18)),
+            Some("strings".into()),
         ),
     );
 }
 
+#[test]
+fn assertion_eq_mismatched_types() {
+    assert_err(
+        "assert_eq(3, 3.0)",
+        Error::MismatchedType {
+            expected: DataType::Int,
+            found: DataType::Float,
+            spans: vec![Span::cols(0, 10, 11), Span::cols(0, 13, 16)],
+        },
+    );
+    assert_err(
+        "assert_ne('a', \"a\")",
+        Error::MismatchedType {
+            expected: DataType::Char,
+            found: DataType::Str,
+            spans: vec![Span::cols(0, 10, 13), Span::cols(0, 15, 18)],
+        },
+    );
+}
+
+#[test]
+fn assertion_ne() {
+    assert("assert_ne(1, 2)", Val::Unit);
+    assert_err(
+        "assert_ne(2, 2)",
+        Error::AssertNeFailed(
+            ValSpan::new(Val::Int(2), Span::cols(0, 10, 11)),
+            ValSpan::new(Val::Int(2), Span::cols(0, 13, 14)),
+            None,
+        ),
+    );
+}
+
+#[test]
+fn assertion_approx() {
+    assert("assert_approx(0.1 + 0.2, 0.3, 0.000001)", Val::Unit);
+    assert_err(
+        r#"assert_approx(1.0, 1.5, 0.1, "too far")"#,
+        Error::AssertApproxFailed {
+            a: ValSpan::new(Val::Float(1.0), Span::cols(0, 14, 17)),
+            b: ValSpan::new(Val::Float(1.5), Span::cols(0, 19, 22)),
+            eps: 0.1,
+            msg: Some("too far".into()),
+        },
+    );
+    // NaN is never close to anything
+    let error = crate::eval("assert_approx(0.0 / 0.0, 0.0, 1.0)").unwrap_err();
+    assert!(matches!(error, Error::AssertApproxFailed { .. }));
+}
+
+#[test]
+fn assertion_approx_invalid_tolerance() {
+    assert_err(
+        "assert_approx(1.0, 1.0, -1.0)",
+        Error::InvalidTolerance(ValSpan::new(Val::Float(-1.0), Span::cols(0, 24, 28))),
+    );
+    let error = crate::eval("assert_approx(1.0, 1.0, 0.0 / 0.0)").unwrap_err();
+    assert!(matches!(error, Error::InvalidTolerance(eps) if eps.span == Span::cols(0, 24, 33)));
+}
+
+#[test]
+fn panic() {
+    assert_err(
+        r#"panic("oh no")"#,
+        Error::Panic("oh no".into(), Span::cols(0, 0, 14)),
+    );
+}
+
+#[test]
+fn assertion_eq_side_by_side() {
+    let error = Error::AssertEqFailed(
+        ValSpan::new(Val::Str("one\ntwo\nthree".into()), Span::pos(0, 0)),
+        ValSpan::new(Val::Str("one\n2".into()), Span::pos(0, 0)),
+        Some("text".into()),
+    );
+    let mut desc = String::new();
+    error.description(&mut desc, "> ", "").unwrap();
+    let expected = "\
+> Assertion failed, values are not equal: text
+> left  │ right
+> one   │ one
+> two   ≠ 2
+> three ≠ ";
+    assert_eq!(desc, expected);
+}
+
 #[test]
 fn if_statement() {
     assert("let mut x = 2; if 4 == 3 + 1 { x += 3 }; x", Val::Int(5));
@@ -548,3 +678,1145 @@ fn line_comment() {
 fn block_comment() {
     assert("let a = /* yeeet */ 5; a", Val::Int(5));
 }
+
+fn host_checker() -> Checker {
+    let mut checker = Checker::default();
+    checker
+        .register_fun(
+            "double",
+            FunSignature::new(&[DataType::Int], Repetition::One, DataType::Int),
+            |args| Ok(Val::Int(args[0].clone().unwrap_int() * 2)),
+        )
+        .unwrap();
+    checker
+        .register_fun(
+            "double",
+            FunSignature::new(&[DataType::Float], Repetition::One, DataType::Float),
+            |args| Ok(Val::Float(args[0].clone().unwrap_float() * 2.0)),
+        )
+        .unwrap();
+    checker
+        .register_fun(
+            "join",
+            FunSignature::new(&[DataType::Str], Repetition::OneOrMore, DataType::Str),
+            |args| Ok(Val::Str(args.iter().map(|a| a.to_string()).collect())),
+        )
+        .unwrap();
+    checker
+        .register_fun(
+            "fail",
+            FunSignature::new(&[], Repetition::One, DataType::Unit),
+            |_| Err("host failure".into()),
+        )
+        .unwrap();
+    checker
+        .register_fun(
+            "liar",
+            FunSignature::new(&[], Repetition::One, DataType::Int),
+            |_| Ok(Val::Bool(true)),
+        )
+        .unwrap();
+    checker
+}
+
+fn assert_host(input: &str, expected: Val) {
+    let mut ctx = Context::default();
+    let mut checker = host_checker();
+    let mut stack = Stack::default();
+    match ctx.parse_and_eval_with(&mut checker, &mut stack, input) {
+        Ok(val) => assert_eq!(val, expected),
+        Err(e) => panic!("{e:?}"),
+    }
+}
+
+fn assert_host_err(input: &str, expected: Error) {
+    let mut ctx = Context::default();
+    let mut checker = host_checker();
+    let mut stack = Stack::default();
+    match ctx.parse_and_eval_with(&mut checker, &mut stack, input) {
+        Ok(_) => panic!("Expected error: {expected:?}"),
+        Err(e) => assert_eq!(e, expected),
+    }
+}
+
+#[test]
+fn host_fun() {
+    assert_host("double(21)", Val::Int(42));
+    assert_host("double(1.5)", Val::Float(3.0));
+    assert_host("join(\"a\", \"b\", \"c\")", Val::Str("abc".into()));
+}
+
+#[test]
+fn host_fun_failed() {
+    assert_host_err(
+        "fail()",
+        Error::HostFunFailed("host failure".into(), Span::cols(0, 0, 6)),
+    );
+}
+
+#[test]
+fn host_fun_mismatched_return_type() {
+    assert_host_err(
+        "liar()",
+        Error::MismatchedType {
+            expected: DataType::Int,
+            found: DataType::Bool,
+            spans: vec![Span::cols(0, 0, 6)],
+        },
+    );
+}
+
+#[test]
+fn host_fun_no_matching_signature() {
+    assert_host_err(
+        "double(1, 2)",
+        Error::NoMatchingHostFunSignature {
+            name: "double".into(),
+            args: vec![DataType::Int, DataType::Int],
+            signatures: vec![
+                FunSignature::new(&[DataType::Int], Repetition::One, DataType::Int),
+                FunSignature::new(&[DataType::Float], Repetition::One, DataType::Float),
+            ],
+            span: Span::cols(0, 0, 12),
+        },
+    );
+}
+
+#[test]
+fn host_fun_redefined() {
+    assert_host_err(
+        "fn double(a: int) -> int { a }",
+        Error::RedefinedHostFun("double".into(), Span::cols(0, 3, 9)),
+    );
+}
+
+#[test]
+fn host_fun_builtin_name() {
+    let mut checker = Checker::default();
+    let res = checker.register_fun(
+        "sqrt",
+        FunSignature::new(&[DataType::Int], Repetition::One, DataType::Int),
+        |args| Ok(args[0].clone()),
+    );
+    assert_eq!(res, Err(Error::RedefinedBuiltinFun("sqrt".into(), None)));
+}
+
+#[test]
+fn host_globals() {
+    let mut ctx = Context::default();
+    let mut checker = Checker::default();
+    let mut stack = Stack::default();
+    ctx.def_global(&mut checker, &mut stack, "price", Val::Float(2.5), false);
+    ctx.def_global(&mut checker, &mut stack, "qty", Val::Float(4.0), false);
+    ctx.def_global(&mut checker, &mut stack, "discount", Val::Float(0.5), false);
+    let total = ctx.def_global(&mut checker, &mut stack, "total", Val::Float(0.0), true);
+
+    let input = "total = price * qty * (1.0 - discount); total";
+    let val = ctx.parse_and_eval_with(&mut checker, &mut stack, input);
+    assert_eq!(val, Ok(Val::Float(5.0)));
+    assert_eq!(stack.get(&total), Val::Float(5.0));
+    assert!(ctx.warnings.is_empty());
+}
+
+#[test]
+fn host_global_immutable() {
+    let mut ctx = Context::default();
+    let mut checker = Checker::default();
+    let mut stack = Stack::default();
+    let x = ctx.def_global(&mut checker, &mut stack, "x", Val::Int(3), false);
+    let info = checker.debug.globals.iter().find(|v| v.var == x).unwrap();
+    assert_eq!(info.span, None);
+
+    // only the use site is reported, host globals don't have a definition inside the input
+    let input = "println(x)\nx = 4";
+    let val = ctx.parse_and_eval_with(&mut checker, &mut stack, input);
+    assert_eq!(
+        val,
+        Err(Error::ImmutableAssign(
+            "x".into(),
+            Initialized::Yes,
+            Span::pos(1, 0),
+            Span::pos(1, 4),
+        ))
+    );
+}
+
+fn checked_script(input: &str) -> (Checker, Stack) {
+    let mut ctx = Context::default();
+    let mut checker = Checker::default();
+    let mut stack = Stack::default();
+    ctx.parse_and_eval_with(&mut checker, &mut stack, input)
+        .unwrap();
+    (checker, stack)
+}
+
+#[test]
+fn call_script_fun() {
+    let input = "
+        let offset = 2
+        fn euclid_gcd(a: int, b: int) -> int {
+            if b == 0 { a } else { euclid_gcd(b, a % b) }
+        }
+        fn add_offset(a: int) -> int { a + offset }
+    ";
+    let (checker, mut stack) = checked_script(input);
+    let val = crate::call(
+        &mut stack,
+        &checker.funs,
+        "euclid_gcd",
+        &[Val::Int(16), Val::Int(24)],
+    );
+    assert_eq!(val, Ok(Val::Int(8)));
+    let val = crate::call(&mut stack, &checker.funs, "add_offset", &[Val::Int(5)]);
+    assert_eq!(val, Ok(Val::Int(7)));
+}
+
+#[test]
+fn call_script_fun_errors() {
+    let input = "
+        fn outer() {
+            fn inner() {}
+            inner()
+        }
+        fn double(a: int) -> int { a * 2 }
+    ";
+    let (checker, mut stack) = checked_script(input);
+    let funs = &checker.funs;
+    assert_eq!(
+        crate::call(&mut stack, funs, "inner", &[]),
+        Err(Error::UnknownFun("inner".into()))
+    );
+    assert_eq!(
+        crate::call(&mut stack, funs, "double", &[]),
+        Err(Error::CallArgCount {
+            name: "double".into(),
+            expected: 1,
+            found: 0,
+        })
+    );
+    assert_eq!(
+        crate::call(&mut stack, funs, "double", &[Val::Float(1.0)]),
+        Err(Error::CallArgType {
+            name: "double".into(),
+            index: 0,
+            expected: DataType::Int,
+            found: DataType::Float,
+        })
+    );
+}
+
+#[test]
+fn program_run_many() {
+    let mut ctx = Context::default();
+    let mut checker = Checker::default();
+    let mut stack = Stack::default();
+    ctx.def_global(&mut checker, &mut stack, "price", Val::Float(0.0), false);
+    ctx.def_global(&mut checker, &mut stack, "qty", Val::Int(0), false);
+    let program = ctx.compile_with(checker, "price * qty as float").unwrap();
+
+    let price = program.global("price").unwrap();
+    let qty = program.global("qty").unwrap();
+    for i in 0..10 {
+        let mut stack = program.stack();
+        stack.set(&price, Val::Float(1.5));
+        stack.set(&qty, Val::Int(i));
+        assert_eq!(
+            program.eval_with(&mut stack),
+            Ok(Val::Float(1.5 * i as f64))
+        );
+    }
+}
+
+#[test]
+fn default_io_does_not_lock_stdin() {
+    let env = Env::default();
+    let (sender, receiver) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        let _lock = std::io::stdin().lock();
+        let _ = sender.send(());
+    });
+    let locked = receiver.recv_timeout(std::time::Duration::from_secs(5));
+    assert_eq!(locked, Ok(()));
+    drop(env);
+}
+
+#[test]
+fn program_host_global_initial_value() {
+    let mut ctx = Context::default();
+    let mut checker = Checker::default();
+    let mut stack = Stack::default();
+    ctx.def_global(&mut checker, &mut stack, "price", Val::Float(2.0), false);
+    let program = ctx.compile_with(checker, "price * 2.0").unwrap();
+
+    assert_eq!(program.eval(), Ok(Val::Float(4.0)));
+    assert_eq!(program.eval(), Ok(Val::Float(4.0)));
+}
+
+#[test]
+fn program_globals() {
+    let mut ctx = Context::default();
+    let program = ctx
+        .compile("let a = 3; { let b = 4; println(b) }; fn c() -> int { a }")
+        .unwrap();
+    let mut stack = program.stack();
+    assert_eq!(program.eval_with(&mut stack), Ok(Val::Unit));
+    assert!(program.global("a").is_some());
+    assert!(program.global("b").is_none());
+    assert_eq!(program.call(&mut stack, "c", &[]), Ok(Val::Int(3)));
+}
+
+#[test]
+fn program_is_send_sync() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Program>();
+}
+
+#[test]
+fn program_concurrent_eval() {
+    let mut ctx = Context::default();
+    let mut checker = Checker::default();
+    let mut stack = Stack::default();
+    checker
+        .register_fun(
+            "square",
+            FunSignature::new(&[DataType::Int], Repetition::One, DataType::Int),
+            |args| {
+                let a = args[0].clone().unwrap_int();
+                Ok(Val::Int(a * a))
+            },
+        )
+        .unwrap();
+    ctx.def_global(&mut checker, &mut stack, "n", Val::Int(0), false);
+    let input = "
+        fn sum(n: int) -> int {
+            let mut sum = 0
+            for i in 1..=n {
+                sum += square(i)
+            }
+            sum
+        }
+        sum(n)
+    ";
+    let program = ctx.compile_with(checker, input).unwrap();
+    let n = program.global("n").unwrap();
+
+    std::thread::scope(|s| {
+        let handles: Vec<_> = (0..8)
+            .map(|i| {
+                let program = &program;
+                s.spawn(move || {
+                    let mut stack = program.stack();
+                    stack.set(&n, Val::Int(i));
+                    program.eval_with(&mut stack)
+                })
+            })
+            .collect();
+
+        for (i, h) in handles.into_iter().enumerate() {
+            let i = i as i128;
+            let expected = i * (i + 1) * (2 * i + 1) / 6;
+            assert_eq!(h.join().unwrap(), Ok(Val::Int(expected)));
+        }
+    });
+}
+
+#[test]
+fn val_conversion() {
+    assert_eq!(42i32.into_val(), Val::Int(42));
+    assert_eq!("abc".into_val(), Val::Str("abc".into()));
+    assert_eq!(u8::from_val(Val::Int(255)), Ok(255));
+    assert_eq!(
+        u8::from_val(Val::Int(256)),
+        Err(FromValError::IntOutOfRange {
+            val: 256,
+            typ: "u8"
+        })
+    );
+    assert_eq!(
+        String::from_val(Val::Bool(true)),
+        Err(FromValError::MismatchedType {
+            expected: DataType::Str,
+            found: DataType::Bool,
+        })
+    );
+}
+
+#[test]
+fn typed_host_fun() {
+    let mut checker = Checker::default();
+    checker
+        .register_typed_fun("repeat", |s: String, n: u32| s.repeat(n as usize))
+        .unwrap();
+    checker
+        .register_typed_fun("checked_div", |a: i64, b: i64| {
+            a.checked_div(b).ok_or("division by zero")
+        })
+        .unwrap();
+
+    let mut ctx = Context::default();
+    let program = ctx.compile_with(checker, "repeat(\"ab\", 3)").unwrap();
+    assert_eq!(program.eval(), Ok(Val::Str("ababab".into())));
+
+    let mut checker = Checker::default();
+    checker
+        .register_typed_fun("repeat", |s: String, n: u32| s.repeat(n as usize))
+        .unwrap();
+    let program = ctx.compile_with(checker, "repeat(\"ab\", -1)").unwrap();
+    assert_eq!(
+        program.eval(),
+        Err(Error::HostFunFailed(
+            "Value `-1` is out of range for `u32`".into(),
+            Span::cols(0, 0, 16)
+        ))
+    );
+
+    let mut checker = Checker::default();
+    checker
+        .register_typed_fun("checked_div", |a: i64, b: i64| {
+            a.checked_div(b).ok_or("division by zero")
+        })
+        .unwrap();
+    let program = ctx.compile_with(checker, "checked_div(1, 0)").unwrap();
+    assert_eq!(
+        program.eval(),
+        Err(Error::HostFunFailed(
+            "division by zero".into(),
+            Span::cols(0, 0, 17)
+        ))
+    );
+}
+
+fn eval_with_input(input: &str, stdin: &str) -> (crate::Result<Val>, String) {
+    let mut ctx = Context::default();
+    let program = ctx.compile(input).unwrap();
+    let mut stdout = Vec::new();
+    let mut env = Env::new(Io::new(stdin.as_bytes(), &mut stdout));
+    let val = program.eval_with_env(&mut program.stack(), &mut env);
+    drop(env);
+    (val, String::from_utf8(stdout).unwrap())
+}
+
+#[test]
+fn read_input() {
+    let input = "
+        let name = read_line()
+        let a = read_int()
+        let b = read_float()
+        println(name, a, b)
+        read_all()
+    ";
+    let (val, stdout) = eval_with_input(input, "cods\r\n 4 \n2.5\nrest\nof input");
+    assert_eq!(val, Ok(Val::Str("rest\nof input".into())));
+    assert_eq!(stdout, "cods 4 2.5\n");
+
+    let (val, _) = eval_with_input("read_line()", "");
+    assert_eq!(val, Ok(Val::Str("".into())));
+}
+
+#[test]
+fn read_invalid_input() {
+    let (val, _) = eval_with_input("read_int()", "3.4\n");
+    assert_eq!(
+        val,
+        Err(Error::InvalidInput(
+            "3.4".into(),
+            DataType::Int,
+            Span::cols(0, 0, 10)
+        ))
+    );
+    let (val, _) = eval_with_input("read_float()", "");
+    assert_eq!(
+        val,
+        Err(Error::InvalidInput(
+            "".into(),
+            DataType::Float,
+            Span::cols(0, 0, 12)
+        ))
+    );
+}
+
+fn eval_with_permissions(input: &str, permissions: Permissions) -> crate::Result<Val> {
+    let mut ctx = Context::default();
+    let program = ctx.compile(input).unwrap();
+    let mut env = Env::new(Io::silent());
+    env.permissions = permissions;
+    program.eval_with_env(&mut program.stack(), &mut env)
+}
+
+#[test]
+fn file_system() {
+    let dir = std::env::temp_dir().join(format!("cods_file_system_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let mut permissions = Permissions::default();
+    permissions.allow_read(&dir);
+    permissions.allow_write(&dir);
+
+    let input = format!(
+        "
+        let file = \"{dir}/a.txt\"
+        assert(!exists(file))
+        write_file(file, \"hello\")
+        append_file(file, \" world\")
+        write_file(\"{dir}/b.txt\", \"\")
+        assert(exists(file))
+        assert_eq(list_dir(\"{dir}\"), \"a.txt\\nb.txt\")
+        read_file(file)
+        ",
+        dir = dir.display()
+    );
+    let val = eval_with_permissions(&input, permissions);
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(val, Ok(Val::Str("hello world".into())));
+}
+
+#[test]
+fn file_system_permission_denied() {
+    let dir = std::env::temp_dir().join(format!("cods_permission_{}", std::process::id()));
+    let mut permissions = Permissions::default();
+    permissions.allow_read(&dir);
+
+    let input = format!("read_file(\"{}/../secret\")", dir.display());
+    let val = eval_with_permissions(&input, permissions.clone());
+    let path = format!("{}/../secret", dir.display());
+    let span = Span::cols(0, 10, 10 + path.len() as u32 + 2);
+    assert_eq!(val, Err(Error::PermissionDenied(path, Access::Read, span)));
+
+    let input = format!("write_file(\"{}/file\", \"\")", dir.display());
+    let val = eval_with_permissions(&input, permissions);
+    let path = format!("{}/file", dir.display());
+    let span = Span::cols(0, 11, 11 + path.len() as u32 + 2);
+    assert_eq!(val, Err(Error::PermissionDenied(path, Access::Write, span)));
+
+    let val = eval_with_permissions("exists(\"/\")", Permissions::default());
+    assert_eq!(
+        val,
+        Err(Error::PermissionDenied(
+            "/".into(),
+            Access::Read,
+            Span::cols(0, 7, 10)
+        ))
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn file_system_symlink_parent_dir() {
+    let base = std::env::temp_dir().join(format!("cods_symlink_{}", std::process::id()));
+    let allowed = base.join("allowed");
+    let outside = base.join("outside");
+    std::fs::create_dir_all(&allowed).unwrap();
+    std::fs::create_dir_all(&outside).unwrap();
+    std::os::unix::fs::symlink(&outside, allowed.join("link")).unwrap();
+    let mut permissions = Permissions::default();
+    permissions.allow_write(&allowed);
+
+    let is_allowed = |p: &str| {
+        let path = format!("{}/{p}", allowed.display());
+        permissions.is_allowed(Access::Write, Path::new(&path))
+    };
+    // `link/..` is the parent of `outside`, not `allowed`
+    let link_parent = is_allowed("link/../new");
+    let link = is_allowed("link/new");
+    let missing_parent = is_allowed("missing/../new");
+    let cur_dir = is_allowed("./new");
+    std::fs::remove_dir_all(&base).unwrap();
+
+    assert!(!link_parent);
+    assert!(!link);
+    assert!(!missing_parent);
+    assert!(cur_dir);
+}
+
+#[test]
+fn script_args() {
+    let mut ctx = Context::default();
+    let program = ctx.compile("assert_eq(arg_count(), 2); arg(1)").unwrap();
+    let mut env = Env::new(Io::silent());
+    env.args = vec!["a".into(), "b".into()];
+    let val = program.eval_with_env(&mut program.stack(), &mut env);
+    assert_eq!(val, Ok(Val::Str("b".into())));
+
+    let program = ctx.compile("arg(2)").unwrap();
+    let val = program.eval_with_env(&mut program.stack(), &mut env);
+    assert_eq!(
+        val,
+        Err(Error::ArgOutOfBounds(
+            ValSpan::new(Val::Int(2), Span::pos(0, 4)),
+            2
+        ))
+    );
+}
+
+#[test]
+fn env_var() {
+    let mut ctx = Context::default();
+    let program = ctx.compile("env(\"PATH\")").unwrap();
+    let mut env = Env::new(Io::silent());
+    let val = program.eval_with_env(&mut program.stack(), &mut env);
+    assert_eq!(
+        val,
+        Err(Error::PermissionDenied(
+            "PATH".into(),
+            Access::Env,
+            Span::cols(0, 4, 10)
+        ))
+    );
+
+    env.permissions.env = true;
+    let val = program.eval_with_env(&mut program.stack(), &mut env);
+    let path = std::env::var("PATH").unwrap_or_default();
+    assert_eq!(val, Ok(Val::Str(path)));
+}
+
+#[test]
+fn env_var_not_present() {
+    let mut ctx = Context::default();
+    let program = ctx.compile("env(\"CODS_UNSET_VAR\")").unwrap();
+    let mut env = Env::new(Io::silent());
+    env.permissions.env = true;
+    let val = program.eval_with_env(&mut program.stack(), &mut env);
+    assert_eq!(
+        val,
+        Err(Error::EnvVarNotPresent(
+            "CODS_UNSET_VAR".into(),
+            Span::cols(0, 4, 20)
+        ))
+    );
+}
+
+#[test]
+fn exit() {
+    let mut ctx = Context::default();
+    let input = "
+        fn check(a: int) {
+            if a > 2 {
+                exit(a)
+            }
+        }
+        for i in 0..10 {
+            println(i)
+            check(i)
+        }
+    ";
+    let program = ctx.compile(input).unwrap();
+    let mut stdout = Vec::new();
+    let mut env = Env::new(Io::new(io::empty(), &mut stdout));
+    let val = program.eval_with_env(&mut program.stack(), &mut env);
+    assert_eq!(val, Ok(Val::Unit));
+    assert_eq!(env.exit_code, Some(3));
+    drop(env);
+    assert_eq!(String::from_utf8(stdout).unwrap(), "0\n1\n2\n3\n");
+}
+
+#[test]
+fn exit_code_out_of_range() {
+    assert_err(
+        "exit(256)",
+        Error::InvalidExitCode(ValSpan::new(Val::Int(256), Span::cols(0, 5, 8))),
+    );
+    assert_err(
+        "exit(-1)",
+        Error::InvalidExitCode(ValSpan::new(Val::Int(-1), Span::cols(0, 5, 7))),
+    );
+}
+
+#[test]
+fn fake_clock() {
+    let mut ctx = Context::default();
+    let input = "
+        let start = monotonic()
+        let time = now()
+        sleep(1500ns)
+        sleep(500ns)
+        assert_eq(now() - time, 2000)
+        monotonic() - start
+    ";
+    let program = ctx.compile(input).unwrap();
+    let mut clock = FakeClock::new(1_700_000_000_000_000_000);
+    let mut env = Env::new(Io::silent());
+    env.clock = Box::new(&mut clock);
+    let val = program.eval_with_env(&mut program.stack(), &mut env);
+    drop(env);
+    assert_eq!(val, Ok(Val::Duration(2000)));
+    assert_eq!(clock.elapsed, 2000);
+    assert_eq!(clock.now(), 1_700_000_000_000_002_000);
+}
+
+#[test]
+fn colorful_counting_example() {
+    let mut ctx = Context::default();
+    let input = include_str!("../../../examples/colorful_counting.cods");
+    let program = ctx.compile(input).unwrap();
+    let mut clock = FakeClock::default();
+    let mut env = Env::new(Io::silent());
+    env.clock = Box::new(&mut clock);
+    let val = program.eval_with_env(&mut program.stack(), &mut env);
+    drop(env);
+    assert_eq!(val, Ok(Val::Unit));
+    assert_eq!(clock.elapsed, 100 * 50_000_000 + 10 * 150_000_000);
+}
+
+#[test]
+fn duration_arithmetic() {
+    assert("1m + 30s", Val::Duration(90_000_000_000));
+    assert("1s - 1500ms", Val::Duration(-500_000_000));
+    assert("3 * 20ms", Val::Duration(60_000_000));
+    assert("20ms * 3", Val::Duration(60_000_000));
+    assert("1s / 4", Val::Duration(250_000_000));
+    assert("1m / 15s", Val::Int(4));
+    assert("1m % 7s", Val::Duration(4_000_000_000));
+    assert("-(2h)", Val::Duration(-7_200_000_000_000));
+    assert("999ms < 1s", Val::Bool(true));
+    assert("60s >= 1m", Val::Bool(true));
+    assert("60s == 1m", Val::Bool(true));
+}
+
+#[test]
+fn duration_overflow() {
+    let low = "let low = -170141183460469231731687303715884105727ns - 1ns;\n";
+    assert_err(
+        &format!("{low}-low"),
+        Error::NegOverflow(Span::cols(1, 1, 4)),
+    );
+    assert_err(
+        &format!("{low}low / -1"),
+        Error::DivOverflow(Span::cols(1, 0, 3), Span::cols(1, 6, 8)),
+    );
+    assert_err(
+        &format!("{low}low / -1ns"),
+        Error::DivOverflow(Span::cols(1, 0, 3), Span::cols(1, 6, 10)),
+    );
+    assert_err(
+        &format!("{low}low % -1ns"),
+        Error::RemOverflow(Span::cols(1, 0, 3), Span::cols(1, 6, 10)),
+    );
+    assert_err(
+        "1s / 0",
+        Error::DivideByZero(Span::cols(0, 0, 2), Span::cols(0, 5, 6)),
+    );
+    assert_err(
+        "1s % 0s",
+        Error::RemainderByZero(Span::cols(0, 0, 2), Span::cols(0, 5, 7)),
+    );
+}
+
+#[test]
+fn duration_cast() {
+    assert("1us as int", Val::Int(1000));
+    assert("250 as duration", Val::Duration(250));
+}
+
+#[test]
+fn duration_display() {
+    let fmt = |nanos| Val::Duration(nanos).to_string();
+    assert_eq!(fmt(0), "0s");
+    assert_eq!(fmt(90_000_000_000), "1m30s");
+    assert_eq!(fmt(3_600_001_000_002), "1h1ms2ns");
+    assert_eq!(fmt(-500_000_000), "-500ms");
+}
+
+#[test]
+fn duration_conversion() {
+    use std::time::Duration;
+
+    assert_eq!(
+        Duration::from_val(Val::Duration(1_500_000_000)),
+        Ok(Duration::from_millis(1500))
+    );
+    assert_eq!(
+        Duration::from_val(Val::Duration(-1)),
+        Err(FromValError::NegativeDuration(Val::Duration(-1)))
+    );
+    assert_eq!(Duration::from_micros(3).into_val(), Val::Duration(3000));
+}
+
+#[test]
+fn negative_sleep() {
+    assert_err(
+        "sleep(-1s)",
+        Error::NegativeSleepDuration(ValSpan::new(
+            Val::Duration(-1_000_000_000),
+            Span::cols(0, 6, 9),
+        )),
+    );
+}
+
+fn eval_with_rng(input: &str, rng: Rng) -> (crate::Result<Val>, String) {
+    let mut ctx = Context::default();
+    let program = ctx.compile(input).unwrap();
+    let mut stdout = Vec::new();
+    let mut env = Env::new(Io::new(io::empty(), &mut stdout));
+    env.rng = rng;
+    let val = program.eval_with_env(&mut program.stack(), &mut env);
+    drop(env);
+    (val, String::from_utf8(stdout).unwrap())
+}
+
+#[test]
+fn seeded_random() {
+    let input = "
+        for _ in 0..100 {
+            let f = random()
+            assert(f >= 0.0 && f < 1.0)
+            let i = random_int(-3, 3)
+            assert(i >= -3 && i <= 3)
+            let r = random_range(10..12)
+            assert(r == 10 || r == 11)
+            println(f, i, r)
+        }
+    ";
+    let (val, a) = eval_with_rng(input, Rng::new(42));
+    assert_eq!(val, Ok(Val::Unit));
+    let (_, b) = eval_with_rng(input, Rng::new(42));
+    let (_, c) = eval_with_rng(input, Rng::new(43));
+    assert_eq!(a, b);
+    assert_ne!(a, c);
+}
+
+#[test]
+fn seed_builtin() {
+    let input = "
+        seed(7)
+        let a = random_int(0, 1000000)
+        seed(7)
+        let b = random_int(0, 1000000)
+        assert_eq(a, b)
+        random_range(5..=5)
+    ";
+    let (val, _) = eval_with_rng(input, Rng::default());
+    assert_eq!(val, Ok(Val::Int(5)));
+}
+
+#[test]
+fn empty_random_range() {
+    assert_err(
+        "let r = 3..3; random_range(r)",
+        Error::EmptyRandomRange(ValSpan::new(
+            Val::Range(Range::Exclusive(3, 3)),
+            Span::pos(0, 27),
+        )),
+    );
+    assert_err(
+        "random_int(2, 1)",
+        Error::EmptyRandomRange(ValSpan::new(
+            Val::Range(Range::Inclusive(2, 1)),
+            Span::cols(0, 11, 15),
+        )),
+    );
+}
+
+#[test]
+fn random_full_int_range() {
+    let mut rng = Rng::new(0);
+    let a = rng.next_int(i128::MIN, i128::MAX);
+    let b = rng.next_int(i128::MIN, i128::MAX);
+    assert_ne!(a, b);
+    assert_eq!(rng.next_int(i128::MAX, i128::MAX), i128::MAX);
+}
+
+#[test]
+fn format() {
+    assert(
+        r#"format("{} and {}", 1, "two")"#,
+        Val::Str("1 and two".into()),
+    );
+    assert(r#"format("{1}{0}{1}", 'a', 'b')"#, Val::Str("bab".into()));
+    assert(r#"format("{{{}}}", 3)"#, Val::Str("{3}".into()));
+    assert(
+        r#"format("[{:>8.3}]", 3.14159)"#,
+        Val::Str("[   3.142]".into()),
+    );
+    assert(r#"format("[{:<6}]", "ab")"#, Val::Str("[ab    ]".into()));
+    assert(r#"format("[{:*^7}]", "ab")"#, Val::Str("[**ab***]".into()));
+    assert(r#"format("[{:5}]", 42)"#, Val::Str("[   42]".into()));
+    assert(r#"format("{:+}", 5)"#, Val::Str("+5".into()));
+    assert(r#"format("{:06.2}", -1.5)"#, Val::Str("-01.50".into()));
+    assert(
+        r#"format("{:x} {:X} {:o} {:b}", 255, 255, 8, 5)"#,
+        Val::Str("ff FF 10 101".into()),
+    );
+    assert(r#"format("{:#<4x}", -10)"#, Val::Str("-a##".into()));
+    assert(
+        r#"format("{:e} {:.2E}", 1234.5, 1234)"#,
+        Val::Str("1.2345e3 1.23E3".into()),
+    );
+    assert(r#"format("{:.3}", "abcdef")"#, Val::Str("abc".into()));
+    assert(r#"format("{}", 1m30s)"#, Val::Str("1m30s".into()));
+}
+
+#[test]
+fn printf() {
+    let (val, stdout) = eval_with_input(r#"printf("{:>3}|{:<3}|\n", 1, 2)"#, "");
+    assert_eq!(val, Ok(Val::Unit));
+    assert_eq!(stdout, "  1|2  |\n");
+}
+
+#[test]
+fn format_dynamic_string() {
+    assert(r#"let f = "{:>4}"; format(f, 7)"#, Val::Str("   7".into()));
+    assert_err(
+        r#"let f = "{:x}"; format(f, 2.0)"#,
+        Error::FormatArgType {
+            placeholder: "{:x}".into(),
+            data_type: DataType::Float,
+            span: Span::cols(0, 26, 29),
+        },
+    );
+    assert_err(
+        r#"let f = "{} {}"; format(f, 1)"#,
+        Error::FormatArgCount {
+            expected: 2,
+            found: 1,
+            span: Span::cols(0, 17, 29),
+        },
+    );
+    assert_err(
+        r#"let f = "{:.65536}"; format(f, 1.0)"#,
+        Error::InvalidFormatString(
+            crate::FormatError::InvalidSpec(".65536".into()),
+            Span::cols(0, 28, 29),
+        ),
+    );
+}
+
+#[derive(Default)]
+struct Recorder {
+    statements: Vec<(u32, usize)>,
+    calls: Vec<String>,
+    break_at: Option<u32>,
+}
+
+impl Observer for Recorder {
+    fn statement(&mut self, state: &EvalState<'_>, ast: &Ast) -> ControlFlow<u8> {
+        let line = ast.span.start.line;
+        self.statements.push((line, state.calls.len()));
+        match self.break_at == Some(line) {
+            true => ControlFlow::Break(7),
+            false => ControlFlow::Continue(()),
+        }
+    }
+
+    fn enter_fun(&mut self, state: &EvalState<'_>) {
+        let i = state.calls.len() - 1;
+        let args = state
+            .frame(i)
+            .iter()
+            .map(Val::to_string)
+            .collect::<Vec<_>>();
+        self.calls
+            .push(format!("enter {}({})", state.fun_name(i), args.join(", ")));
+    }
+
+    fn exit_fun(&mut self, state: &EvalState<'_>, ret: Option<&Val>) {
+        let i = state.calls.len() - 1;
+        let ret = ret.map_or("-".into(), Val::to_string);
+        self.calls
+            .push(format!("exit {} = {ret}", state.fun_name(i)));
+    }
+}
+
+#[test]
+fn observer() {
+    let mut ctx = Context::default();
+    let input = "\
+fn sqr(a: int) -> int {
+    a * a
+}
+let x = sqr(3)
+x + 1
+";
+    let program = ctx.compile(input).unwrap();
+    let mut recorder = Recorder::default();
+    let mut env = Env::new(Io::silent());
+    env.observer = Some(Box::new(&mut recorder));
+    let val = program.eval_with_env(&mut program.stack(), &mut env);
+    drop(env);
+
+    assert_eq!(val, Ok(Val::Int(10)));
+    assert_eq!(recorder.statements, [(3, 1), (1, 2), (4, 1)]);
+    assert_eq!(recorder.calls, ["enter sqr(3)", "exit sqr = 9"]);
+}
+
+#[test]
+fn observer_break() {
+    let mut ctx = Context::default();
+    let input = "\
+let mut x = 1
+x += 1
+x += 1
+x
+";
+    let program = ctx.compile(input).unwrap();
+    let mut recorder = Recorder {
+        break_at: Some(2),
+        ..Default::default()
+    };
+    let mut env = Env::new(Io::silent());
+    env.observer = Some(Box::new(&mut recorder));
+    let mut stack = program.stack();
+    let val = program.eval_with_env(&mut stack, &mut env);
+    let exit_code = env.exit_code;
+    drop(env);
+
+    assert_eq!(val, Ok(Val::Unit));
+    assert_eq!(exit_code, Some(7));
+    assert_eq!(recorder.statements, [(0, 1), (1, 1), (2, 1)]);
+    assert_eq!(stack.frame(0), [Val::Int(2)]);
+}
+
+#[test]
+fn debug_info() {
+    let mut ctx = Context::default();
+    let input = "\
+let a = 1
+fn f(p: int) -> int {
+    if p > 0 {
+        let b = p
+        b
+    } else {
+        let c = 'c';
+        -p
+    }
+}
+f(a)
+";
+    let program = ctx.compile(input).unwrap();
+    let debug = &program.debug;
+    let names = |vars: Vec<&VarInfo>| {
+        vars.iter()
+            .map(|v| (v.name.clone(), v.var, v.data_type))
+            .collect::<Vec<_>>()
+    };
+
+    let f = program.funs.find("f");
+    assert_eq!(debug.fun_name(f), "f");
+    assert_eq!(debug.fun(f.unwrap()).unwrap().span, Span::cols(1, 3, 4));
+    assert_eq!(debug.fun_name(None), "<main>");
+
+    assert_eq!(
+        names(debug.live_vars(None, Pos::new(10, 0))),
+        [("a".into(), VarRef::Global(0), DataType::Int)]
+    );
+    assert_eq!(
+        names(debug.live_vars(f, Pos::new(4, 8))),
+        [
+            ("p".into(), VarRef::Local(0), DataType::Int),
+            ("b".into(), VarRef::Local(1), DataType::Int),
+        ]
+    );
+    // `c` reuses the slot of `b`, which is no longer live
+    assert_eq!(
+        names(debug.live_vars(f, Pos::new(7, 8))),
+        [
+            ("p".into(), VarRef::Local(0), DataType::Int),
+            ("c".into(), VarRef::Local(1), DataType::Char),
+        ]
+    );
+    assert_eq!(
+        debug.frame_vars(f)[1].live,
+        Span::new(Pos::new(3, 12), Pos::new(5, 5))
+    );
+}
+
+#[test]
+fn stack_trace() {
+    let mut ctx = Context::default();
+    let input = "\
+fn inner(a: int) -> int {
+    a / 0
+}
+fn outer(a: int) -> int {
+    let b = inner(a)
+    b
+}
+outer(1)
+";
+    let program = ctx.compile(input).unwrap();
+    let mut env = Env::new(Io::silent());
+    let res = program.eval_with_env(&mut program.stack(), &mut env);
+
+    assert!(matches!(res, Err(Error::DivideByZero(..))));
+    assert_eq!(
+        env.trace,
+        [
+            TraceFrame::new("inner".into(), Span::cols(4, 12, 20)),
+            TraceFrame::new("outer".into(), Span::cols(7, 0, 8)),
+        ]
+    );
+
+    // the trace is reset when evaluating again
+    let res = program.call_with_env(&mut program.stack(), &mut env, "outer", &[Val::Int(1)]);
+    assert!(res.is_err());
+    assert_eq!(
+        env.trace,
+        [TraceFrame::new("inner".into(), Span::cols(4, 12, 20))]
+    );
+}
+
+#[test]
+fn tracer() {
+    let mut ctx = Context::default();
+    let input = "\
+fn greet(name: str, n: int) -> str {
+    let s = format(\"{}{}\", name, n)
+    s
+}
+let x = greet(\"hi\", 2)
+";
+    let program = ctx.compile(input).unwrap();
+    let mut out = Vec::new();
+    let mut env = Env::new(Io::silent());
+    env.observer = Some(Box::new(Tracer::new(&mut out, input)));
+    program
+        .eval_with_env(&mut program.stack(), &mut env)
+        .unwrap();
+    drop(env);
+
+    let expected = "\
+5: let x = greet(\"hi\", 2)
+  call greet(\"hi\", 2)
+  2: let s = format(\"{}{}\", name, n)
+  3: s
+  return greet = \"hi2\"
+";
+    assert_eq!(String::from_utf8(out).unwrap(), expected);
+}
+
+#[test]
+fn coverage() {
+    let mut ctx = Context::default();
+    let input = "\
+fn sign(a: int) -> int {
+    if a < 0 {
+        return -1
+    } else if a == 0 {
+        return 0
+    }
+    1
+}
+let x = sign(4) + sign(5)
+match x {
+    2 => println(\"two\")
+    _ => println(\"other\")
+}
+";
+    let program = ctx.compile(input).unwrap();
+    let mut coverage = Coverage::new(&program.asts, &program.funs);
+    let mut env = Env::new(Io::silent());
+    env.observer = Some(Box::new(&mut coverage));
+    program
+        .eval_with_env(&mut program.stack(), &mut env)
+        .unwrap();
+    drop(env);
+
+    assert_eq!(
+        coverage.lines(),
+        [(1, 2), (2, 0), (4, 0), (6, 2), (8, 1), (9, 1)]
+    );
+    assert_eq!(coverage.branches.len(), 2);
+    assert_eq!(coverage.branches[0].span.start, Pos::new(1, 4));
+    assert_eq!(coverage.branches[0].hits, [0, 0, 2]);
+    assert_eq!(coverage.branches[1].span.start, Pos::new(9, 0));
+    assert_eq!(coverage.branches[1].hits, [1, 0]);
+    assert!(coverage.branches[1].reached());
+}
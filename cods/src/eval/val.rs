@@ -36,6 +36,13 @@ impl Val {
         }
     }
 
+    pub fn into_duration(self) -> Option<i128> {
+        match self {
+            Self::Duration(d) => Some(d),
+            _ => None,
+        }
+    }
+
     pub fn unwrap_int(self) -> i128 {
         match self {
             Self::Int(i) => i,
@@ -70,4 +77,14 @@ impl Val {
             _ => panic!("Expected val of type 'range', found '{}'", self.data_type()),
         }
     }
+
+    pub fn unwrap_duration(self) -> i128 {
+        match self {
+            Self::Duration(d) => d,
+            _ => panic!(
+                "Expected val of type 'duration', found '{}'",
+                self.data_type()
+            ),
+        }
+    }
 }
@@ -120,6 +120,11 @@ impl Context {
     pub fn lex(&mut self, string: &str) -> crate::Result<Vec<Token>> {
         let mut lexer = Lexer::new(string);
 
+        // skip shebang line of executable scripts
+        if string.starts_with("#!") {
+            self.line_comment(&mut lexer)?;
+        }
+
         while let Some(c) = lexer.next() {
             let span = Span::from(lexer.pos());
             match c {
@@ -225,11 +230,14 @@ impl Context {
                         let s = Span::new(span.start, lexer.end_pos());
                         self.new_atom(&mut lexer, Token::op(op, s))?;
                     }
+                    // the fractional part of a float or a duration component like `1m0.5s`
                     Some(c)
-                        if lexer
-                            .literal()
-                            .map(|l| l.chars().all(|c| c.is_ascii_digit()) && c.is_ascii_digit())
-                            .unwrap_or(false) =>
+                        if c.is_ascii_digit()
+                            && lexer.literal().is_some_and(|l| {
+                                l.starts_with(|c: char| c.is_ascii_digit())
+                                    && l.ends_with(|c: char| c.is_ascii_digit())
+                                    && !l.contains('.')
+                            }) =>
                     {
                         lexer.continue_literal();
                     }
@@ -540,10 +548,16 @@ fn parse_num_literal(literal: &str, span: Span) -> crate::Result<ValSpan> {
                 let num = parse_prefixed_integer_literal::<4>(chars, span)?;
                 return Ok(ValSpan::new(Val::Int(num), span));
             }
+            Some((_, '.')) if is_fractional_duration(literal) => {
+                return parse_duration_literal(literal, span);
+            }
             Some((_, '.')) => {
                 let num = parse_float(literal, span)?;
                 return Ok(ValSpan::new(Val::Float(num), span));
             }
+            Some((_, 'n' | 'u' | 'm' | 's' | 'h')) => {
+                return parse_duration_literal(literal, span);
+            }
             Some((_, radix)) => {
                 return Err(crate::Error::InvalidIntRadix(
                     radix,
@@ -577,6 +591,9 @@ fn parse_num_literal(literal: &str, span: Span) -> crate::Result<ValSpan> {
                     }
                 }
             }
+            '.' if is_fractional_duration(literal) => {
+                return parse_duration_literal(literal, span);
+            }
             '.' => {
                 let num = parse_float(literal, span)?;
                 return Ok(ValSpan::new(Val::Float(num), span));
@@ -598,6 +615,9 @@ fn parse_num_literal(literal: &str, span: Span) -> crate::Result<ValSpan> {
                 last_underscore = true;
                 continue;
             }
+            'n' | 'u' | 'm' | 's' | 'h' => {
+                return parse_duration_literal(literal, span);
+            }
             _ => {
                 let span = Span::from(span.start.plus(i as u32));
                 return Err(crate::Error::InvalidNumChar(c, span));
@@ -616,6 +636,111 @@ fn parse_num_literal(literal: &str, span: Span) -> crate::Result<ValSpan> {
     Ok(ValSpan::new(Val::Int(int_accum), span))
 }
 
+/// Whether a literal containing a `.` is a duration like `1.5s` instead of a float.
+fn is_fractional_duration(literal: &str) -> bool {
+    literal.ends_with(['s', 'm', 'h'])
+}
+
+/// Parse a duration literal made up of one or more components like `1m30s` or `1.5s` into
+/// nanoseconds.
+fn parse_duration_literal(literal: &str, span: Span) -> crate::Result<ValSpan> {
+    let mut chars = literal.char_indices().peekable();
+    let mut nanos: i128 = 0;
+
+    while chars.peek().is_some() {
+        let mut num: i128 = 0;
+        let mut fraction: i128 = 0;
+        // the number of fractional digits, if there is a `.`
+        let mut fraction_digits: Option<u32> = None;
+        while let Some((i, c)) =
+            chars.next_if(|(_, c)| c.is_ascii_digit() || *c == '_' || *c == '.')
+        {
+            match c {
+                '_' => (),
+                '.' if fraction_digits.is_none() => fraction_digits = Some(0),
+                '.' => {
+                    let span = Span::from(span.start.plus(i as u32));
+                    return Err(crate::Error::InvalidNumChar(c, span));
+                }
+                _ => {
+                    let digit = (c as u32 - '0' as u32) as i128;
+                    let n = match &mut fraction_digits {
+                        Some(digits) => {
+                            *digits += 1;
+                            &mut fraction
+                        }
+                        None => &mut num,
+                    };
+                    *n = match n.checked_mul(10).and_then(|n| n.checked_add(digit)) {
+                        Some(n) => n,
+                        None => return Err(crate::Error::DurationOverflow(span)),
+                    };
+                }
+            }
+
+            if chars.peek().is_none() {
+                let span = Span::from(span.start.plus(i as u32 + 1));
+                return Err(crate::Error::MissingDurationUnit(span));
+            }
+        }
+
+        let Some(&(unit_start, _)) = chars.peek() else {
+            break;
+        };
+        let mut unit_end = unit_start;
+        while let Some((i, c)) = chars.next_if(|(_, c)| !c.is_ascii_digit()) {
+            unit_end = i + c.len_utf8();
+        }
+
+        let unit = &literal[unit_start..unit_end];
+        let factor: i128 = match unit {
+            "ns" => 1,
+            "us" => 1_000,
+            "ms" => 1_000_000,
+            "s" => 1_000_000_000,
+            "m" => 60_000_000_000,
+            "h" => 3_600_000_000_000,
+            _ => {
+                let start = span
+                    .start
+                    .plus(literal[..unit_start].chars().count() as u32);
+                let end = start.plus(unit.chars().count() as u32);
+                return Err(crate::Error::InvalidDurationUnit(
+                    unit.to_owned(),
+                    Span::new(start, end),
+                ));
+            }
+        };
+
+        let fraction_nanos = match fraction_digits {
+            Some(digits) => {
+                let Some(divisor) = 10i128.checked_pow(digits) else {
+                    return Err(crate::Error::DurationTooPrecise(span));
+                };
+                let Some(f) = fraction.checked_mul(factor) else {
+                    return Err(crate::Error::DurationOverflow(span));
+                };
+                if f % divisor != 0 {
+                    return Err(crate::Error::DurationTooPrecise(span));
+                }
+                f / divisor
+            }
+            None => 0,
+        };
+
+        nanos = match num
+            .checked_mul(factor)
+            .and_then(|n| n.checked_add(fraction_nanos))
+            .and_then(|n| nanos.checked_add(n))
+        {
+            Some(n) => n,
+            None => return Err(crate::Error::DurationOverflow(span)),
+        };
+    }
+
+    Ok(ValSpan::new(Val::Duration(nanos), span))
+}
+
 fn parse_prefixed_integer_literal<const BITS: u32>(
     mut chars: impl Iterator<Item = (usize, char)>,
     span: Span,
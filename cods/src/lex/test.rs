@@ -237,6 +237,63 @@ fn integer_literal_hex() {
     );
 }
 
+#[test]
+fn duration_literal() {
+    assert(
+        "50ms",
+        vec![Token::val(Val::Duration(50_000_000), Span::cols(0, 0, 4))],
+    );
+    assert(
+        "1m30s",
+        vec![Token::val(
+            Val::Duration(90_000_000_000),
+            Span::cols(0, 0, 5),
+        )],
+    );
+    assert(
+        "0s",
+        vec![Token::val(Val::Duration(0), Span::cols(0, 0, 2))],
+    );
+    assert(
+        "1h1_000us2ns",
+        vec![Token::val(
+            Val::Duration(3_600_001_000_002),
+            Span::cols(0, 0, 12),
+        )],
+    );
+    assert(
+        "1.5s",
+        vec![Token::val(
+            Val::Duration(1_500_000_000),
+            Span::cols(0, 0, 4),
+        )],
+    );
+    assert(
+        "1m0.25s",
+        vec![Token::val(
+            Val::Duration(60_250_000_000),
+            Span::cols(0, 0, 7),
+        )],
+    );
+}
+
+#[test]
+fn duration_literal_errors() {
+    assert_err(
+        "3min",
+        crate::Error::InvalidDurationUnit("min".into(), Span::cols(0, 1, 4)),
+    );
+    assert_err("1m30", crate::Error::MissingDurationUnit(Span::pos(0, 4)));
+    assert_err(
+        "99999999999999999999999999999h",
+        crate::Error::DurationOverflow(Span::cols(0, 0, 30)),
+    );
+    assert_err(
+        "1.5ns",
+        crate::Error::DurationTooPrecise(Span::cols(0, 0, 5)),
+    );
+}
+
 #[test]
 fn integer_float_literal() {
     assert(
@@ -283,3 +340,14 @@ fn float_literals() {
         vec![Token::val(Val::Float(1234e+3), Span::cols(0, 0, 7))],
     );
 }
+
+#[test]
+fn shebang() {
+    assert(
+        "#!/usr/bin/env -S cods run\n3",
+        vec![
+            Token::pct(PctT::Newln, Span::pos(0, 26)),
+            Token::val(Val::Int(3), Span::pos(1, 0)),
+        ],
+    );
+}
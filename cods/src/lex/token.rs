@@ -130,6 +130,8 @@ pub enum Val {
     Char(char),
     Str(String),
     Range(Range),
+    /// A duration in nanoseconds.
+    Duration(i128),
     Unit,
 }
 
@@ -142,11 +144,41 @@ impl Display for Val {
             Self::Char(v) => write!(f, "{v}"),
             Self::Str(v) => write!(f, "{v}"),
             Self::Range(v) => write!(f, "{v}"),
+            Self::Duration(v) => fmt_duration(f, *v),
             Self::Unit => write!(f, "()"),
         }
     }
 }
 
+/// Format a duration in the same notation duration literals are written in, e.g. `1m30s`.
+fn fmt_duration(f: &mut fmt::Formatter<'_>, nanos: i128) -> fmt::Result {
+    const UNITS: [(&str, u128); 6] = [
+        ("h", 3_600_000_000_000),
+        ("m", 60_000_000_000),
+        ("s", 1_000_000_000),
+        ("ms", 1_000_000),
+        ("us", 1_000),
+        ("ns", 1),
+    ];
+
+    if nanos == 0 {
+        return write!(f, "0s");
+    }
+    if nanos < 0 {
+        write!(f, "-")?;
+    }
+
+    let mut rest = nanos.unsigned_abs();
+    for (unit, factor) in UNITS {
+        let n = rest / factor;
+        if n > 0 {
+            write!(f, "{n}{unit}")?;
+        }
+        rest %= factor;
+    }
+    Ok(())
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Range {
     Exclusive(i128, i128),
@@ -5,6 +5,7 @@ pub use group::*;
 pub use ident::*;
 pub use lex::*;
 pub use parse::*;
+pub use program::*;
 pub use span::*;
 
 mod check;
@@ -14,6 +15,7 @@ mod group;
 mod ident;
 mod lex;
 mod parse;
+mod program;
 mod span;
 
 #[derive(Clone, Debug, Default)]
@@ -21,6 +23,8 @@ pub struct Context {
     pub idents: Idents,
     pub errors: Vec<crate::Error>,
     pub warnings: Vec<crate::Warning>,
+    /// Definitions and references of variables and functions found while checking.
+    pub symbols: Vec<Symbol>,
 }
 
 impl Context {
@@ -28,6 +32,7 @@ impl Context {
         self.idents.clear();
         self.errors.clear();
         self.warnings.clear();
+        self.symbols.clear();
     }
 
     pub fn clear_errors(&mut self) {
@@ -36,17 +41,25 @@ impl Context {
     }
 
     pub fn parse_and_eval(&mut self, input: &str) -> crate::Result<Val> {
+        self.compile(input)?.eval()
+    }
+
+    pub fn parse_and_eval_with(
+        &mut self,
+        checker: &mut Checker,
+        stack: &mut Stack,
+        input: &str,
+    ) -> crate::Result<Val> {
         let tokens = self.lex(input.as_ref())?;
         let items = self.group(tokens)?;
         let csts = self.parse(items)?;
 
-        let mut checker = Checker::default();
-        let asts = self.check_with(&mut checker, csts)?;
+        let asts = self.check_with(checker, csts)?;
         if !self.errors.is_empty() {
             return Err(self.errors.remove(0));
         }
 
-        let val = eval::eval(&checker.funs, &asts)?;
+        let val = eval::eval_with(stack, &checker.funs, &asts)?;
         Ok(val)
     }
 }
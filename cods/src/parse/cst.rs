@@ -205,6 +205,8 @@ impl ForLoop {
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct FunDef {
+    /// The span of the contextual `test` keyword.
+    pub test: Option<Span>,
     pub fn_kw: Kw,
     pub ident: IdentSpan,
     pub params: FunParams,
@@ -257,6 +259,7 @@ impl ReturnType {
 
 impl FunDef {
     pub fn new(
+        test: Option<Span>,
         fn_kw: Kw,
         ident: IdentSpan,
         params: FunParams,
@@ -264,6 +267,7 @@ impl FunDef {
         block: Block,
     ) -> Self {
         Self {
+            test,
             fn_kw,
             ident,
             params,
@@ -274,7 +278,8 @@ impl FunDef {
     }
 
     pub fn span(&self) -> Span {
-        Span::across(self.fn_kw.span, self.block.r_par.span)
+        let start = self.test.unwrap_or(self.fn_kw.span);
+        Span::across(start, self.block.r_par.span)
     }
 }
 
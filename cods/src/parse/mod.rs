@@ -78,7 +78,16 @@ impl Context {
             }
             Some(Item::Ident(_)) => {
                 let i = parser.next().unwrap().into_ident().unwrap();
-                Cst::Ident(i)
+                match parser.peek() {
+                    // `test` is only a keyword in front of `fn`
+                    Some(&Item::Kw(k))
+                        if k.typ == KwT::Fn && self.idents.name(i.ident) == "test" =>
+                    {
+                        parser.next();
+                        self.parse_fun_def(parser, k, Some(i.span))?
+                    }
+                    _ => Cst::Ident(i),
+                }
             }
             Some(&Item::Op(o)) => {
                 parser.next();
@@ -270,6 +279,75 @@ impl Context {
         Ok(cst::FunArgs::new(group.l_par, group.r_par, args))
     }
 
+    fn parse_fun_def(
+        &mut self,
+        parser: &mut Parser,
+        fn_kw: Kw,
+        test: Option<Span>,
+    ) -> crate::Result<Cst> {
+        let ident = parser.expect_ident()?;
+
+        let name = self.idents.name(ident.ident);
+        if name.parse::<BuiltinFun>().is_ok() {
+            self.errors.push(crate::Error::RedefinedBuiltinFun(
+                name.to_owned(),
+                Some(ident.span),
+            ));
+        }
+
+        let param_group = parser.expect_fun_pars()?;
+        let params = {
+            let s = param_group.span();
+            let mut parser = Parser::new(param_group.items, s.start);
+            let mut params = Vec::new();
+            while let Some(i) = parser.next() {
+                let s = i.span();
+
+                let ident = match i {
+                    Item::Ident(id) => id,
+                    _ => return Err(crate::Error::ExpectedIdent(s)),
+                };
+
+                let colon = parser.expect_pct(PctT::Colon)?;
+                let typ = match self.parse_bp(&mut parser, 0, StopOn::Comma)? {
+                    Cst::Empty(s) => return Err(crate::Error::ExpectedType(s)),
+                    c => c,
+                };
+
+                params.push(cst::FunParam::new(ident, colon, typ));
+
+                match parser.next() {
+                    Some(i) if i.is_comma() => (),
+                    Some(i) => {
+                        let s = i.span().before();
+                        return Err(crate::Error::ExpectedPct(PctT::Comma, s));
+                    }
+                    None => break,
+                }
+            }
+            cst::FunParams::new(param_group.l_par, param_group.r_par, params)
+        };
+
+        let mut return_type = None;
+        if let Some(&Item::Pct(p)) = parser.peek() {
+            if let PctT::Arrow = p.typ {
+                parser.next();
+
+                let t = match self.parse_bp(parser, 0, StopOn::LCurly)? {
+                    Cst::Empty(s) => return Err(crate::Error::ExpectedType(s)),
+                    c => c,
+                };
+                return_type = Some(cst::ReturnType::new(p, Box::new(t)));
+            }
+        }
+
+        let block_group = parser.expect_block()?;
+        let block = self.parse_block(block_group)?;
+
+        let fun = cst::FunDef::new(test, fn_kw, ident, params, return_type, block);
+        Ok(Cst::FunDef(fun))
+    }
+
     fn parse_lang_construct(
         &mut self,
         parser: &mut Parser,
@@ -357,69 +435,7 @@ impl Context {
                 Ok(Cst::ForLoop(for_loop))
             }
             KwT::In => Err(crate::Error::WrongContext(kw)),
-            KwT::Fn => {
-                let ident = parser.expect_ident()?;
-
-                let name = self.idents.name(ident.ident);
-                if name.parse::<BuiltinFun>().is_ok() {
-                    self.errors.push(crate::Error::RedefinedBuiltinFun(
-                        name.to_owned(),
-                        ident.span,
-                    ));
-                }
-
-                let param_group = parser.expect_fun_pars()?;
-                let params = {
-                    let s = param_group.span();
-                    let mut parser = Parser::new(param_group.items, s.start);
-                    let mut params = Vec::new();
-                    while let Some(i) = parser.next() {
-                        let s = i.span();
-
-                        let ident = match i {
-                            Item::Ident(id) => id,
-                            _ => return Err(crate::Error::ExpectedIdent(s)),
-                        };
-
-                        let colon = parser.expect_pct(PctT::Colon)?;
-                        let typ = match self.parse_bp(&mut parser, 0, StopOn::Comma)? {
-                            Cst::Empty(s) => return Err(crate::Error::ExpectedType(s)),
-                            c => c,
-                        };
-
-                        params.push(cst::FunParam::new(ident, colon, typ));
-
-                        match parser.next() {
-                            Some(i) if i.is_comma() => (),
-                            Some(i) => {
-                                let s = i.span().before();
-                                return Err(crate::Error::ExpectedPct(PctT::Comma, s));
-                            }
-                            None => break,
-                        }
-                    }
-                    cst::FunParams::new(param_group.l_par, param_group.r_par, params)
-                };
-
-                let mut return_type = None;
-                if let Some(&Item::Pct(p)) = parser.peek() {
-                    if let PctT::Arrow = p.typ {
-                        parser.next();
-
-                        let t = match self.parse_bp(parser, 0, StopOn::LCurly)? {
-                            Cst::Empty(s) => return Err(crate::Error::ExpectedType(s)),
-                            c => c,
-                        };
-                        return_type = Some(cst::ReturnType::new(p, Box::new(t)));
-                    }
-                }
-
-                let block_group = parser.expect_block()?;
-                let block = self.parse_block(block_group)?;
-
-                let fun = cst::FunDef::new(kw, ident, params, return_type, block);
-                Ok(Cst::FunDef(fun))
-            }
+            KwT::Fn => self.parse_fun_def(parser, kw, None),
             KwT::Return => {
                 parser.eat_newlns();
                 let val = if parser.current_newln {
@@ -26,6 +26,7 @@ impl Parser {
         !self.current_newln
     }
 
+    #[allow(clippy::should_implement_trait)]
     pub fn next(&mut self) -> Option<Item> {
         while let Some(i) = self.items.pop_front() {
             if self.next_item(&i) {
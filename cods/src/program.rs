@@ -0,0 +1,105 @@
+use crate::{Asts, Checker, Context, DebugInfo, Env, Funs, Stack, Val, VarRef};
+
+/// A checked script that can be evaluated many times without lexing, parsing and checking it
+/// again. Programs are `Send + Sync`, so they can be shared between threads, all mutable state
+/// of an evaluation is confined to the [`Stack`].
+#[derive(Clone, Debug)]
+pub struct Program {
+    pub asts: Asts,
+    pub funs: Funs,
+    /// Names of the variables in the global scope, including the ones defined by the host.
+    pub globals: Vec<(String, VarRef)>,
+    /// The initial values of the globals defined by the host.
+    pub host_globals: Vec<(VarRef, Val)>,
+    pub debug: DebugInfo,
+}
+
+impl Context {
+    pub fn compile(&mut self, input: &str) -> crate::Result<Program> {
+        self.compile_with(Checker::default(), input)
+    }
+
+    /// Compile the input with a checker that might already have host functions or globals
+    /// defined.
+    pub fn compile_with(&mut self, mut checker: Checker, input: &str) -> crate::Result<Program> {
+        let tokens = self.lex(input)?;
+        let items = self.group(tokens)?;
+        let csts = self.parse(items)?;
+
+        let asts = self.check_with(&mut checker, csts)?;
+        if !self.errors.is_empty() {
+            return Err(self.errors.remove(0));
+        }
+
+        let globals = checker
+            .scopes
+            .global_vars()
+            .iter()
+            .map(|v| (self.idents.name(v.ident).to_owned(), v.inner))
+            .collect();
+
+        Ok(Program {
+            asts,
+            funs: checker.funs,
+            globals,
+            host_globals: checker.host_globals,
+            debug: checker.debug,
+        })
+    }
+}
+
+impl Program {
+    /// Evaluate the program with a fresh stack.
+    pub fn eval(&self) -> crate::Result<Val> {
+        let mut stack = self.stack();
+        self.eval_with(&mut stack)
+    }
+
+    /// Evaluate the program with a provided stack. Host globals have to be set on the stack
+    /// before evaluating, see [`Program::stack`] which initializes them with the values they
+    /// were defined with, and [`Program::global`].
+    pub fn eval_with(&self, stack: &mut Stack) -> crate::Result<Val> {
+        crate::eval_with(stack, &self.funs, &self.asts)
+    }
+
+    /// Evaluate the program with a provided stack and environment, which allows capturing the
+    /// output of the program.
+    pub fn eval_with_env(&self, stack: &mut Stack, env: &mut Env<'_>) -> crate::Result<Val> {
+        crate::eval_with_env(stack, &self.funs, env, &self.asts)
+    }
+
+    /// Call a top-level function of the program, see [`crate::call`].
+    pub fn call(&self, stack: &mut Stack, name: &str, args: &[Val]) -> crate::Result<Val> {
+        crate::call(stack, &self.funs, name, args)
+    }
+
+    pub fn call_with_env(
+        &self,
+        stack: &mut Stack,
+        env: &mut Env<'_>,
+        name: &str,
+        args: &[Val],
+    ) -> crate::Result<Val> {
+        crate::call_with_env(stack, &self.funs, env, name, args)
+    }
+
+    /// Create a stack with the global frame allocated and the host globals set to their initial
+    /// values, they can be changed before evaluating the program.
+    pub fn stack(&self) -> Stack {
+        let mut stack = Stack::default();
+        stack.resize(self.asts.global_frame_size);
+        for (var, val) in self.host_globals.iter() {
+            stack.set(var, val.clone());
+        }
+        stack
+    }
+
+    /// Find a variable in the global scope by name.
+    pub fn global(&self, name: &str) -> Option<VarRef> {
+        self.globals
+            .iter()
+            .rev()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| *v)
+    }
+}